@@ -0,0 +1,77 @@
+#[macro_use]
+extern crate criterion;
+extern crate nalgebra as na;
+extern crate rand;
+extern crate renderer;
+
+use criterion::{BenchmarkId, Criterion};
+use na::{Point3, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use renderer::material::{MaterialBox, PerfectSpecularMaterial};
+use renderer::object::Object;
+use renderer::ray::Ray;
+use renderer::scene::Scene;
+use renderer::surface::Sphere;
+use std::sync::Arc;
+
+fn random_point<R: Rng>(rng: &mut R, scale: f32) -> Point3<f32> {
+    Point3::new(
+        (rng.gen::<f32>() - 0.5) * scale,
+        (rng.gen::<f32>() - 0.5) * scale,
+        (rng.gen::<f32>() - 0.5) * scale,
+    )
+}
+
+// A cloud of small spheres with a set of rays shot through it.
+fn setup(count: usize) -> (Scene, Vec<Ray>) {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let objects = (0..count)
+        .map(|_| Object {
+            surface: Box::new(Sphere {
+                center: random_point(&mut rng, 10.0),
+                radius: 0.05,
+            }),
+            material: MaterialBox::Reflective(Arc::new(PerfectSpecularMaterial)),
+        })
+        .collect();
+
+    let rays = (0..1000)
+        .map(|_| {
+            let origin = random_point(&mut rng, 2.0) + Vector3::new(0.0, 0.0, -20.0);
+            let target = random_point(&mut rng, 10.0);
+            Ray::new_from_air(origin, (target - origin).normalize())
+        })
+        .collect();
+
+    (Scene::new(objects), rays)
+}
+
+fn intersect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("intersect");
+
+    for &count in &[10, 100, 1000, 10000] {
+        let (scene, rays) = setup(count);
+
+        group.bench_with_input(BenchmarkId::new("bvh", count), &rays, |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|r| scene.intersects(r).is_some())
+                    .count()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("brute_force", count), &rays, |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter(|r| scene.intersects_brute_force(r).is_some())
+                    .count()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, intersect);
+criterion_main!(benches);
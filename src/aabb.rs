@@ -0,0 +1,100 @@
+use na::{Point3, Vector3};
+use ray::Ray;
+use std::f32;
+
+// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    // A box containing nothing, which can be grown to fit other boxes.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Point3<f32>]) -> Aabb {
+        points
+            .iter()
+            .fold(Aabb::empty(), |aabb, point| aabb.grow(point))
+    }
+
+    // The smallest box containing both this box and the given point.
+    pub fn grow(&self, point: &Point3<f32>) -> Aabb {
+        Aabb {
+            min: self.min.inf(point),
+            max: self.max.sup(point),
+        }
+    }
+
+    // The smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Point3<f32> {
+        Point3::from((self.min.coords + self.max.coords) * 0.5)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    // The axis along which the box is longest.
+    pub fn largest_axis(&self) -> usize {
+        self.extent().imax()
+    }
+
+    // Check if a ray passes through the box between its `t_min` and the given
+    // distance. Returns the distance at which the ray enters the box.
+    //
+    // `inv_direction` is the component-wise reciprocal of the ray direction,
+    // which is passed in so that it only has to be computed once per ray.
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        inv_direction: &Vector3<f32>,
+        max_distance: f32,
+    ) -> Option<f32> {
+        // Slab method: clip the ray against the pair of planes on each axis.
+        let mut t_min = ray.t_min;
+        let mut t_max = max_distance;
+
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let t2 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+
+            // Written so that a NaN, from a ray lying exactly in a slab plane,
+            // leaves the bounds untouched.
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+        }
+
+        if t_min <= t_max {
+            Some(t_min)
+        } else {
+            None
+        }
+    }
+}
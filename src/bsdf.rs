@@ -0,0 +1,175 @@
+use na::Vector3;
+use sampling::{cosine_hemisphere, orthonormal_basis};
+use std::f32::consts::PI;
+
+// An orthonormal basis about the shading normal at a point on a surface.
+// BSDFs work in this local frame, where the normal is +z, so that angles to
+// the normal can be read off of the z coordinate.
+#[derive(Clone, Copy, Debug)]
+pub struct ShadingFrame {
+    pub tangent: Vector3<f32>,
+    pub bitangent: Vector3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+impl ShadingFrame {
+    pub fn new(normal: &Vector3<f32>) -> ShadingFrame {
+        let (tangent, bitangent) = orthonormal_basis(normal);
+
+        ShadingFrame {
+            tangent,
+            bitangent,
+            normal: *normal,
+        }
+    }
+
+    pub fn to_local(&self, v: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.normal),
+        )
+    }
+
+    pub fn to_world(&self, v: &Vector3<f32>) -> Vector3<f32> {
+        self.tangent * v.x + self.bitangent * v.y + self.normal * v.z
+    }
+}
+
+// A direction chosen by a BSDF.
+pub struct BsdfSample {
+    // The direction that light arrives from, in the local frame.
+    pub wi: Vector3<f32>,
+
+    // The value of the BSDF for the pair of directions.
+    pub f: Vector3<f32>,
+
+    // The density with which `wi` was chosen, with respect to solid angle.
+    // For specular samples, `f` and `pdf` are only meaningful as a ratio.
+    pub pdf: f32,
+
+    // Whether `wi` was chosen from a specular lobe, which `eval` and `pdf`
+    // can't evaluate.
+    pub specular: bool,
+
+    // The index of refraction on the side of the surface that `wi` leaves
+    // into, if it passes through the surface.
+    pub index_of_refraction: Option<f32>,
+}
+
+// A bidirectional scattering distribution function, which describes how light
+// arriving at a surface from one direction is scattered towards another.
+//
+// Directions are given in the local shading frame and point away from the
+// surface: `wo` towards the viewer, and `wi` towards where light arrives from.
+pub trait Bsdf {
+    // The fraction of light arriving from `wi` which is scattered towards
+    // `wo`, per unit solid angle. Specular lobes are not included.
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32>;
+
+    // The density, with respect to solid angle, with which `sample` would
+    // choose `wi`. Specular lobes are not included.
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32;
+
+    // Choose a direction for light to arrive from, given `u_lobe` to choose
+    // between lobes and `u` to choose within a lobe, all uniformly distributed
+    // in [0, 1).
+    fn sample(&self, wo: &Vector3<f32>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample>;
+
+    // Whether the BSDF only has specular lobes, so that `eval` and `pdf` are
+    // always zero and there is no point in sampling lights.
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+// Whether two directions are on the same side of the surface.
+pub fn same_hemisphere(a: &Vector3<f32>, b: &Vector3<f32>) -> bool {
+    a.z * b.z > 0.0
+}
+
+// Mirror a direction about the normal.
+pub fn reflect(wo: &Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(-wo.x, -wo.y, wo.z)
+}
+
+// Ideal diffuse reflection, which scatters light equally in all directions.
+// Both sides of the surface reflect.
+pub struct LambertianBsdf {
+    pub color: Vector3<f32>,
+}
+
+impl Bsdf for LambertianBsdf {
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        if same_hemisphere(wo, wi) {
+            self.color / PI
+        } else {
+            Vector3::zeros()
+        }
+    }
+
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        if same_hemisphere(wo, wi) {
+            wi.z.abs() / PI
+        } else {
+            0.0
+        }
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, _u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        // Use a cosine rather than uniform distribution, which matches the
+        // cosine term of the rendering equation, so that the directions that
+        // contribute the most are chosen most often.
+        let mut wi = cosine_hemisphere(u);
+        if wo.z < 0.0 {
+            wi.z = -wi.z;
+        }
+
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf,
+            specular: false,
+            index_of_refraction: None,
+        })
+    }
+}
+
+// An ideal mirror.
+pub struct SpecularReflectionBsdf {
+    pub color: Vector3<f32>,
+}
+
+impl Bsdf for SpecularReflectionBsdf {
+    fn eval(&self, _: &Vector3<f32>, _: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::zeros()
+    }
+
+    fn pdf(&self, _: &Vector3<f32>, _: &Vector3<f32>) -> f32 {
+        0.0
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, _u_lobe: f32, _u: (f32, f32)) -> Option<BsdfSample> {
+        let wi = reflect(wo);
+        if wi.z == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.color / wi.z.abs(),
+            pdf: 1.0,
+            specular: true,
+            index_of_refraction: None,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
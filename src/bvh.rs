@@ -0,0 +1,312 @@
+use aabb::Aabb;
+use intersection::Intersection;
+use na::Vector3;
+use ray::Ray;
+use std::f32;
+
+// Leaves are never split once they have this many or fewer primitives and
+// splitting doesn't pay off according to the surface area heuristic.
+const MAX_LEAF_SIZE: usize = 4;
+
+// Number of buckets used to approximate the surface area heuristic.
+const SAH_BUCKETS: usize = 12;
+
+// Relative cost of visiting an interior node compared to intersecting one
+// primitive.
+const TRAVERSAL_COST: f32 = 0.5;
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        // Range into `Bvh::indices`.
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: Aabb,
+        // The left child is always the next node, so only the right child needs
+        // to be stored.
+        right: usize,
+        // Axis the children were split along, used to pick which child to
+        // visit first.
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match *self {
+            Node::Leaf { ref bounds, .. } | Node::Interior { ref bounds, .. } => bounds,
+        }
+    }
+}
+
+// A bounding volume hierarchy over a list of primitives, built using the
+// surface area heuristic. The hierarchy only stores primitive indices, so it
+// can be used for anything that has a bounding box.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+struct Primitive {
+    index: usize,
+    bounds: Aabb,
+    centroid: na::Point3<f32>,
+}
+
+impl Bvh {
+    // Build a hierarchy over primitives with the given bounds. The indices
+    // reported when intersecting are indices into this slice.
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut primitives: Vec<Primitive> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| Primitive {
+                index,
+                bounds: *bounds,
+                centroid: bounds.centroid(),
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: Vec::with_capacity(bounds.len()),
+        };
+
+        if !primitives.is_empty() {
+            bvh.build_recursive(&mut primitives);
+        }
+
+        bvh
+    }
+
+    fn build_recursive(&mut self, primitives: &mut [Primitive]) {
+        let bounds = primitives
+            .iter()
+            .fold(Aabb::empty(), |b, p| b.union(&p.bounds));
+        let centroid_bounds = primitives
+            .iter()
+            .fold(Aabb::empty(), |b, p| b.grow(&p.centroid));
+
+        let axis = centroid_bounds.largest_axis();
+        let min = centroid_bounds.min[axis];
+        let extent = centroid_bounds.max[axis] - min;
+
+        // If every centroid is in the same place there is no way to split the
+        // primitives.
+        if primitives.len() == 1 || extent <= 0.0 {
+            self.push_leaf(bounds, primitives);
+            return;
+        }
+
+        let bucket_of = |p: &Primitive| {
+            let b = ((p.centroid[axis] - min) / extent * SAH_BUCKETS as f32) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bucket_bounds = [Aabb::empty(); SAH_BUCKETS];
+        for p in primitives.iter() {
+            let b = bucket_of(p);
+            counts[b] += 1;
+            bucket_bounds[b] = bucket_bounds[b].union(&p.bounds);
+        }
+
+        // Evaluate the cost of splitting after each bucket, sweeping from both
+        // ends so that each split is computed in constant time.
+        let mut below = [(0usize, 0.0f32); SAH_BUCKETS - 1];
+        let (mut count, mut aabb) = (0, Aabb::empty());
+        for i in 0..SAH_BUCKETS - 1 {
+            count += counts[i];
+            aabb = aabb.union(&bucket_bounds[i]);
+            below[i] = (count, aabb.surface_area());
+        }
+
+        let (mut best_split, mut best_cost) = (0, f32::INFINITY);
+        let (mut count, mut aabb) = (0, Aabb::empty());
+        for i in (1..SAH_BUCKETS).rev() {
+            count += counts[i];
+            aabb = aabb.union(&bucket_bounds[i]);
+
+            let (below_count, below_area) = below[i - 1];
+            let cost = below_count as f32 * below_area + count as f32 * aabb.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        let split_cost = TRAVERSAL_COST + best_cost / bounds.surface_area();
+        let leaf_cost = primitives.len() as f32;
+
+        if primitives.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            self.push_leaf(bounds, primitives);
+            return;
+        }
+
+        let mid = partition(primitives, |p| bucket_of(p) < best_split);
+
+        let node = self.nodes.len();
+        self.nodes.push(Node::Interior {
+            bounds,
+            right: 0,
+            axis,
+        });
+
+        let (left, right) = primitives.split_at_mut(mid);
+        self.build_recursive(left);
+
+        let right_index = self.nodes.len();
+        self.build_recursive(right);
+
+        if let Node::Interior { ref mut right, .. } = self.nodes[node] {
+            *right = right_index;
+        }
+    }
+
+    fn push_leaf(&mut self, bounds: Aabb, primitives: &[Primitive]) {
+        self.nodes.push(Node::Leaf {
+            bounds,
+            first: self.indices.len(),
+            count: primitives.len(),
+        });
+
+        self.indices.extend(primitives.iter().map(|p| p.index));
+    }
+
+    // The bounds of every primitive in the hierarchy, or `None` if it is
+    // empty.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.bounds())
+    }
+
+    // Find the closest intersection of a ray with the primitives. Every
+    // primitive whose bounding box the ray passes through is tested with
+    // `intersects`, which is given the primitive index. Boxes beyond the end
+    // of the ray, or the closest hit so far, are skipped.
+    pub fn intersects<F>(&self, ray: &Ray, mut intersects: F) -> Option<(Intersection, usize)>
+    where
+        F: FnMut(usize) -> Option<Intersection>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_direction = inverse_direction(ray);
+
+        let mut closest: Option<(Intersection, usize)> = None;
+        let mut closest_distance = ray.t_max;
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node
+                .bounds()
+                .intersects(ray, &inv_direction, closest_distance)
+                .is_none()
+            {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for &primitive in &self.indices[first..first + count] {
+                        if let Some(intersection) = intersects(primitive) {
+                            if intersection.distance < closest_distance {
+                                closest_distance = intersection.distance;
+                                closest = Some((intersection, primitive));
+                            }
+                        }
+                    }
+                }
+                Node::Interior { right, axis, .. } => {
+                    // Visit the child closest to the ray origin first, so that
+                    // the farther one is more likely to be culled.
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(index + 1);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    // Check if the ray hits any of the primitives, stopping at the first one
+    // found. Every primitive whose bounding box the ray passes through is
+    // tested with `occluded` until one returns true.
+    pub fn occluded<F>(&self, ray: &Ray, mut occluded: F) -> bool
+    where
+        F: FnMut(usize) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_direction = inverse_direction(ray);
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node
+                .bounds()
+                .intersects(ray, &inv_direction, ray.t_max)
+                .is_none()
+            {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    if self.indices[first..first + count]
+                        .iter()
+                        .any(|&primitive| occluded(primitive))
+                    {
+                        return true;
+                    }
+                }
+                Node::Interior { right, .. } => {
+                    stack.push(right);
+                    stack.push(index + 1);
+                }
+            }
+        }
+
+        false
+    }
+}
+
+// The component-wise reciprocal of the ray direction, used to intersect boxes.
+fn inverse_direction(ray: &Ray) -> Vector3<f32> {
+    Vector3::new(
+        1.0 / ray.direction.x,
+        1.0 / ray.direction.y,
+        1.0 / ray.direction.z,
+    )
+}
+
+// Reorder the slice so that all elements matching the predicate come first,
+// returning the number of matching elements.
+fn partition<T, F>(items: &mut [T], predicate: F) -> usize
+where
+    F: Fn(&T) -> bool,
+{
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    mid
+}
@@ -1,20 +1,99 @@
 use na::{Point3, Vector3};
 use ray::Ray;
 
+// A perspective camera.
 pub struct Camera {
     // Location of the camera within the scene.
-    pub position: Point3<f32>,
+    position: Point3<f32>,
 
-    // Direction that the camera is facing.
-    pub direction: Vector3<f32>,
+    // Orthonormal basis of the camera: the direction it is facing, and the
+    // directions towards the right and top of the image.
+    forward: Vector3<f32>,
+    right: Vector3<f32>,
+    up: Vector3<f32>,
+
+    // Vertical field of view, in radians.
+    vertical_fov: f32,
+
+    // Half the width and height of the image plane at unit distance from the
+    // camera.
+    half_width: f32,
+    half_height: f32,
 }
 
 impl Camera {
+    // Create a camera at `position` looking at `target`. `up` is the direction
+    // that should appear upwards in the image, and only needs to be roughly
+    // perpendicular to the viewing direction.
+    //
+    // `vertical_fov` is the angle between the top and bottom of the image, in
+    // radians, and `aspect_ratio` is the image width divided by its height.
+    pub fn look_at(
+        position: Point3<f32>,
+        target: Point3<f32>,
+        up: Vector3<f32>,
+        vertical_fov: f32,
+        aspect_ratio: f32,
+    ) -> Camera {
+        Camera::new(position, target - position, up, vertical_fov, aspect_ratio)
+    }
+
+    // Create a camera at `position` facing in the given direction. See
+    // `look_at`.
+    pub fn new(
+        position: Point3<f32>,
+        direction: Vector3<f32>,
+        up: Vector3<f32>,
+        vertical_fov: f32,
+        aspect_ratio: f32,
+    ) -> Camera {
+        let forward = direction.normalize();
+        let right = up.cross(&forward).normalize();
+        let up = forward.cross(&right);
+
+        let mut camera = Camera {
+            position,
+            forward,
+            right,
+            up,
+            vertical_fov,
+            half_width: 0.0,
+            half_height: 0.0,
+        };
+        camera.set_aspect_ratio(aspect_ratio);
+
+        camera
+    }
+
+    // Change the aspect ratio of the image, keeping the vertical field of
+    // view.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.half_height = (self.vertical_fov / 2.0).tan();
+        self.half_width = self.half_height * aspect_ratio;
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.position
+    }
+
+    pub fn direction(&self) -> Vector3<f32> {
+        self.forward
+    }
+
+    // The width of the area around `point` seen by a single pixel of an image
+    // `image_height` pixels tall, if it faces the camera.
+    pub fn footprint(&self, point: &Point3<f32>, image_height: usize) -> f32 {
+        let depth = (point - self.position).dot(&self.forward).abs();
+        depth * 2.0 * self.half_height / image_height as f32
+    }
+
     // Returns a ray passing through the specified position, where the domain of
-    // the position is from -1.0 to 1.0.
+    // the position is from -1.0 to 1.0. (-1, -1) is the bottom left corner of
+    // the image.
     pub fn get_ray(&self, x: f32, y: f32) -> Ray {
-        // TODO: This makes assumptions about the field of view.
-        let direction = (self.direction + Vector3::new(x, y, 0.0)).normalize();
+        let direction =
+            (self.forward + self.right * (x * self.half_width) + self.up * (y * self.half_height))
+                .normalize();
 
         Ray::new_from_air(self.position, direction)
     }
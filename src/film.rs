@@ -0,0 +1,236 @@
+use na::{DMatrix, Vector3};
+use render::CropWindow;
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+// The shape of a reconstruction filter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterKind {
+    // Every sample within the radius counts equally.
+    Box,
+
+    // Weights fall off linearly to zero at the radius.
+    Tent,
+
+    // A Gaussian, shifted down so that it reaches zero at the radius.
+    Gaussian,
+
+    // The Mitchell-Netravali cubic with B = C = 1/3, stretched over the
+    // radius. Its negative lobes sharpen the image slightly.
+    Mitchell,
+
+    // A sinc windowed by a wider sinc, with the radius as the number of lobes.
+    Lanczos,
+}
+
+impl FilterKind {
+    // A radius which suits the shape of the filter, in pixels.
+    pub fn default_radius(&self) -> f32 {
+        match *self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FilterKind, String> {
+        match s.to_lowercase().as_str() {
+            "box" => Ok(FilterKind::Box),
+            "tent" | "triangle" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(format!("unknown filter `{}`", s)),
+        }
+    }
+}
+
+// A reconstruction filter, which weights the contribution of each sample to
+// the pixels around it by its distance from their centers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+
+    // The distance from a sample, in pixels, beyond which pixels aren't
+    // affected by it.
+    pub radius: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind) -> Filter {
+        Filter {
+            kind,
+            radius: kind.default_radius(),
+        }
+    }
+
+    // The weight of a sample at an offset from the center of a pixel. The
+    // filters are separable, so this is the product of the two axes.
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x >= self.radius {
+            return 0.0;
+        }
+
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x / self.radius,
+            FilterKind::Gaussian => {
+                const ALPHA: f32 = 2.0;
+                (-ALPHA * x * x).exp() - (-ALPHA * self.radius * self.radius).exp()
+            }
+            FilterKind::Mitchell => mitchell(2.0 * x / self.radius, 1.0 / 3.0, 1.0 / 3.0),
+            FilterKind::Lanczos => sinc(x) * sinc(x / self.radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::new(FilterKind::Box)
+    }
+}
+
+// The Mitchell-Netravali filter over [0, 2).
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    let result = if x < 1.0 {
+        (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b)
+    } else {
+        (-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c)
+    };
+
+    result / 6.0
+}
+
+// The normalized sinc function, sin(πx) / πx.
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// The weighted sum of the samples that fall near each pixel of a region of
+// the image. Positions on the film are in pixels, with pixel (x, y) covering
+// [x, x + 1) × [y, y + 1).
+pub struct Film {
+    filter: Filter,
+    window: CropWindow,
+    color: DMatrix<Vector3<f32>>,
+    weight: DMatrix<f32>,
+}
+
+impl Film {
+    pub fn new(window: CropWindow, filter: Filter) -> Film {
+        Film {
+            filter,
+            window,
+            color: DMatrix::zeros(window.width(), window.height()),
+            weight: DMatrix::zeros(window.width(), window.height()),
+        }
+    }
+
+    // How far outside a region samples can affect pixels, in whole pixels.
+    pub fn margin(&self) -> usize {
+        self.filter.radius.ceil() as usize
+    }
+
+    // Create an empty tile to collect the samples taken within `samples`.
+    // The tile covers every pixel of the film that those samples can affect.
+    pub fn tile(&self, samples: &CropWindow) -> FilmTile {
+        let margin = self.margin();
+        let bounds = CropWindow {
+            x0: samples.x0.saturating_sub(margin).max(self.window.x0),
+            y0: samples.y0.saturating_sub(margin).max(self.window.y0),
+            x1: (samples.x1 + margin).min(self.window.x1),
+            y1: (samples.y1 + margin).min(self.window.y1),
+        };
+
+        FilmTile {
+            filter: self.filter,
+            bounds,
+            color: DMatrix::zeros(bounds.width(), bounds.height()),
+            weight: DMatrix::zeros(bounds.width(), bounds.height()),
+        }
+    }
+
+    // Add the samples collected by a tile to the film.
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for x in 0..tile.bounds.width() {
+            for y in 0..tile.bounds.height() {
+                let position = (
+                    tile.bounds.x0 - self.window.x0 + x,
+                    tile.bounds.y0 - self.window.y0 + y,
+                );
+                self.color[position] += tile.color[(x, y)];
+                self.weight[position] += tile.weight[(x, y)];
+            }
+        }
+    }
+
+    // The filtered image, indexed by (x, y) relative to the corner of the
+    // film's window. Filters with negative lobes can make pixels negative,
+    // which are clamped to zero.
+    pub fn image(&self) -> DMatrix<Vector3<f32>> {
+        self.color.zip_map(&self.weight, |color, weight| {
+            if weight != 0.0 {
+                (color / weight).map(|c| c.max(0.0))
+            } else {
+                Vector3::zeros()
+            }
+        })
+    }
+}
+
+// The samples of one region of the film, which can be collected separately
+// from other regions and merged into the film afterwards.
+pub struct FilmTile {
+    filter: Filter,
+    bounds: CropWindow,
+    color: DMatrix<Vector3<f32>>,
+    weight: DMatrix<f32>,
+}
+
+impl FilmTile {
+    // Add a sample at a position on the film to every pixel whose center is
+    // within the filter radius.
+    pub fn add_sample(&mut self, position: (f32, f32), color: Vector3<f32>) {
+        let radius = self.filter.radius;
+
+        // Pixel centers are at half-pixel offsets.
+        let (px, py) = (position.0 - 0.5, position.1 - 0.5);
+        let x0 = ((px - radius).floor() + 1.0).max(self.bounds.x0 as f32) as usize;
+        let y0 = ((py - radius).floor() + 1.0).max(self.bounds.y0 as f32) as usize;
+        let x1 = ((px + radius).ceil()).min(self.bounds.x1 as f32) as usize;
+        let y1 = ((py + radius).ceil()).min(self.bounds.y1 as f32) as usize;
+
+        for x in x0..x1 {
+            for y in y0..y1 {
+                let weight = self.filter.evaluate(px - x as f32, py - y as f32);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let index = (x - self.bounds.x0, y - self.bounds.y0);
+                self.color[index] += color * weight;
+                self.weight[index] += weight;
+            }
+        }
+    }
+}
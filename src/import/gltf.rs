@@ -0,0 +1,536 @@
+use camera::Camera;
+use gltf::buffer::Source as BufferSource;
+use gltf::camera::Projection;
+use gltf::image::Source as ImageSource;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::texture::{Texture as GltfTexture, WrappingMode};
+use gltf::{Gltf, Material, Mesh, Node};
+use import::{normalize_normals, ImportError};
+use material::{EmissiveMaterial, MaterialBox, PrincipledMaterial};
+use mesh::TriangleMesh;
+use na::{Matrix4, Point2, Point3, Vector3};
+use object::Object;
+use principled::PrincipledParameters;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use surface::{Sphere, Surface};
+use texture::{
+    ConstantTexture, ImageTexture, ScaledTexture, Texture, TextureFilter, VertexColorTexture,
+    WrapMode,
+};
+use transform::{Transform, TransformedSurface};
+
+// Point lights are infinitely small, which the renderer can't sample, so they
+// become spheres of this radius instead.
+const POINT_LIGHT_RADIUS: f32 = 0.01;
+
+// The luminous efficacy used to convert the photometric units of glTF lights
+// to radiometric ones, in lumens per watt.
+const LUMENS_PER_WATT: f32 = 683.0;
+
+// The contents of a glTF file which the renderer can use.
+pub struct ImportedScene {
+    pub objects: Vec<Object>,
+
+    // The first perspective camera in the scene, if there is one.
+    pub camera: Option<ImportedCamera>,
+
+    // Parts of the file which were left out or approximated because the
+    // renderer doesn't support them. They're left to the caller to report.
+    pub warnings: Vec<String>,
+}
+
+pub struct ImportedCamera {
+    pub camera: Camera,
+
+    // The aspect ratio of the image the camera was set up for, if the file
+    // gives one.
+    pub aspect_ratio: Option<f32>,
+}
+
+// Load a glTF file, in either its JSON (.gltf) or binary (.glb) form.
+pub fn load(path: &Path) -> Result<ImportedScene, ImportError> {
+    let contents = fs::read(path)?;
+    parse(&contents, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+// Parse the contents of a glTF file, with buffers and images that aren't
+// embedded in it found relative to `base_dir`.
+//
+// The default scene is imported, or the first scene if there is no default.
+// glTF uses a right-handed coordinate system while the renderer's is
+// left-handed, so the z axis is flipped, which keeps +y up and leaves
+// everything looking as it would in other viewers.
+//
+// Each mesh is loaded once and shared by every node that uses it. Point lights
+// become small emissive spheres, with their intensity converted from candela
+// to watts per steradian. Spot lights become point lights of the same
+// intensity, shining outside of their cone too, while directional lights
+// aren't supported and are left out with a warning, as are primitives which
+// aren't triangles.
+pub fn parse(contents: &[u8], base_dir: &Path) -> Result<ImportedScene, ImportError> {
+    let Gltf { document, blob } = Gltf::from_slice(contents)?;
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| {
+            let data = match buffer.source() {
+                BufferSource::Bin => blob
+                    .clone()
+                    .ok_or_else(|| gltf_error("the file has no binary chunk"))?,
+                BufferSource::Uri(uri) => read_uri(uri, base_dir)?,
+            };
+            if data.len() < buffer.length() {
+                return Err(gltf_error(&format!(
+                    "buffer {} is shorter than its length of {} bytes",
+                    buffer.index(),
+                    buffer.length()
+                )));
+            }
+            Ok(data)
+        })
+        .collect::<Result<Vec<Vec<u8>>, ImportError>>()?;
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| gltf_error("the file has no scenes"))?;
+
+    let mut importer = Importer {
+        buffers,
+        base_dir,
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        scene: ImportedScene {
+            objects: Vec::new(),
+            camera: None,
+            warnings: Vec::new(),
+        },
+    };
+    for node in scene.nodes() {
+        importer.visit(&node, &Matrix4::identity())?;
+    }
+
+    Ok(importer.scene)
+}
+
+// A triangle primitive of a mesh, ready to be placed in the scene by any
+// number of nodes.
+struct Primitive {
+    surface: Arc<dyn Surface>,
+    material: MaterialBox,
+}
+
+struct Importer<'a> {
+    buffers: Vec<Vec<u8>>,
+    base_dir: &'a Path,
+
+    // Meshes by index, and materials by index, or `None` for the default
+    // material, and whether they're used with vertex colors.
+    meshes: HashMap<usize, Arc<Vec<Primitive>>>,
+    materials: HashMap<(Option<usize>, bool), MaterialBox>,
+
+    scene: ImportedScene,
+}
+
+impl<'a> Importer<'a> {
+    // Import a node and its children, where `parent` is the transform from the
+    // parent node to the scene, in glTF coordinates.
+    fn visit(&mut self, node: &Node, parent: &Matrix4<f32>) -> Result<(), ImportError> {
+        let to_scene = parent * Matrix4::from(node.transform().matrix());
+
+        // Flipping the z axis both before and after the transform gives the
+        // same transform in the renderer's coordinates, which applies to the
+        // flipped meshes.
+        let flip = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.0, -1.0));
+        match Transform::new(flip * to_scene * flip) {
+            Some(transform) => self.place(node, &transform)?,
+            // Nodes are sometimes hidden by scaling them to nothing.
+            None => return Ok(()),
+        }
+
+        for child in node.children() {
+            self.visit(&child, &to_scene)?;
+        }
+
+        Ok(())
+    }
+
+    // Add the mesh, camera and light of a node to the scene.
+    fn place(&mut self, node: &Node, transform: &Transform) -> Result<(), ImportError> {
+        if let Some(mesh) = node.mesh() {
+            let primitives = self.mesh(&mesh)?;
+            for primitive in primitives.iter() {
+                self.scene.objects.push(Object {
+                    surface: Box::new(TransformedSurface::new(
+                        primitive.surface.clone(),
+                        *transform,
+                    )),
+                    material: primitive.material.clone(),
+                });
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            match camera.projection() {
+                Projection::Perspective(perspective) => {
+                    if self.scene.camera.is_none() {
+                        // Cameras look down -z, which is +z once flipped.
+                        self.scene.camera = Some(ImportedCamera {
+                            camera: Camera::new(
+                                transform.point(&Point3::origin()),
+                                transform.vector(&Vector3::z()),
+                                transform.vector(&Vector3::y()),
+                                perspective.yfov(),
+                                perspective.aspect_ratio().unwrap_or(1.0),
+                            ),
+                            aspect_ratio: perspective.aspect_ratio(),
+                        });
+                    }
+                }
+                Projection::Orthographic(_) => {
+                    self.warn("orthographic cameras aren't supported".to_string());
+                }
+            }
+        }
+
+        if let Some(light) = node.light() {
+            let point = match light.kind() {
+                Kind::Point => true,
+                // The renderer has no lights which only shine within a cone,
+                // so spot lights shine in every direction instead.
+                Kind::Spot { .. } => {
+                    self.warn(format!(
+                        "spot light {} is rendered as a point light",
+                        light.index()
+                    ));
+                    true
+                }
+                Kind::Directional => {
+                    self.warn("directional lights aren't supported".to_string());
+                    false
+                }
+            };
+            if point {
+                let intensity = light.intensity() / LUMENS_PER_WATT;
+                let radiance =
+                    Vector3::from(light.color()) * intensity / (PI * POINT_LIGHT_RADIUS.powi(2));
+                self.scene.objects.push(Object {
+                    surface: Box::new(Sphere {
+                        center: transform.point(&Point3::origin()),
+                        radius: POINT_LIGHT_RADIUS,
+                    }),
+                    material: MaterialBox::Emissive(Arc::new(EmissiveMaterial {
+                        emission: Box::new(ConstantTexture { color: radiance }),
+                        one_sided: false,
+                    })),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // The triangle primitives of a mesh.
+    fn mesh(&mut self, mesh: &Mesh) -> Result<Arc<Vec<Primitive>>, ImportError> {
+        if let Some(primitives) = self.meshes.get(&mesh.index()) {
+            return Ok(primitives.clone());
+        }
+
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            let context = format!("mesh {} primitive {}", mesh.index(), primitive.index());
+            if primitive.mode() != Mode::Triangles {
+                self.warn(format!("{} isn't made of triangles", context));
+                continue;
+            }
+
+            let buffers = &self.buffers;
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+            // The z axis is flipped, along with the winding order of the
+            // triangles so that they still face the same way.
+            let positions: Vec<Point3<f32>> = reader
+                .read_positions()
+                .ok_or_else(|| gltf_error(&format!("{} has no positions", context)))?
+                .map(|p| Point3::new(p[0], p[1], -p[2]))
+                .collect();
+            if let Some(i) = positions
+                .iter()
+                .position(|p| !p.iter().all(|c| c.is_finite()))
+            {
+                return Err(gltf_error(&format!(
+                    "{} vertex {}: position must be finite",
+                    context, i
+                )));
+            }
+            let normals: Vec<Vector3<f32>> = reader
+                .read_normals()
+                .map(|normals| normals.map(|n| Vector3::new(n[0], n[1], -n[2])).collect())
+                .unwrap_or_default();
+            // Texture coordinates have v increasing down the image.
+            let uvs: Vec<Point2<f32>> = reader
+                .read_tex_coords(0)
+                .map(|uvs| {
+                    uvs.into_f32()
+                        .map(|uv| Point2::new(uv[0], 1.0 - uv[1]))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let colors: Option<Vec<Vector3<f32>>> = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgb_f32().map(Vector3::from).collect());
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+
+            if !indices.len().is_multiple_of(3) {
+                return Err(gltf_error(&format!(
+                    "{} has a number of indices which isn't a multiple of three",
+                    context
+                )));
+            }
+            if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+                return Err(gltf_error(&format!(
+                    "{} refers to vertex {}, but there are only {}",
+                    context,
+                    index,
+                    positions.len()
+                )));
+            }
+            let attributes = [
+                ("normals", normals.len()),
+                ("texture coordinates", uvs.len()),
+                ("colors", colors.as_ref().map_or(0, Vec::len)),
+            ];
+            for &(name, count) in &attributes {
+                if count != 0 && count != positions.len() {
+                    return Err(gltf_error(&format!(
+                        "{} has {} {} for {} vertices",
+                        context,
+                        count,
+                        name,
+                        positions.len()
+                    )));
+                }
+            }
+
+            let normals = normalize_normals(normals, &context, &mut self.scene.warnings);
+            let triangles = indices.chunks(3).map(|t| [t[0], t[2], t[1]]).collect();
+            let mut triangle_mesh = TriangleMesh::new(positions, normals, uvs, triangles);
+            let vertex_colors = colors.is_some();
+            if let Some(colors) = colors {
+                triangle_mesh = triangle_mesh.with_colors(colors);
+            }
+
+            primitives.push(Primitive {
+                surface: Arc::new(triangle_mesh),
+                material: self.material(&primitive.material(), vertex_colors),
+            });
+        }
+
+        let primitives = Arc::new(primitives);
+        self.meshes.insert(mesh.index(), primitives.clone());
+
+        Ok(primitives)
+    }
+
+    // A material of the file, which is emissive if it has an emissive color
+    // and otherwise uses the principled BSDF, which covers the metallic
+    // roughness model of glTF. The base color is multiplied by the vertex
+    // colors of meshes which have them.
+    fn material(&mut self, material: &Material, vertex_colors: bool) -> MaterialBox {
+        let key = (material.index(), vertex_colors);
+        if let Some(converted) = self.materials.get(&key) {
+            return converted.clone();
+        }
+
+        let emission =
+            Vector3::from(material.emissive_factor()) * material.emissive_strength().unwrap_or(1.0);
+        let converted = if emission != Vector3::zeros() {
+            let texture = material
+                .emissive_texture()
+                .and_then(|info| self.texture(&info.texture(), true));
+            MaterialBox::Emissive(Arc::new(EmissiveMaterial {
+                emission: scaled(texture, emission),
+                one_sided: !material.double_sided(),
+            }))
+        } else {
+            let pbr = material.pbr_metallic_roughness();
+            let factor = pbr.base_color_factor();
+            let base_color = Vector3::new(factor[0], factor[1], factor[2]);
+
+            let mut texture = pbr
+                .base_color_texture()
+                .and_then(|info| self.texture(&info.texture(), true));
+            if texture.is_none() && vertex_colors {
+                texture = Some(Box::new(VertexColorTexture {
+                    fallback: Vector3::repeat(1.0),
+                }));
+            }
+
+            // Transmission and the index of refraction come from extensions,
+            // with the same defaults as the principled BSDF otherwise.
+            let defaults = PrincipledParameters::default();
+            let index_of_refraction = material.ior().unwrap_or(defaults.index_of_refraction);
+            let reflectance = ((index_of_refraction - 1.0) / (index_of_refraction + 1.0)).powi(2);
+            let parameters = PrincipledParameters {
+                base_color,
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                // A specular of 0.5 is a reflectance of 4% at normal incidence.
+                specular: (reflectance / 0.08).min(1.0),
+                transmission: material
+                    .transmission()
+                    .map_or(0.0, |t| t.transmission_factor()),
+                index_of_refraction,
+                ..defaults
+            };
+
+            MaterialBox::Reflective(Arc::new(PrincipledMaterial {
+                parameters,
+                base_color: texture.map(|texture| scaled(Some(texture), base_color)),
+                metallic_roughness: pbr
+                    .metallic_roughness_texture()
+                    .and_then(|info| self.texture(&info.texture(), false)),
+            }))
+        };
+
+        self.materials.insert(key, converted.clone());
+
+        converted
+    }
+
+    // Load the image of a texture, decoding its colors from sRGB if `srgb` is
+    // set. As with OBJ files, a texture which can't be loaded isn't an error,
+    // and the material goes without it instead.
+    fn texture(&mut self, texture: &GltfTexture, srgb: bool) -> Option<Box<dyn Texture>> {
+        let image = texture.source();
+        let contents = match image.source() {
+            ImageSource::View { view, .. } => {
+                let start = view.offset();
+                start
+                    .checked_add(view.length())
+                    .and_then(|end| self.buffers.get(view.buffer().index())?.get(start..end))
+                    .map(<[u8]>::to_vec)
+                    .ok_or_else(|| {
+                        gltf_error(&format!("buffer view {} is out of bounds", view.index()))
+                    })
+            }
+            ImageSource::Uri { uri, .. } => read_uri(uri, self.base_dir),
+        };
+
+        let wrap = match texture.sampler().wrap_s() {
+            WrappingMode::Repeat => WrapMode::Repeat,
+            WrappingMode::MirroredRepeat => WrapMode::Mirror,
+            WrappingMode::ClampToEdge => WrapMode::Clamp,
+        };
+        let result = contents.and_then(|contents| {
+            ImageTexture::decode(&contents, srgb, TextureFilter::Trilinear, wrap)
+                .map_err(|err| gltf_error(&err.to_string()))
+        });
+
+        match result {
+            Ok(texture) => Some(Box::new(texture)),
+            Err(err) => {
+                self.warn(format!("could not load image {}: {}", image.index(), err));
+                None
+            }
+        }
+    }
+    fn warn(&mut self, message: String) {
+        self.scene.warnings.push(message);
+    }
+}
+
+// A texture multiplied by a color, or just the color without a texture.
+fn scaled(texture: Option<Box<dyn Texture>>, color: Vector3<f32>) -> Box<dyn Texture> {
+    match texture {
+        Some(texture) => Box::new(ScaledTexture {
+            texture,
+            scale: color,
+        }),
+        None => Box::new(ConstantTexture { color }),
+    }
+}
+
+// The contents of a buffer or image, either embedded in a data URI or in a
+// file relative to `base_dir`.
+fn read_uri(uri: &str, base_dir: &Path) -> Result<Vec<u8>, ImportError> {
+    if uri.starts_with("data:") {
+        return match uri.find(";base64,") {
+            Some(start) => decode_base64(&uri[start + ";base64,".len()..])
+                .ok_or_else(|| gltf_error("a data URI isn't valid base64")),
+            None => Err(gltf_error("only base64 data URIs are supported")),
+        };
+    }
+    if uri.contains("://") {
+        return Err(gltf_error(&format!("unsupported URI `{}`", uri)));
+    }
+
+    let path = base_dir.join(decode_percent(uri));
+    fs::read(&path).map_err(|err| gltf_error(&format!("{}: {}", path.display(), err)))
+}
+
+// Decode the escaped characters of a relative URI, such as %20 for a space.
+fn decode_percent(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' {
+            uri.get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Decode standard base64, with optional padding, or `None` if it's invalid.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut decoded = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for &c in text {
+        bits = bits << 6 | u32::from(value(c)?);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            decoded.push((bits >> count) as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+fn gltf_error(message: &str) -> ImportError {
+    ImportError::Gltf(message.to_string())
+}
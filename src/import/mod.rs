@@ -0,0 +1,111 @@
+// Loaders for geometry from other file formats.
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+
+use material::MaterialBox;
+use mesh::TriangleMesh;
+use na::Vector3;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use tobj;
+
+// A mesh loaded from a file, along with the material the file assigns to it,
+// if there is one.
+pub struct ImportedMesh {
+    pub name: String,
+    pub mesh: TriangleMesh,
+    pub material: Option<MaterialBox>,
+
+    // Problems with the mesh which didn't stop it from loading, such as
+    // textures which couldn't be found. They're left to the caller to report.
+    pub warnings: Vec<String>,
+}
+
+// Normalize the vertex normals of a mesh. Normals which are zero or not
+// finite have no direction, so if there are any, the mesh is left without
+// normals and shaded with the normals of its triangles instead.
+pub fn normalize_normals(
+    normals: Vec<Vector3<f32>>,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> Vec<Vector3<f32>> {
+    let normalized: Option<Vec<Vector3<f32>>> = normals
+        .iter()
+        .map(|normal| {
+            // Dividing by the largest component first keeps the length from
+            // overflowing.
+            let largest = normal.amax();
+            if normal.iter().all(|c| c.is_finite()) && largest > 0.0 {
+                Some((normal / largest).normalize())
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    normalized.unwrap_or_else(|| {
+        warnings.push(format!(
+            "{} has invalid vertex normals, which are ignored",
+            name
+        ));
+        Vec::new()
+    })
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    // The file could not be read.
+    Io(io::Error),
+
+    // The OBJ file could not be read or parsed.
+    Obj(tobj::LoadError),
+
+    // The PLY, STL or glTF file is malformed, with a message saying where.
+    Ply(String),
+    Stl(String),
+    Gltf(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImportError::Io(ref err) => write!(f, "{}", err),
+            ImportError::Obj(ref err) => write!(f, "{}", err),
+            ImportError::Ply(ref message) => write!(f, "invalid PLY file: {}", message),
+            ImportError::Stl(ref message) => write!(f, "invalid STL file: {}", message),
+            ImportError::Gltf(ref message) => write!(f, "invalid glTF file: {}", message),
+        }
+    }
+}
+
+impl Error for ImportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ImportError::Io(ref err) => Some(err),
+            ImportError::Obj(ref err) => Some(err),
+            ImportError::Ply(_) | ImportError::Stl(_) | ImportError::Gltf(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImportError {
+    fn from(err: io::Error) -> ImportError {
+        ImportError::Io(err)
+    }
+}
+
+impl From<tobj::LoadError> for ImportError {
+    fn from(err: tobj::LoadError) -> ImportError {
+        ImportError::Obj(err)
+    }
+}
+
+impl From<::gltf::Error> for ImportError {
+    fn from(err: ::gltf::Error) -> ImportError {
+        ImportError::Gltf(err.to_string())
+    }
+}
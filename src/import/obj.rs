@@ -0,0 +1,190 @@
+use import::{normalize_normals, ImportError, ImportedMesh};
+use material::{
+    EmissiveMaterial, MaterialBox, PerfectDiffuseMaterial, PerfectRefractiveMaterial,
+    PerfectSpecularMaterial,
+};
+use mesh::TriangleMesh;
+use na::{Point2, Point3, Vector3};
+use std::path::Path;
+use std::sync::Arc;
+use texture::{ConstantTexture, ImageTexture, Texture, TextureFilter, WrapMode};
+use tobj;
+
+// Load every object in an OBJ file as a separate triangle mesh. Polygons are
+// triangulated.
+//
+// A missing or broken MTL file is not an error, since many OBJ files refer to
+// material libraries that aren't distributed with them; the meshes are
+// returned without materials, and with a warning, instead.
+pub fn load(path: &Path) -> Result<Vec<ImportedMesh>, ImportError> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        // Use one index for all vertex attributes, duplicating vertices where
+        // needed, so that the attributes can share the position indices.
+        single_index: true,
+        ..Default::default()
+    };
+
+    let (models, materials) = tobj::load_obj(path, &options)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    models
+        .into_iter()
+        .filter(|model| !model.mesh.indices.is_empty())
+        .map(|model| {
+            let mut warnings = Vec::new();
+            let material = match (model.mesh.material_id, &materials) {
+                (Some(id), Ok(materials)) => materials
+                    .get(id)
+                    .map(|material| convert_material(material, base_dir, &mut warnings)),
+                (Some(_), Err(err)) => {
+                    warnings.push(format!("could not load materials: {}", err));
+                    None
+                }
+                (None, _) => None,
+            };
+
+            Ok(ImportedMesh {
+                mesh: convert_mesh(&model.mesh, &model.name, &mut warnings)?,
+                name: model.name,
+                material,
+                warnings,
+            })
+        })
+        .collect()
+}
+
+fn convert_mesh(
+    mesh: &tobj::Mesh,
+    name: &str,
+    warnings: &mut Vec<String>,
+) -> Result<TriangleMesh, ImportError> {
+    // tobj parses `nan` and `inf` like any other number, but they are as
+    // unusable as a position which can't be parsed at all.
+    if !mesh.positions.iter().all(|c| c.is_finite()) {
+        return Err(ImportError::Obj(tobj::LoadError::PositionParseError));
+    }
+
+    let positions = mesh
+        .positions
+        .chunks(3)
+        .map(|p| Point3::new(p[0], p[1], p[2]))
+        .collect();
+    let normals = mesh
+        .normals
+        .chunks(3)
+        .map(|n| Vector3::new(n[0], n[1], n[2]))
+        .collect();
+    let normals = normalize_normals(normals, name, warnings);
+    let uvs = mesh
+        .texcoords
+        .chunks(2)
+        .map(|t| Point2::new(t[0], t[1]))
+        .collect();
+    let indices = mesh.indices.chunks(3).map(|t| [t[0], t[1], t[2]]).collect();
+
+    Ok(TriangleMesh::new(positions, normals, uvs, indices))
+}
+
+// Parse a color from an MTL parameter that tobj doesn't know about, e.g. `Ke`.
+fn unknown_color(material: &tobj::Material, key: &str) -> Option<[f32; 3]> {
+    let values: Vec<f32> = material
+        .unknown_param
+        .get(key)?
+        .split_whitespace()
+        .map(|v| v.parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    match values[..] {
+        [r, g, b] => Some([r, g, b]),
+        [v] => Some([v, v, v]),
+        _ => None,
+    }
+}
+
+// Pick the closest of the renderer's materials to an MTL material:
+//
+// * Anything with an emission color (`Ke`) is a light of that color.
+// * Illumination models with refraction, or materials that aren't fully
+//   opaque, are refractive with the index of refraction `Ni`.
+// * Illumination models with ray traced reflection are perfect mirrors.
+// * Everything else is diffuse with the diffuse color `Kd`, or the texture
+//   `map_Kd` if there is one. Textures are found relative to `base_dir`, and
+//   those which can't be loaded are added to `warnings`.
+pub fn convert_material(
+    material: &tobj::Material,
+    base_dir: &Path,
+    warnings: &mut Vec<String>,
+) -> MaterialBox {
+    if let Some(emission) = unknown_color(material, "Ke") {
+        if emission.iter().any(|&c| c > 0.0) {
+            return MaterialBox::Emissive(Arc::new(EmissiveMaterial {
+                emission: Box::new(ConstantTexture {
+                    color: Vector3::from(emission),
+                }),
+                one_sided: false,
+            }));
+        }
+    }
+
+    match material.illumination_model {
+        Some(4) | Some(6) | Some(7) | Some(9) => return refractive(material),
+        Some(3) | Some(5) | Some(8) => {
+            return MaterialBox::Reflective(Arc::new(PerfectSpecularMaterial))
+        }
+        _ => {}
+    }
+
+    if material.dissolve < 1.0 {
+        return refractive(material);
+    }
+
+    let kd = material.diffuse;
+    let color: Box<dyn Texture> = match diffuse_texture(material, base_dir, warnings) {
+        Some(texture) => Box::new(texture),
+        None => Box::new(ConstantTexture {
+            color: Vector3::new(kd[0], kd[1], kd[2]),
+        }),
+    };
+
+    MaterialBox::Reflective(Arc::new(PerfectDiffuseMaterial { color }))
+}
+
+// Load the diffuse texture of an MTL material. As with the MTL file itself, a
+// missing texture isn't an error, and the diffuse color is used instead.
+fn diffuse_texture(
+    material: &tobj::Material,
+    base_dir: &Path,
+    warnings: &mut Vec<String>,
+) -> Option<ImageTexture> {
+    if material.diffuse_texture.is_empty() {
+        return None;
+    }
+
+    let path = base_dir.join(&material.diffuse_texture);
+    match ImageTexture::open(&path, TextureFilter::Trilinear, WrapMode::Repeat) {
+        Ok(texture) => Some(texture),
+        Err(err) => {
+            warnings.push(format!(
+                "could not load texture {}: {}",
+                path.display(),
+                err
+            ));
+            None
+        }
+    }
+}
+
+fn refractive(material: &tobj::Material) -> MaterialBox {
+    // Fall back to glass if no index of refraction is given.
+    let ior = if material.optical_density > 1.0 {
+        material.optical_density
+    } else {
+        1.5
+    };
+
+    MaterialBox::Reflective(Arc::new(PerfectRefractiveMaterial {
+        index_of_refraction: ior,
+    }))
+}
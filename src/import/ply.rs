@@ -0,0 +1,523 @@
+use import::{normalize_normals, ImportError, ImportedMesh};
+use material::{MaterialBox, PerfectDiffuseMaterial};
+use mesh::TriangleMesh;
+use na::{Point2, Point3, Vector3};
+use std::fs;
+use std::path::Path;
+use std::str;
+use std::sync::Arc;
+use texture::VertexColorTexture;
+use tonemap::srgb_to_linear;
+
+// Load a PLY file, in either its ASCII or binary form, as a single triangle
+// mesh named after the file.
+pub fn load(path: &Path) -> Result<Vec<ImportedMesh>, ImportError> {
+    let contents = fs::read(path)?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    parse(&contents, &name).map(|mesh| vec![mesh])
+}
+
+// Parse the contents of a PLY file.
+//
+// Vertices need `x`, `y` and `z` properties, and may also have normals (`nx`,
+// `ny` and `nz`), texture coordinates (`u` and `v`, or `s` and `t`) and colors
+// (`red`, `green` and `blue`). Faces list their vertices in
+// `vertex_indices`, and those with more than three are split into triangles
+// around their first vertex. Other elements and properties are skipped.
+// Positions must be finite numbers. Normals are ignored if any of them are zero or not finite.
+//
+// Meshes with vertex colors are given a diffuse material colored by them.
+pub fn parse(contents: &[u8], name: &str) -> Result<ImportedMesh, ImportError> {
+    let (header, body, body_line) = parse_header(contents).map_err(ImportError::Ply)?;
+    let mut reader = match header.format {
+        Format::Ascii => Reader::ascii(body, body_line).map_err(ImportError::Ply)?,
+        Format::BinaryLittleEndian => Reader::binary(body, false),
+        Format::BinaryBigEndian => Reader::binary(body, true),
+    };
+
+    let mut vertices = None;
+    let mut faces = None;
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => vertices = Some(read_vertices(element, &mut reader)?),
+            "face" => faces = Some(read_faces(element, &mut reader)?),
+            _ => {
+                for i in 0..element.count {
+                    for property in &element.properties {
+                        reader
+                            .read_property(property)
+                            .map_err(|err| element_error(element, i, &err))?;
+                    }
+                }
+            }
+        }
+    }
+
+    let vertices = vertices.ok_or_else(|| ImportError::Ply("no vertex element".to_string()))?;
+    let faces = faces.ok_or_else(|| ImportError::Ply("no face element".to_string()))?;
+
+    let mut indices = Vec::new();
+    for (i, face) in faces.iter().enumerate() {
+        if face.len() < 3 {
+            return Err(ImportError::Ply(format!(
+                "face {} has fewer than three vertices",
+                i
+            )));
+        }
+        if let Some(&index) = face
+            .iter()
+            .find(|&&index| index as usize >= vertices.positions.len())
+        {
+            return Err(ImportError::Ply(format!(
+                "face {} refers to vertex {}, but there are only {}",
+                i,
+                index,
+                vertices.positions.len()
+            )));
+        }
+
+        for j in 1..face.len() - 1 {
+            indices.push([face[0], face[j], face[j + 1]]);
+        }
+    }
+
+    let material = if vertices.colors.is_empty() {
+        None
+    } else {
+        Some(MaterialBox::Reflective(Arc::new(PerfectDiffuseMaterial {
+            color: Box::new(VertexColorTexture {
+                fallback: Vector3::repeat(0.8),
+            }),
+        })))
+    };
+
+    let mut warnings = Vec::new();
+    let normals = normalize_normals(vertices.normals, name, &mut warnings);
+    let mesh = TriangleMesh::new(vertices.positions, normals, vertices.uvs, indices);
+
+    Ok(ImportedMesh {
+        name: name.to_string(),
+        mesh: if vertices.colors.is_empty() {
+            mesh
+        } else {
+            mesh.with_colors(vertices.colors)
+        },
+        material,
+        warnings,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // The largest value of an integer type, which colors stored as that type
+    // are scaled by. Colors stored as floating point are already in [0, 1].
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::Int8 => 127.0,
+            ScalarType::UInt8 => 255.0,
+            ScalarType::Int16 => 32767.0,
+            ScalarType::UInt16 => 65535.0,
+            ScalarType::Int32 => 2_147_483_647.0,
+            ScalarType::UInt32 => 4_294_967_295.0,
+            ScalarType::Float32 | ScalarType::Float64 => 1.0,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        self.color_scale() != 1.0
+    }
+}
+
+#[derive(Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    // A list of values, preceded by how many there are.
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+// Split the header off of the file and parse it, returning it along with the
+// rest of the file and the number of the line the rest starts on.
+fn parse_header(contents: &[u8]) -> Result<(Header, &[u8], usize), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    let mut start = 0;
+    for number in 1.. {
+        if start >= contents.len() {
+            return Err("the header has no end_header line".to_string());
+        }
+        let end = contents[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(contents.len(), |i| start + i);
+        let line = str::from_utf8(&contents[start..end])
+            .map_err(|_| format!("line {}: the header isn't text", number))?
+            .trim();
+        start = end + 1;
+
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if number == 1 {
+            if keyword != Some("ply") {
+                return Err("not a PLY file".to_string());
+            }
+            continue;
+        }
+
+        let error = |message: &str| Err(format!("line {}: {}", number, message));
+        match keyword {
+            Some("format") => {
+                format = match (words.next(), words.next()) {
+                    (Some("ascii"), Some("1.0")) => Some(Format::Ascii),
+                    (Some("binary_little_endian"), Some("1.0")) => Some(Format::BinaryLittleEndian),
+                    (Some("binary_big_endian"), Some("1.0")) => Some(Format::BinaryBigEndian),
+                    _ => return error("unsupported format"),
+                }
+            }
+            Some("element") => {
+                let name = words.next();
+                let count = words.next().and_then(|count| count.parse::<usize>().ok());
+                match (name, count) {
+                    (Some(name), Some(count)) => elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    }),
+                    _ => return error("expected an element name and count"),
+                }
+            }
+            Some("property") => {
+                let words: Vec<&str> = words.collect();
+                let property = match words[..] {
+                    ["list", count, item, name] => {
+                        match (ScalarType::parse(count), ScalarType::parse(item)) {
+                            (Some(count), Some(item)) if count.is_integer() => Property {
+                                name: name.to_string(),
+                                kind: PropertyType::List { count, item },
+                            },
+                            _ => return error("invalid list property types"),
+                        }
+                    }
+                    [kind, name] => match ScalarType::parse(kind) {
+                        Some(kind) => Property {
+                            name: name.to_string(),
+                            kind: PropertyType::Scalar(kind),
+                        },
+                        None => return error(&format!("unknown property type `{}`", kind)),
+                    },
+                    _ => return error("expected a property type and name"),
+                };
+
+                match elements.last_mut() {
+                    Some(element) => element.properties.push(property),
+                    None => return error("property before any element"),
+                }
+            }
+            Some("end_header") => {
+                let format = format.ok_or_else(|| "the header has no format".to_string())?;
+                let body = &contents[start.min(contents.len())..];
+                return Ok((Header { format, elements }, body, number + 1));
+            }
+            Some("comment") | Some("obj_info") | None => {}
+            Some(keyword) => return error(&format!("unexpected `{}`", keyword)),
+        }
+    }
+
+    unreachable!()
+}
+
+// Reads the values of properties in turn from the body of the file.
+enum Reader<'a> {
+    // Whitespace separated values, along with the number of the line each is
+    // on.
+    Ascii {
+        values: Vec<(usize, &'a str)>,
+        next: usize,
+    },
+    Binary {
+        data: &'a [u8],
+        offset: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Reader<'a> {
+    fn ascii(body: &'a [u8], first_line: usize) -> Result<Reader<'a>, String> {
+        let text = str::from_utf8(body).map_err(|_| "the body isn't text".to_string())?;
+        let values = text
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                line.split_whitespace()
+                    .map(move |value| (first_line + i, value))
+            })
+            .collect();
+
+        Ok(Reader::Ascii { values, next: 0 })
+    }
+
+    fn binary(data: &'a [u8], big_endian: bool) -> Reader<'a> {
+        Reader::Binary {
+            data,
+            offset: 0,
+            big_endian,
+        }
+    }
+
+    fn read(&mut self, kind: ScalarType) -> Result<f64, String> {
+        match *self {
+            Reader::Ascii {
+                ref values,
+                ref mut next,
+            } => {
+                let &(line, value) = values
+                    .get(*next)
+                    .ok_or_else(|| "unexpected end of file".to_string())?;
+                *next += 1;
+
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("line {}: `{}` isn't a number", line, value))
+            }
+            Reader::Binary {
+                data,
+                ref mut offset,
+                big_endian,
+            } => {
+                let size = kind.size();
+                if *offset + size > data.len() {
+                    return Err("unexpected end of file".to_string());
+                }
+
+                let mut bytes = [0; 8];
+                bytes[..size].copy_from_slice(&data[*offset..*offset + size]);
+                if big_endian {
+                    bytes[..size].reverse();
+                }
+                *offset += size;
+
+                Ok(match kind {
+                    ScalarType::Int8 => f64::from(bytes[0] as i8),
+                    ScalarType::UInt8 => f64::from(bytes[0]),
+                    ScalarType::Int16 => f64::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+                    ScalarType::UInt16 => f64::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+                    ScalarType::Int32 => {
+                        f64::from(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    }
+                    ScalarType::UInt32 => {
+                        f64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    }
+                    ScalarType::Float32 => {
+                        f64::from(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                    }
+                    ScalarType::Float64 => f64::from_le_bytes(bytes),
+                })
+            }
+        }
+    }
+
+    // Read every value of a property, which is a single value unless the
+    // property is a list.
+    fn read_property(&mut self, property: &Property) -> Result<Vec<f64>, String> {
+        match property.kind {
+            PropertyType::Scalar(kind) => Ok(vec![self.read(kind)?]),
+            PropertyType::List { count, item } => {
+                let count = self.read(count)?;
+                if count < 0.0 || count.fract() != 0.0 {
+                    return Err(format!("invalid list length {}", count));
+                }
+
+                (0..count as usize).map(|_| self.read(item)).collect()
+            }
+        }
+    }
+}
+
+fn element_error(element: &Element, index: usize, message: &str) -> ImportError {
+    ImportError::Ply(format!("{} {}: {}", element.name, index, message))
+}
+
+struct Vertices {
+    positions: Vec<Point3<f32>>,
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Point2<f32>>,
+    colors: Vec<Vector3<f32>>,
+}
+
+fn read_vertices(element: &Element, reader: &mut Reader) -> Result<Vertices, ImportError> {
+    // Find the properties holding each attribute. Attributes with some of
+    // their properties missing are left out.
+    let find = |names: &[&[&str]]| -> Option<Vec<usize>> {
+        names.iter().map(|names| element.property(names)).collect()
+    };
+    let position = find(&[&["x"], &["y"], &["z"]])
+        .ok_or_else(|| ImportError::Ply("vertices have no position".to_string()))?;
+    let normal = find(&[&["nx"], &["ny"], &["nz"]]);
+    let uv = find(&[
+        &["u", "s", "texture_u", "texture_s"],
+        &["v", "t", "texture_v", "texture_t"],
+    ]);
+    let color = find(&[&["red", "r"], &["green", "g"], &["blue", "b"]]);
+
+    // Integer colors are stored in sRGB, like 8-bit images, and need
+    // converting to linear RGB.
+    let color_scale = color
+        .as_ref()
+        .map(|color| match element.properties[color[0]].kind {
+            PropertyType::Scalar(kind) => kind.color_scale(),
+            PropertyType::List { .. } => 1.0,
+        });
+
+    // The count in the header can't be trusted not to be absurdly large, so
+    // nothing is allocated up front.
+    let mut vertices = Vertices {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        colors: Vec::new(),
+    };
+    for i in 0..element.count {
+        let mut values = Vec::with_capacity(element.properties.len());
+        for property in &element.properties {
+            let value = reader
+                .read_property(property)
+                .map_err(|err| element_error(element, i, &err))?;
+            values.push(value.first().cloned().unwrap_or(0.0) as f32);
+        }
+
+        let vector = |p: &[usize]| Vector3::new(values[p[0]], values[p[1]], values[p[2]]);
+
+        let position = vector(&position);
+        if !position.iter().all(|c| c.is_finite()) {
+            return Err(element_error(element, i, "position must be finite"));
+        }
+
+        vertices.positions.push(Point3::from(position));
+        if let Some(ref normal) = normal {
+            vertices.normals.push(vector(normal));
+        }
+        if let Some(ref uv) = uv {
+            vertices.uvs.push(Point2::new(values[uv[0]], values[uv[1]]));
+        }
+        if let (Some(ref color), Some(scale)) = (&color, color_scale) {
+            let color = vector(color);
+            vertices.colors.push(if scale == 1.0 {
+                color
+            } else {
+                color.map(|c| srgb_to_linear((f64::from(c) / scale) as f32))
+            });
+        }
+    }
+
+    Ok(vertices)
+}
+
+fn read_faces(element: &Element, reader: &mut Reader) -> Result<Vec<Vec<u32>>, ImportError> {
+    let indices = element
+        .property(&["vertex_indices", "vertex_index"])
+        .ok_or_else(|| ImportError::Ply("faces have no vertex_indices".to_string()))?;
+    if let PropertyType::Scalar(_) = element.properties[indices].kind {
+        return Err(ImportError::Ply(
+            "vertex_indices must be a list".to_string(),
+        ));
+    }
+
+    let mut faces = Vec::new();
+    for i in 0..element.count {
+        let mut face = Vec::new();
+        for (j, property) in element.properties.iter().enumerate() {
+            let values = reader
+                .read_property(property)
+                .map_err(|err| element_error(element, i, &err))?;
+            if j != indices {
+                continue;
+            }
+
+            for index in values {
+                if index < 0.0 || index.fract() != 0.0 || index > f64::from(u32::MAX) {
+                    return Err(element_error(
+                        element,
+                        i,
+                        &format!("invalid vertex index {}", index),
+                    ));
+                }
+                face.push(index as u32);
+            }
+        }
+        faces.push(face);
+    }
+
+    Ok(faces)
+}
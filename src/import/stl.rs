@@ -0,0 +1,182 @@
+use import::{ImportError, ImportedMesh};
+use mesh::TriangleMesh;
+use na::Point3;
+use std::fs;
+use std::path::Path;
+use std::str;
+
+// Load an STL file, in either its ASCII or binary form.
+pub fn load(path: &Path) -> Result<Vec<ImportedMesh>, ImportError> {
+    let contents = fs::read(path)?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    parse(&contents, &name)
+}
+
+// Parse the contents of an STL file. Each solid in an ASCII file becomes a
+// separate mesh, named after the solid if it has a name and `name` otherwise,
+// while a binary file holds a single mesh named `name`.
+//
+// STL files only hold triangles, which don't share vertices, so every triangle
+// gets vertices of its own. The normals stored with each triangle are ignored
+// in favor of the winding order, which is more reliable. Solids without any
+// triangles are left out.
+pub fn parse(contents: &[u8], name: &str) -> Result<Vec<ImportedMesh>, ImportError> {
+    // Binary files start with an 80 byte header, which some exporters fill in
+    // with "solid" just like ASCII files, so go by whether the size matches
+    // the triangle count following the header instead.
+    if contents.len() >= 84 {
+        let count = u32::from_le_bytes([contents[80], contents[81], contents[82], contents[83]]);
+        if contents.len() as u64 == 84 + 50 * u64::from(count) {
+            return parse_binary(&contents[84..], name);
+        }
+    }
+
+    // Anything else that isn't text is most likely a binary file which has
+    // been cut short.
+    match str::from_utf8(contents) {
+        Ok(text) if text.starts_with("solid") => parse_ascii(text, name),
+        _ => Err(ImportError::Stl(
+            "the size of the file doesn't match its triangle count".to_string(),
+        )),
+    }
+}
+
+fn parse_binary(triangles: &[u8], name: &str) -> Result<Vec<ImportedMesh>, ImportError> {
+    // Each triangle is a normal and three vertices, all as little-endian
+    // floats, followed by two bytes which are usually unused.
+    let float = |bytes: &[u8], i: usize| {
+        f32::from_le_bytes([
+            bytes[4 * i],
+            bytes[4 * i + 1],
+            bytes[4 * i + 2],
+            bytes[4 * i + 3],
+        ])
+    };
+    let mut positions = Vec::with_capacity(triangles.len() / 50 * 3);
+    for (i, triangle) in triangles.chunks(50).enumerate() {
+        for v in 1..4 {
+            let position = Point3::new(
+                float(triangle, 3 * v),
+                float(triangle, 3 * v + 1),
+                float(triangle, 3 * v + 2),
+            );
+            if !position.iter().all(|c| c.is_finite()) {
+                return Err(ImportError::Stl(format!(
+                    "triangle {}: vertices must be finite numbers",
+                    i
+                )));
+            }
+            positions.push(position);
+        }
+    }
+
+    Ok(mesh(name, positions).into_iter().collect())
+}
+
+fn parse_ascii(text: &str, name: &str) -> Result<Vec<ImportedMesh>, ImportError> {
+    // Where in the file parsing is up to: outside of any solid, in a solid
+    // between facets, within a facet but outside its loop, or within the loop
+    // of a facet.
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Outside,
+        Solid,
+        Facet,
+        Loop,
+    }
+
+    let mut meshes = Vec::new();
+    let mut state = State::Outside;
+    let mut solid_name = String::new();
+    let mut positions = Vec::new();
+    let mut loop_vertices = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let error = |message: &str| Err(ImportError::Stl(format!("line {}: {}", i + 1, message)));
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match (state, keyword) {
+            (State::Outside, "solid") => {
+                let rest = words.collect::<Vec<&str>>().join(" ");
+                solid_name = if rest.is_empty() {
+                    name.to_string()
+                } else {
+                    rest
+                };
+                state = State::Solid;
+            }
+            (State::Solid, "facet") => {
+                // The normal, which isn't used but should still be valid.
+                if words.next() != Some("normal") || parse_point(words).is_none() {
+                    return error("expected `facet normal` and three numbers");
+                }
+                state = State::Facet;
+            }
+            (State::Solid, "endsolid") => {
+                meshes.extend(mesh(&solid_name, positions.split_off(0)));
+                state = State::Outside;
+            }
+            (State::Facet, "outer") if words.next() == Some("loop") => {
+                loop_vertices = 0;
+                state = State::Loop;
+            }
+            (State::Facet, "endfacet") if loop_vertices == 3 => state = State::Solid,
+            (State::Loop, "vertex") => match parse_point(words) {
+                Some(position) if loop_vertices < 3 => {
+                    positions.push(position);
+                    loop_vertices += 1;
+                }
+                Some(_) => return error("a facet must have exactly three vertices"),
+                None => return error("expected `vertex` and three numbers"),
+            },
+            (State::Loop, "endloop") if loop_vertices == 3 => state = State::Facet,
+            (State::Loop, "endloop") => return error("a facet must have exactly three vertices"),
+            (_, keyword) => return error(&format!("unexpected `{}`", keyword)),
+        }
+    }
+
+    if state != State::Outside {
+        return Err(ImportError::Stl("unexpected end of file".to_string()));
+    }
+
+    Ok(meshes)
+}
+
+// Parse exactly three finite numbers.
+fn parse_point<'a, I: Iterator<Item = &'a str>>(words: I) -> Option<Point3<f32>> {
+    let values: Vec<f32> = words
+        .map(|word| word.parse::<f32>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<f32>>>()?;
+
+    match values[..] {
+        [x, y, z] => Some(Point3::new(x, y, z)),
+        _ => None,
+    }
+}
+
+// A mesh of the triangles formed by each three positions in turn, or `None` if
+// there are no triangles.
+fn mesh(name: &str, positions: Vec<Point3<f32>>) -> Option<ImportedMesh> {
+    if positions.is_empty() {
+        return None;
+    }
+
+    let indices = (0..positions.len() as u32 / 3)
+        .map(|t| [3 * t, 3 * t + 1, 3 * t + 2])
+        .collect();
+
+    Some(ImportedMesh {
+        name: name.to_string(),
+        mesh: TriangleMesh::new(positions, Vec::new(), Vec::new(), indices),
+        material: None,
+        warnings: Vec::new(),
+    })
+}
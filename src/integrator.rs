@@ -1,13 +1,68 @@
+use bsdf::{Bsdf, ShadingFrame};
 use camera::Camera;
+use film::FilmTile;
+use intersection::Intersection;
 use material::MaterialBox;
 use na::Vector3;
-use rand::random;
-use ray::Ray;
+use ray::{MediumStack, Ray};
+use sampler::{Sampler, SamplerKind, SamplerProperties};
+use sampling::{cosine_hemisphere, from_basis, power_heuristic};
 use scene::Scene;
+use std::ops::Range;
+use std::str::FromStr;
+use texture::TexturePoint;
 
-// A rendering equation solver.
-pub trait Integrator {
-    fn integrate(&self, position: (f32, f32)) -> Vector3<f32>;
+// Which rendering equation solver to use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IntegratorKind {
+    // Path tracing, which finds all of the light in the scene.
+    Path,
+
+    // Ambient occlusion, which ignores lights and materials and shades each
+    // surface by how much of the sky above it isn't blocked by other
+    // surfaces. It is quick to converge, so is useful for checking geometry.
+    AmbientOcclusion,
+}
+
+impl FromStr for IntegratorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IntegratorKind, String> {
+        match s.to_lowercase().as_str() {
+            "path" => Ok(IntegratorKind::Path),
+            "ambient_occlusion" | "ao" => Ok(IntegratorKind::AmbientOcclusion),
+            _ => Err(format!("unknown integrator `{}`", s)),
+        }
+    }
+}
+
+// Settings shared by the rendering equation solvers.
+pub struct IntegratorProperties {
+    pub kind: IntegratorKind,
+
+    // How many reflections to continue tracing before giving up.
+    pub max_bounces: u32,
+
+    // How the random numbers of every sample are generated.
+    pub sampler: SamplerKind,
+
+    // Seed for the random numbers of every sample. Renders with the same seed
+    // are identical.
+    pub seed: u64,
+
+    // How far away surfaces can be and still occlude each other, for ambient
+    // occlusion.
+    pub occlusion_distance: f32,
+}
+
+// A rendering equation solver. Integrators are shared between the render
+// threads.
+pub trait Integrator: Sync {
+    // Take the samples with the given indices within a pixel, adding each to
+    // the film. Each sample index always produces the same sample, so
+    // splitting the samples of a pixel into several ranges gives the same
+    // result.
+    fn integrate(&self, pixel: (usize, usize), samples: Range<u32>, film: &mut FilmTile);
 }
 
 // A rendering equation solver that uses path tracing, a Monte Carlo method, to
@@ -20,38 +75,124 @@ pub struct MonteCarloIntegrator<'a> {
     pub width: usize,
     pub height: usize,
 
-    // How many samples should be collected for each pixel.
-    pub samples_per_pixel: u32,
-
     // How many reflections to continue tracing before giving up.
     pub max_bounces: u32,
+
+    // How the random numbers of every sample are generated.
+    pub sampler: SamplerProperties,
 }
 
 impl<'a> MonteCarloIntegrator<'a> {
-    fn trace_with_depth(&self, ray: &Ray, remaining_depth: u32) -> Vector3<f32> {
+    // Find the light arriving along a ray.
+    //
+    // `bsdf_pdf` is the density with which the material at the previous bounce
+    // chose this ray, or `None` if this is a camera ray or the material was
+    // specular. Light which this ray hits directly is weighted against the
+    // chance of it having been found by light sampling at the previous bounce.
+    fn trace_with_depth(
+        &self,
+        ray: &Ray,
+        remaining_depth: u32,
+        bsdf_pdf: Option<f32>,
+        sampler: &mut dyn Sampler,
+    ) -> Vector3<f32> {
         // After the max recursive depth has been reached, don't bother
         // collecting any more bounces.
         if remaining_depth == 0 {
             return Vector3::new(0.0, 0.0, 0.0);
         }
 
-        match self.scene.intersects(&ray) {
+        match self.scene.intersects(ray) {
             // If another object is hit, calculate the contribution of the ray.
-            Some((intersection, object)) => {
+            Some((mut intersection, object)) => {
+                // Textures are blurred over the area seen by a pixel. This is
+                // approximated by the area the camera would see at the
+                // intersection, even if it was reached by bouncing.
+                intersection.footprint = self.camera.footprint(&intersection.position, self.height);
+
                 match object.material {
                     MaterialBox::Emissive(ref mat) => {
-                        // TODO: What color?
-                        Vector3::new(1.0, 1.0, 1.0) * mat.emissivity
+                        let emitted = mat.emitted(
+                            &intersection.normal,
+                            &TexturePoint::new(&intersection),
+                            &-ray.direction,
+                        );
+
+                        match bsdf_pdf {
+                            Some(bsdf_pdf) => {
+                                let light_pdf =
+                                    self.scene.light_pdf(object, &ray.origin, &ray.direction);
+                                emitted * power_heuristic(bsdf_pdf, light_pdf)
+                            }
+                            None => emitted,
+                        }
                     }
                     // For a reflective surface, the contribution is calculated
-                    // by indirect lighting.
+                    // by direct lighting from sampling the lights plus
+                    // indirect lighting.
                     MaterialBox::Reflective(ref mat) => {
+                        let bsdf = mat.bsdf(ray, &intersection);
+                        let frame = ShadingFrame::new(&intersection.normal);
+                        let wo = frame.to_local(&-ray.direction);
+
+                        // The dimensions for light sampling are drawn even
+                        // when they aren't used, so that each dimension of the
+                        // sampler serves the same purpose in every sample.
+                        let u_light = sampler.get_1d();
+                        let u_light_position = sampler.get_2d();
+
+                        // Light reached by bouncing is only counted if there is
+                        // another bounce left, so do the same for light
+                        // sampling to keep the two in balance.
+                        let direct = if bsdf.is_specular() || remaining_depth == 1 {
+                            Vector3::new(0.0, 0.0, 0.0)
+                        } else {
+                            self.sample_direct(
+                                &intersection,
+                                &frame,
+                                &wo,
+                                bsdf.as_ref(),
+                                (u_light, u_light_position),
+                            )
+                        };
+
                         // Bounce a ray off the object recursively to find the
                         // contribution.
-                        let new_ray = mat.bounce(&ray, &intersection);
+                        let u_lobe = sampler.get_1d();
+                        let sample = match bsdf.sample(&wo, u_lobe, sampler.get_2d()) {
+                            Some(sample) if sample.pdf > 0.0 => sample,
+                            _ => return direct,
+                        };
+
+                        // Light passing through the surface enters the object
+                        // if it goes against the normal, and leaves it
+                        // otherwise.
+                        let media = match sample.index_of_refraction {
+                            Some(index) if sample.wi.z < 0.0 => ray.media.enter(index),
+                            Some(_) => ray.media.exit(),
+                            None => ray.media,
+                        };
+
+                        let direction = frame.to_world(&sample.wi);
+                        if !intersection.consistent(&-ray.direction, &direction) {
+                            return direct;
+                        }
 
-                        mat.color()
-                            .component_mul(&self.trace_with_depth(&new_ray, remaining_depth - 1))
+                        let new_ray = intersection.spawn_ray(direction, media);
+                        let pdf = if sample.specular {
+                            None
+                        } else {
+                            Some(sample.pdf)
+                        };
+
+                        let weight = sample.f * (sample.wi.z.abs() / sample.pdf);
+                        direct
+                            + weight.component_mul(&self.trace_with_depth(
+                                &new_ray,
+                                remaining_depth - 1,
+                                pdf,
+                                sampler,
+                            ))
                     }
                 }
             }
@@ -60,28 +201,167 @@ impl<'a> MonteCarloIntegrator<'a> {
             None => Vector3::new(0.0, 0.0, 0.0),
         }
     }
+
+    // Estimate the light arriving directly from a light source at an
+    // intersection by sampling a point on a light and checking if it is
+    // visible. The estimate is weighted against the chance of the material
+    // having bounced a ray towards the same point. `u` chooses the light and
+    // then the point on it.
+    fn sample_direct(
+        &self,
+        intersection: &Intersection,
+        frame: &ShadingFrame,
+        wo: &Vector3<f32>,
+        bsdf: &dyn Bsdf,
+        u: (f32, (f32, f32)),
+    ) -> Vector3<f32> {
+        let origin = intersection.position;
+        let (u_light, u_position) = u;
+        let (sample, light) = match self.scene.sample_light(&origin, u_light, u_position) {
+            Some(sample) => sample,
+            None => return Vector3::new(0.0, 0.0, 0.0),
+        };
+
+        let offset = sample.position - origin;
+        let distance = offset.norm();
+        let direction = offset / distance;
+
+        // One-sided lights, and dark parts of textured ones, may not emit any
+        // light towards the intersection.
+        let emitted = match light.material {
+            MaterialBox::Emissive(ref light) => {
+                let point = TexturePoint {
+                    position: sample.position,
+                    uv: sample.uv,
+                    color: sample.color,
+                    width: 0.0,
+                };
+                light.emitted(&sample.normal, &point, &-direction)
+            }
+            MaterialBox::Reflective(_) => return Vector3::new(0.0, 0.0, 0.0),
+        };
+
+        if !intersection.consistent(&frame.to_world(wo), &direction) {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+
+        let wi = frame.to_local(&direction);
+        let f = bsdf.eval(wo, &wi);
+        let cos = wi.z.abs();
+        if f == Vector3::zeros() || cos == 0.0 || emitted == Vector3::zeros() {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+
+        // The light is visible if nothing lies between the intersection and
+        // the sampled point.
+        let shadow_ray = intersection.spawn_ray_to(&sample.position, &sample.normal, &sample.error);
+        if self.scene.occluded(&shadow_ray) {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+
+        let weight = power_heuristic(sample.pdf, bsdf.pdf(wo, &wi));
+        f.component_mul(&emitted) * (cos * weight / sample.pdf)
+    }
 }
 
 impl<'a> Integrator for MonteCarloIntegrator<'a> {
-    fn integrate(&self, (x, y): (f32, f32)) -> Vector3<f32> {
-        let mut color = Vector3::new(0.0, 0.0, 0.0);
-
-        for _sample in 0..self.samples_per_pixel {
-            // Perturb the ray for this sample by a small amount, but keep it
-            // within the pixel boundaries.
-            let jitter = (
-                (random::<f32>() - 0.5) / self.width as f32,
-                (random::<f32>() - 0.5) / self.height as f32,
+    fn integrate(&self, pixel: (usize, usize), samples: Range<u32>, film: &mut FilmTile) {
+        for sample in samples {
+            let mut sampler = self.sampler.sampler(pixel, sample);
+            let (position, ray) = camera_ray(
+                self.camera,
+                (self.width, self.height),
+                pixel,
+                sampler.as_mut(),
             );
 
-            // Generate a ray from the camera origin through the current position on
-            // the screen.
-            let ray = self.camera.get_ray(x + jitter.0, y + jitter.1);
-
-            let contribution = self.trace_with_depth(&ray, self.max_bounces);
-            color += contribution / self.samples_per_pixel as f32;
+            let color = self.trace_with_depth(&ray, self.max_bounces, None, sampler.as_mut());
+            film.add_sample(position, color);
         }
+    }
+}
+
+// A rendering equation solver that finds how much of the hemisphere above each
+// surface seen by the camera is open, from white where nothing is nearby to
+// black where it is completely enclosed. Surfaces are taken to face the camera
+// and their materials are ignored.
+pub struct AmbientOcclusionIntegrator<'a> {
+    pub camera: &'a Camera,
+    pub scene: &'a Scene,
+
+    // Image dimensions.
+    pub width: usize,
+    pub height: usize,
+
+    // How far away surfaces can be and still occlude. Anything further away is
+    // treated as open sky.
+    pub distance: f32,
+
+    // How the random numbers of every sample are generated.
+    pub sampler: SamplerProperties,
+}
+
+impl<'a> Integrator for AmbientOcclusionIntegrator<'a> {
+    fn integrate(&self, pixel: (usize, usize), samples: Range<u32>, film: &mut FilmTile) {
+        for sample in samples {
+            let mut sampler = self.sampler.sampler(pixel, sample);
+            let (position, ray) = camera_ray(
+                self.camera,
+                (self.width, self.height),
+                pixel,
+                sampler.as_mut(),
+            );
+
+            let intersection = match self.scene.intersects(&ray) {
+                Some((intersection, _)) => intersection,
+                None => {
+                    film.add_sample(position, Vector3::zeros());
+                    continue;
+                }
+            };
+
+            let normal = if intersection.normal.dot(&ray.direction) > 0.0 {
+                -intersection.normal
+            } else {
+                intersection.normal
+            };
 
-        color
+            // Directions are chosen in proportion to the cosine to the normal,
+            // which the occlusion is weighted by, so each sample is simply
+            // open or not.
+            let direction = from_basis(&cosine_hemisphere(sampler.get_2d()), &normal);
+            let occlusion_ray = Ray {
+                t_max: self.distance,
+                ..intersection.spawn_ray(direction, MediumStack::air())
+            };
+
+            let open = if self.scene.occluded(&occlusion_ray) {
+                0.0
+            } else {
+                1.0
+            };
+            film.add_sample(position, Vector3::repeat(open));
+        }
     }
 }
+
+// Pick a point anywhere within a pixel of an image with the given dimensions,
+// and the camera ray through it.
+fn camera_ray(
+    camera: &Camera,
+    (width, height): (usize, usize),
+    (x, y): (usize, usize),
+    sampler: &mut dyn Sampler,
+) -> ((f32, f32), Ray) {
+    let (u, v) = sampler.get_2d();
+    let position = (x as f32 + u, y as f32 + v);
+
+    // Scale the axes to be on the range of [-1, 1]. Also invert the y axis, as
+    // positive y needs to be towards the top of the screen.
+    let ray = camera.get_ray(
+        position.0 / width as f32 * 2.0 - 1.0,
+        -(position.1 / height as f32 * 2.0 - 1.0),
+    );
+
+    (position, ray)
+}
@@ -1,6 +1,5 @@
-use na::{Point3, Vector3};
-use ray::Ray;
-use surface::Surface;
+use na::{Point2, Point3, Vector3};
+use ray::{gamma, offset_origin, MediumStack, Ray};
 
 #[derive(Clone, Copy)]
 pub struct Intersection {
@@ -10,20 +9,136 @@ pub struct Intersection {
     // The point at which the intersection occured.
     pub position: Point3<f32>,
 
-    // The normal on the surface at which the intersection occured.
+    // A bound on the absolute error in each coordinate of `position`.
+    pub error: Vector3<f32>,
+
+    // The normal on the surface at which the intersection occured, used for
+    // shading.
     pub normal: Vector3<f32>,
+
+    // The normal of the surface itself, which may differ from `normal` where
+    // normals are interpolated for smooth shading.
+    pub geometric_normal: Vector3<f32>,
+
+    // The texture coordinates of the surface at the intersection.
+    pub uv: Point2<f32>,
+
+    // Where the intersection lies within the triangle that was hit, as the
+    // weights of its second and third vertices, or `None` for surfaces which
+    // aren't made of triangles.
+    pub barycentric: Option<Point2<f32>>,
+
+    // The color given to the surface by its vertices, if it has one.
+    pub color: Option<Vector3<f32>>,
+
+    // How quickly the texture coordinates change across the surface around
+    // the intersection, in texture units per unit of distance.
+    pub uv_density: f32,
+
+    // The width of the area around the intersection seen by a single pixel,
+    // or zero if it isn't known. This is filled in by the integrator.
+    pub footprint: f32,
 }
 
 impl Intersection {
-    // Create a new ray from a given intersection distance, ray, and surface.
-    pub fn new_from_distance(distance: f32, ray: &Ray, surface: &dyn Surface) -> Intersection {
-        let position = ray.origin + ray.direction * distance;
-        let normal = surface.normal_towards(position);
-
+    // Create a new intersection at the given distance along a ray, where the
+    // surface has the given normal and texture coordinates. The position is
+    // assumed to be exact, and the normal to be that of the surface itself.
+    pub fn new(
+        distance: f32,
+        position: Point3<f32>,
+        normal: Vector3<f32>,
+        uv: Point2<f32>,
+        uv_density: f32,
+    ) -> Intersection {
         Intersection {
             distance,
             position,
+            error: Vector3::zeros(),
             normal,
+            geometric_normal: normal,
+            uv,
+            barycentric: None,
+            color: None,
+            uv_density,
+            footprint: 0.0,
+        }
+    }
+
+    // The same intersection, where the position has the given error bound.
+    pub fn with_error(mut self, error: Vector3<f32>) -> Intersection {
+        self.error = error;
+        self
+    }
+
+    // The same intersection, where the surface itself has the given normal.
+    pub fn with_geometric_normal(mut self, normal: Vector3<f32>) -> Intersection {
+        self.geometric_normal = normal;
+        self
+    }
+
+    // The same intersection, at the given barycentric coordinates within a
+    // triangle.
+    pub fn with_barycentric(mut self, barycentric: Point2<f32>) -> Intersection {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    // The same intersection, where the surface's vertices give it a color.
+    pub fn with_color(mut self, color: Option<Vector3<f32>>) -> Intersection {
+        self.color = color;
+        self
+    }
+
+    // Whether light passing between `wo` and `wi` reflects off of the surface
+    // or passes through it is the same by the shading normal as by the
+    // surface itself. Where normals are interpolated they can disagree near
+    // silhouettes, and following the shading normal there would leak light
+    // through the surface.
+    pub fn consistent(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> bool {
+        let shading = wo.dot(&self.normal) * wi.dot(&self.normal);
+        let geometric = wo.dot(&self.geometric_normal) * wi.dot(&self.geometric_normal);
+
+        (shading > 0.0) == (geometric > 0.0)
+    }
+
+    // A ray leaving the intersection in the given direction.
+    pub fn spawn_ray(&self, direction: Vector3<f32>, media: MediumStack) -> Ray {
+        let origin = offset_origin(
+            &self.position,
+            &self.error,
+            &self.geometric_normal,
+            &direction,
+        );
+
+        Ray::new(origin, direction, media)
+    }
+
+    // A ray from the intersection to a point on another surface, which has
+    // the given normal and error bound. Both ends are moved off of their
+    // surfaces, so the ray finds neither of them, only what is in between.
+    pub fn spawn_ray_to(
+        &self,
+        target: &Point3<f32>,
+        target_normal: &Vector3<f32>,
+        target_error: &Vector3<f32>,
+    ) -> Ray {
+        let origin = offset_origin(
+            &self.position,
+            &self.error,
+            &self.geometric_normal,
+            &(target - self.position),
+        );
+        let target = offset_origin(target, target_error, target_normal, &(origin - target));
+
+        let offset = target - origin;
+        let distance = offset.norm();
+
+        // Distances along rays are found with a few rounding errors, so stop
+        // just short of the target, where no surface can be.
+        Ray {
+            t_max: distance * (1.0 - gamma(16)),
+            ..Ray::new_from_air(origin, offset / distance)
         }
     }
 }
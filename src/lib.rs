@@ -1,12 +1,36 @@
+extern crate exr;
+extern crate gltf;
+extern crate image;
 extern crate nalgebra as na;
-extern crate rand;
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate tobj;
+extern crate toml;
 
+pub mod aabb;
+pub mod bsdf;
+pub mod bvh;
 pub mod camera;
+pub mod film;
+pub mod import;
 pub mod integrator;
 pub mod intersection;
 pub mod material;
+pub mod mesh;
+pub mod microfacet;
 pub mod object;
+pub mod output;
+pub mod principled;
 pub mod ray;
 pub mod render;
+pub mod sampler;
+pub mod sampling;
 pub mod scene;
+pub mod scene_file;
+pub mod spectrum;
 pub mod surface;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
@@ -1,167 +1,338 @@
-extern crate image;
-extern crate nalgebra as na;
+#[macro_use]
+extern crate clap;
 extern crate renderer;
 
-use na::{DMatrix, Point3, Vector3};
+use clap::{App, Arg, ArgMatches};
+use renderer::film::Filter;
+use renderer::integrator::{Integrator, IntegratorKind};
+use renderer::output::OutputFormat;
+use renderer::render::CropWindow;
+use renderer::sampler::SamplerProperties;
 use renderer::*;
+use std::fmt::Display;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
 
 fn main() {
-    let sphere1 = surface::Sphere {
-        center: Point3::new(-0.75, 0.25, 0.75),
-        radius: 0.25,
-    };
-    let sphere2 = surface::Sphere {
-        center: Point3::new(0.75, 0.25, 0.75),
-        radius: 0.25,
-    };
-    let cool_light = surface::Sphere {
-        center: Point3::new(-0.35, 1.0, -0.2),
-        radius: 0.1,
-    };
-    let sphere3 = surface::Sphere {
-        center: Point3::new(0.0, 0.45, 0.0),
-        radius: 0.25,
-    };
-    let back = surface::Plane {
-        normal: Vector3::new(0.0, 0.0, -1.0),
-        offset: 1.0,
-    };
-    let front = surface::Plane {
-        normal: Vector3::new(0.0, 0.0, 1.0),
-        offset: -2.0,
-    };
-    let floor = surface::Plane {
-        normal: Vector3::new(0.0, 1.0, 0.0),
-        offset: 0.0,
+    let matches = App::new("renderer")
+        .version(crate_version!())
+        .about("A Monte Carlo path tracer.")
+        .arg(
+            Arg::with_name("scene")
+                .value_name("SCENE")
+                .help("Scene file, or glTF file, to render")
+                .default_value("scenes/box.toml"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Image file to write")
+                .default_value("test.ppm"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output image format [default: guessed from the output file name]")
+                .possible_values(&["ppm", "png", "jpeg", "bmp", "tga", "hdr", "exr"]),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .value_name("PIXELS")
+                .help("Image width, overriding the scene file")
+                .validator(validate_positive),
+        )
+        .arg(
+            Arg::with_name("height")
+                .long("height")
+                .value_name("PIXELS")
+                .help("Image height, overriding the scene file")
+                .validator(validate_positive),
+        )
+        .arg(
+            Arg::with_name("spp")
+                .short("s")
+                .long("spp")
+                .value_name("SAMPLES")
+                .help("Samples per pixel, overriding the scene file")
+                .validator(validate_positive),
+        )
+        .arg(
+            Arg::with_name("pass-spp")
+                .long("pass-spp")
+                .value_name("SAMPLES")
+                .help(
+                    "Render progressively in passes of this many samples per pixel, \
+                     writing the image after each pass",
+                )
+                .validator(validate_positive),
+        )
+        .arg(
+            Arg::with_name("time-limit")
+                .long("time-limit")
+                .value_name("SECONDS")
+                .help("Stop rendering after the pass that exceeds this time")
+                .validator(validate_seconds),
+        )
+        .arg(
+            Arg::with_name("max-bounces")
+                .short("b")
+                .long("max-bounces")
+                .value_name("BOUNCES")
+                .help("Maximum number of bounces, overriding the scene file")
+                .validator(validate_parse::<u32>),
+        )
+        .arg(
+            Arg::with_name("integrator")
+                .long("integrator")
+                .value_name("INTEGRATOR")
+                .help("Rendering algorithm, overriding the scene file")
+                .possible_values(&["path", "ambient_occlusion"]),
+        )
+        .arg(
+            Arg::with_name("sampler")
+                .long("sampler")
+                .value_name("SAMPLER")
+                .help("Sample generator, overriding the scene file")
+                .possible_values(&["uniform", "stratified", "halton", "sobol"]),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Random seed, overriding the scene file")
+                .validator(validate_parse::<u64>),
+        )
+        .arg(
+            Arg::with_name("crop")
+                .long("crop")
+                .value_name("X0,Y0,X1,Y1")
+                .help("Only render this region of the image, in pixels")
+                .validator(validate_parse::<CropWindow>),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("filter")
+                .value_name("FILTER")
+                .help("Reconstruction filter, overriding the scene file")
+                .possible_values(&["box", "tent", "gaussian", "mitchell", "lanczos"]),
+        )
+        .arg(
+            Arg::with_name("filter-radius")
+                .long("filter-radius")
+                .value_name("PIXELS")
+                .help("Reconstruction filter radius [default: depends on the filter]")
+                .validator(validate_radius),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("j")
+                .long("threads")
+                .value_name("THREADS")
+                .help("Number of render threads [default: one per CPU]")
+                .validator(validate_positive),
+        )
+        .arg(
+            Arg::with_name("exposure")
+                .long("exposure")
+                .value_name("STOPS")
+                .help("Exposure adjustment for 8-bit output, overriding the scene file")
+                .allow_hyphen_values(true)
+                .validator(validate_finite),
+        )
+        .arg(
+            Arg::with_name("tonemap")
+                .long("tonemap")
+                .value_name("OPERATOR")
+                .help("Tone mapping operator for 8-bit output, overriding the scene file")
+                .possible_values(&["clamp", "reinhard", "aces"]),
+        )
+        .arg(
+            Arg::with_name("dither")
+                .long("dither")
+                .help("Dither 8-bit output to hide banding"),
+        )
+        .get_matches();
+
+    let scene_path = matches.value_of("scene").unwrap();
+    let mut config = match scene_file::load(Path::new(scene_path)) {
+        Ok(config) => config,
+        Err(err) => fail(&format!("{}: {}", scene_path, err)),
     };
-    let left = surface::Plane {
-        normal: Vector3::new(1.0, 0.0, 0.0),
-        offset: -1.0,
+    for warning in &config.warnings {
+        eprintln!("warning: {}: {}", scene_path, warning);
+    }
+
+    // Command line options take precedence over the scene file.
+    if let Some(width) = parsed(&matches, "width") {
+        config.render.width = width;
+    }
+    if let Some(height) = parsed(&matches, "height") {
+        config.render.height = height;
+    }
+    if let Some(crop) = parsed(&matches, "crop") {
+        config.render.crop = Some(crop);
+    }
+    if let Some(kind) = parsed(&matches, "filter") {
+        config.render.filter = Filter::new(kind);
+    }
+    if let Some(radius) = parsed(&matches, "filter-radius") {
+        config.render.filter.radius = radius;
+    }
+    if let Some(threads) = parsed(&matches, "threads") {
+        config.render.threads = threads;
+    }
+    if let Some(spp) = parsed(&matches, "spp") {
+        config.render.samples_per_pixel = spp;
+    }
+    if let Some(kind) = parsed(&matches, "integrator") {
+        config.integrator.kind = kind;
+    }
+    if let Some(sampler) = parsed(&matches, "sampler") {
+        config.integrator.sampler = sampler;
+    }
+    if let Some(seed) = parsed(&matches, "seed") {
+        config.integrator.seed = seed;
+    }
+    if let Some(samples_per_pass) = parsed(&matches, "pass-spp") {
+        config.render.samples_per_pass = Some(samples_per_pass);
+    }
+    if let Some(seconds) = parsed(&matches, "time-limit") {
+        config.render.time_limit = Some(Duration::from_secs_f32(seconds));
+    }
+    if let Some(max_bounces) = parsed(&matches, "max-bounces") {
+        config.integrator.max_bounces = max_bounces;
+    }
+
+    if let Some(exposure) = parsed(&matches, "exposure") {
+        config.tonemap.exposure = exposure;
+    }
+    if let Some(operator) = parsed(&matches, "tonemap") {
+        config.tonemap.operator = operator;
+    }
+    if matches.is_present("dither") {
+        config.tonemap.dither = true;
+    }
+
+    config
+        .camera
+        .set_aspect_ratio(config.render.width as f32 / config.render.height as f32);
+
+    if let Some(crop) = config.render.crop {
+        if !crop.fits(config.render.width, config.render.height) {
+            fail(&format!(
+                "crop window {},{},{},{} does not fit within a {}x{} image",
+                crop.x0, crop.y0, crop.x1, crop.y1, config.render.width, config.render.height
+            ));
+        }
+    }
+
+    let output = Path::new(matches.value_of("output").unwrap());
+    let format = match parsed(&matches, "format").or_else(|| OutputFormat::from_path(output)) {
+        Some(format) => format,
+        None => fail(&format!(
+            "cannot guess the image format of {}, use --format",
+            output.display()
+        )),
     };
-    let right = surface::Plane {
-        normal: Vector3::new(-1.0, 0.0, 0.0),
-        offset: 1.0,
+
+    let sampler = SamplerProperties {
+        kind: config.integrator.sampler,
+        seed: config.integrator.seed,
+        samples_per_pixel: config.render.samples_per_pixel,
     };
-    let light = surface::Plane {
-        normal: Vector3::new(0.0, -1.5, 0.0),
-        offset: 2.0,
+    let integrator: Box<dyn Integrator> = match config.integrator.kind {
+        IntegratorKind::Path => Box::new(integrator::MonteCarloIntegrator {
+            camera: &config.camera,
+            scene: &config.scene,
+            width: config.render.width,
+            height: config.render.height,
+            max_bounces: config.integrator.max_bounces,
+            sampler,
+        }),
+        IntegratorKind::AmbientOcclusion => Box::new(integrator::AmbientOcclusionIntegrator {
+            camera: &config.camera,
+            scene: &config.scene,
+            width: config.render.width,
+            height: config.render.height,
+            distance: config.integrator.occlusion_distance,
+            sampler,
+        }),
     };
 
-    let scene = scene::Scene {
-        objects: vec![
-            // object::Object {
-            //     surface: Box::new(sphere1),
-            //     material: material::MaterialBox::Reflective(Box::new(material::PerfectDiffuseMaterial {
-            //         color: Vector3::new(1.0, 1.0, 1.0)
-            //     }))
-            // },
-            // object::Object {
-            //     surface: Box::new(sphere2),
-            //     material: material::MaterialBox::Reflective(Box::new(material::PerfectSpecularMaterial))
-            // },
-            object::Object {
-                surface: Box::new(sphere3),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectRefractiveMaterial {
-                        index_of_refraction: 1.440,
-                        reflect_prob: 0.1,
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(back),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectDiffuseMaterial {
-                        color: Vector3::new(1.0, 1.0, 1.0),
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(front),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectDiffuseMaterial {
-                        color: Vector3::new(1.0, 1.0, 1.0),
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(floor),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectDiffuseMaterial {
-                        color: Vector3::new(1.0, 1.0, 1.0),
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(left),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectDiffuseMaterial {
-                        color: Vector3::new(0.5, 0.5, 0.9),
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(right),
-                material: material::MaterialBox::Reflective(Box::new(
-                    material::PerfectDiffuseMaterial {
-                        color: Vector3::new(0.5, 0.9, 0.5),
-                    },
-                )),
-            },
-            object::Object {
-                surface: Box::new(cool_light),
-                material: material::MaterialBox::Emissive(Box::new(material::EmissiveMaterial {
-                    emissivity: 50.0,
-                })),
-            },
-            // object::Object {
-            //     surface: Box::new(light),
-            //     material: material::MaterialBox::Emissive(Box::new(material::EmissiveMaterial {
-            //         emissivity: 0.5
-            //     }))
-            // },
-        ],
-    };
+    // Write a checkpoint after every pass, so that progressive renders can be
+    // previewed while they run.
+    render::render_progressive(&config.render, integrator.as_ref(), |pass| {
+        println!(
+            "pass {} finished: {} samples per pixel ({:.0}%) in {:.1}s",
+            pass.index + 1,
+            pass.samples_per_pixel,
+            pass.samples_per_pixel as f32 / config.render.samples_per_pixel as f32 * 100.0,
+            pass.elapsed.as_secs_f32()
+        );
 
-    let camera = camera::Camera {
-        position: Point3::new(0.0, 0.3, -1.0),
-        direction: Vector3::new(0.0, 0.0, 1.0),
-    };
+        if let Err(err) = output::write_image(output, pass.image, format, &config.tonemap) {
+            fail(&format!("could not write {}: {}", output.display(), err));
+        }
+    });
+}
 
-    let props = render::RenderProperties {
-        width: 500,
-        height: 500,
-    };
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
 
-    let integrator = integrator::MonteCarloIntegrator {
-        camera: &camera,
-        scene: &scene,
-        width: props.width,
-        height: props.height,
-        samples_per_pixel: 1000,
-        max_bounces: 5,
-    };
+// Parse an argument that has already been checked by its validator.
+fn parsed<T: FromStr>(matches: &ArgMatches, name: &str) -> Option<T> {
+    matches.value_of(name).and_then(|value| value.parse().ok())
+}
 
-    let screen = render::render(&props, &integrator);
-    write_image("test.ppm", &screen);
+fn validate_parse<T>(value: String) -> Result<(), String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse::<T>()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
 }
 
-fn write_image(file_name: &str, screen: &DMatrix<Vector3<f32>>) {
-    let size = (screen.ncols() as u32, screen.nrows() as u32);
+fn validate_finite(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() => Ok(()),
+        Ok(_) => Err("must be a finite number".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
 
-    let mut imbuf = image::ImageBuffer::new(size.0, size.1);
-    for x in 0..size.0 {
-        for y in 0..size.1 {
-            let position = (x as usize, y as usize);
+fn validate_radius(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(radius) if radius.is_finite() && radius > 0.0 => Ok(()),
+        Ok(_) => Err("must be a positive number".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
 
-            let pixel = image::Rgb([
-                (screen[position].x.min(1.0) * 255.0) as u8,
-                (screen[position].y.min(1.0) * 255.0) as u8,
-                (screen[position].z.min(1.0) * 255.0) as u8,
-            ]);
-            imbuf.put_pixel(x, y, pixel);
-        }
+fn validate_seconds(value: String) -> Result<(), String> {
+    // Durations can't be infinite, or more than a few hundred billion years.
+    match value.parse::<f32>().map(Duration::try_from_secs_f32) {
+        Ok(Ok(seconds)) if seconds > Duration::ZERO => Ok(()),
+        Ok(_) => Err("must be a positive number of seconds".to_string()),
+        Err(err) => Err(err.to_string()),
     }
+}
 
-    let _ = imbuf.save(file_name);
+fn validate_positive(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(0) => Err("must be greater than zero".to_string()),
+        Ok(_) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
 }
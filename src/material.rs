@@ -1,155 +1,190 @@
+use bsdf::{Bsdf, LambertianBsdf, SpecularReflectionBsdf};
 use intersection::Intersection;
+use microfacet::{
+    DielectricBsdf, DistributionKind, Fresnel, MicrofacetDistribution, MicrofacetReflectionBsdf,
+};
 use na::Vector3;
-use rand::random;
-use ray::{Ray, INDEX_OF_REFRACTION_AIR};
-use std::f32::consts::PI;
-
+use principled::{PrincipledBsdf, PrincipledParameters};
+use ray::Ray;
+use std::sync::Arc;
+use texture::{Texture, TexturePoint};
+
+// Materials are reference counted so that instances of the same geometry can
+// share them.
+//
 // TODO: Boxing the enum rather than the individual components causes an ICE.
+#[derive(Clone)]
 pub enum MaterialBox {
-    Emissive(Box<EmissiveMaterial>),
-    Reflective(Box<dyn ReflectiveMaterial>),
+    Emissive(Arc<EmissiveMaterial>),
+    Reflective(Arc<dyn ReflectiveMaterial>),
 }
 
+// A light source, which emits light from its surface but doesn't reflect any.
 pub struct EmissiveMaterial {
-    pub emissivity: f32,
+    // The radiance emitted at each point of the surface.
+    pub emission: Box<dyn Texture>,
+
+    // Whether light is only emitted from the side of the surface its normal
+    // points towards, rather than from both sides.
+    pub one_sided: bool,
 }
 
-pub trait ReflectiveMaterial {
-    fn bounce(&self, incoming: &Ray, intersection: &Intersection) -> Ray;
+impl EmissiveMaterial {
+    // The radiance emitted in `direction` from a point of the surface with the
+    // given normal.
+    pub fn emitted(
+        &self,
+        normal: &Vector3<f32>,
+        point: &TexturePoint,
+        direction: &Vector3<f32>,
+    ) -> Vector3<f32> {
+        if self.one_sided && normal.dot(direction) <= 0.0 {
+            return Vector3::zeros();
+        }
+
+        self.emission.evaluate(point)
+    }
+}
 
-    // TODO: Are there some materials where the color depends on the incoming
-    // ray or the intersection? Subsurface scattering perhaps?
-    fn color(&self) -> Vector3<f32>;
+pub trait ReflectiveMaterial: Send + Sync {
+    // The BSDF at an intersection of the surface with a ray.
+    fn bsdf(&self, incoming: &Ray, intersection: &Intersection) -> Box<dyn Bsdf>;
 }
 
 pub struct PerfectDiffuseMaterial {
-    pub color: Vector3<f32>,
-}
-
-// Find a cosine-distributed random vector on the surface of the hemnisphere
-// about the given normal.
-fn random_vec_on_hemnisphere(normal: Vector3<f32>) -> Vector3<f32> {
-    // Use a cosine instead of uniform distribution. This is because the diffuse
-    // lighting term in the rendering equation looks like:
-    //
-    // L_i * cos(\theta)
-    //
-    // Rays that maximize the cosine term will be weighted more importantly, so
-    // maybe we should just sample those rays more often anyway.
-    //
-    // Reference: http://www.rorydriscoll.com/2009/01/07/better-sampling/
-    let r = random::<f32>().sqrt();
-    let theta = 2.0 * PI * random::<f32>();
-
-    // Convert polar to Cartesian.
-    let x = r * theta.cos();
-    let y = r * theta.sin();
-
-    // TODO: Should this be another random variable, or u1?
-    let z = (1.0 - random::<f32>()).sqrt();
-
-    // Generate the vector about the xy-plane.
-    let v = Vector3::new(x, y, z).normalize();
-
-    // This vector is either within the hemnisphere or in the opposite
-    // direction. Correct it if needed.
-    // TODO: Does this preserve the distribution?
-    if v.dot(&normal) > 0.0 {
-        v
-    } else {
-        -v
-    }
+    pub color: Box<dyn Texture>,
 }
 
 impl ReflectiveMaterial for PerfectDiffuseMaterial {
-    fn bounce(&self, _incoming: &Ray, intersection: &Intersection) -> Ray {
-        let origin = intersection.position;
-        let direction = random_vec_on_hemnisphere(intersection.normal);
-
-        Ray::new_from_air(origin, direction)
-    }
-
-    fn color(&self) -> Vector3<f32> {
-        self.color
+    fn bsdf(&self, _: &Ray, intersection: &Intersection) -> Box<dyn Bsdf> {
+        Box::new(LambertianBsdf {
+            color: self.color.evaluate(&TexturePoint::new(intersection)),
+        })
     }
 }
 
 pub struct PerfectSpecularMaterial;
 
 impl ReflectiveMaterial for PerfectSpecularMaterial {
-    fn bounce(&self, incoming: &Ray, intersection: &Intersection) -> Ray {
-        let direction = (incoming.direction
-            - intersection.normal * 2.0 * incoming.direction.dot(&intersection.normal))
-        .normalize();
-
-        Ray::new_from_air(incoming.origin, direction)
-    }
-
-    fn color(&self) -> Vector3<f32> {
+    fn bsdf(&self, _: &Ray, _: &Intersection) -> Box<dyn Bsdf> {
         // TODO: Correct color?
-        Vector3::new(1.0, 1.0, 1.0)
+        Box::new(SpecularReflectionBsdf {
+            color: Vector3::new(1.0, 1.0, 1.0),
+        })
     }
 }
 
+// A smooth transparent material, such as glass or water, which reflects or
+// refracts light in proportion to the Fresnel equations.
 pub struct PerfectRefractiveMaterial {
     pub index_of_refraction: f32,
-
-    // Probability that a ray will be reflected rather than refracted.
-    pub reflect_prob: f32,
 }
 
 impl ReflectiveMaterial for PerfectRefractiveMaterial {
-    fn bounce(&self, incoming: &Ray, intersection: &Intersection) -> Ray {
-        let refract_or_reflect = random::<f32>();
-        if refract_or_reflect > self.reflect_prob {
-            // Refract
-
-            // Equations from http://graphics.stanford.edu/courses/cs148-10-summer/docs/2006--degreve--reflection_refraction.pdf
-
-            // TODO: Index of refraction varies with the light's wavelength.
-
-            // Compare the direction of the incoming ray to the direction of the
-            // normal to see if the ray is entering or exiting the material.
-            let dir = incoming.direction.dot(&intersection.normal);
-
-            let (n1, n2) = if dir >= 0.0 {
-                // Normal and incoming ray are in the same direction, so the ray is
-                // exiting the refractive material.
-
-                // TODO: Entering... air?
-                (self.index_of_refraction, INDEX_OF_REFRACTION_AIR)
-            } else {
-                // Entering refractive material.
-                (incoming.index_of_refraction, self.index_of_refraction)
-            };
-
-            // Apply Snell's law to determine the direction of the new ray.
-            let n_ratio = n1 / n2;
-            let cost = -incoming.direction.dot(&intersection.normal);
-            let sin2t = n_ratio.powi(2) * (1.0 - cost.powi(2));
-
-            let t1 = incoming.direction * n_ratio;
-            let t2 = intersection.normal * (n_ratio * cost - (1.0 - sin2t).sqrt());
-
-            // The final direction is the sum of the vector in the original ray
-            // direction (t1) and the vector in the direction of the surface normal
-            // (t2).
-            let direction = (t1 + t2).normalize();
-
-            Ray {
-                origin: intersection.position,
-                direction,
-                index_of_refraction: n2,
-            }
-        } else {
-            // Reflect
-            let reflect_mat = PerfectSpecularMaterial;
-            reflect_mat.bounce(incoming, intersection)
+    fn bsdf(&self, incoming: &Ray, intersection: &Intersection) -> Box<dyn Bsdf> {
+        Box::new(dielectric(
+            MicrofacetDistribution::new(DistributionKind::Ggx, 0.0, 0.0),
+            self.index_of_refraction,
+            incoming,
+            intersection,
+        ))
+    }
+}
+
+// The boundary of a transparent object with the media that the ray is
+// travelling through. A ray arriving from outside the object is in the medium
+// surrounding it, while one leaving it returns to whichever medium it was in
+// before entering.
+fn dielectric(
+    distribution: MicrofacetDistribution,
+    index_of_refraction: f32,
+    incoming: &Ray,
+    intersection: &Intersection,
+) -> DielectricBsdf {
+    DielectricBsdf {
+        distribution,
+        outside: outside_medium(incoming, intersection),
+        inside: index_of_refraction,
+    }
+}
+
+// The index of refraction on the side of a surface that its normal points to.
+fn outside_medium(incoming: &Ray, intersection: &Intersection) -> f32 {
+    if incoming.direction.dot(&intersection.normal) < 0.0 {
+        incoming.media.current()
+    } else {
+        incoming.media.outside()
+    }
+}
+
+// An all-purpose material controlled by a handful of intuitive parameters,
+// which can describe anything from plastic and metal to glass.
+pub struct PrincipledMaterial {
+    pub parameters: PrincipledParameters,
+
+    // A texture which, if given, replaces the base color of `parameters`.
+    pub base_color: Option<Box<dyn Texture>>,
+
+    // A texture which, if given, scales the roughness of `parameters` by its
+    // green channel and the metallic by its blue channel, as glTF packs them.
+    pub metallic_roughness: Option<Box<dyn Texture>>,
+}
+
+impl ReflectiveMaterial for PrincipledMaterial {
+    fn bsdf(&self, incoming: &Ray, intersection: &Intersection) -> Box<dyn Bsdf> {
+        let mut parameters = self.parameters;
+        let point = TexturePoint::new(intersection);
+        if let Some(ref base_color) = self.base_color {
+            parameters.base_color = base_color.evaluate(&point);
+        }
+        if let Some(ref metallic_roughness) = self.metallic_roughness {
+            let value = metallic_roughness.evaluate(&point);
+            parameters.roughness *= value.y;
+            parameters.metallic *= value.z;
         }
+
+        Box::new(PrincipledBsdf::new(
+            &parameters,
+            outside_medium(incoming, intersection),
+        ))
     }
+}
 
-    fn color(&self) -> Vector3<f32> {
-        // TODO: Correct color?
-        Vector3::new(1.0, 1.0, 1.0)
+// A metal with a rough or polished finish, described by its complex index of
+// refraction.
+pub struct ConductorMaterial {
+    pub distribution: MicrofacetDistribution,
+
+    // The complex index of refraction, eta + ik, for red, green and blue.
+    pub eta: Vector3<f32>,
+    pub k: Vector3<f32>,
+}
+
+impl ReflectiveMaterial for ConductorMaterial {
+    fn bsdf(&self, _: &Ray, _: &Intersection) -> Box<dyn Bsdf> {
+        Box::new(MicrofacetReflectionBsdf {
+            distribution: self.distribution,
+            fresnel: Fresnel::Conductor {
+                eta: self.eta,
+                k: self.k,
+            },
+        })
+    }
+}
+
+// A transparent material with a rough surface, such as frosted glass.
+pub struct RoughDielectricMaterial {
+    pub distribution: MicrofacetDistribution,
+    pub index_of_refraction: f32,
+}
+
+impl ReflectiveMaterial for RoughDielectricMaterial {
+    fn bsdf(&self, incoming: &Ray, intersection: &Intersection) -> Box<dyn Bsdf> {
+        Box::new(dielectric(
+            self.distribution,
+            self.index_of_refraction,
+            incoming,
+            intersection,
+        ))
     }
 }
@@ -0,0 +1,299 @@
+use aabb::Aabb;
+use bvh::Bvh;
+use intersection::Intersection;
+use na::{Point2, Point3, Vector3};
+use ray::Ray;
+use surface::{
+    area_to_solid_angle, barycentric_density, intersect_triangle, sample_triangle, triangle_point,
+    Surface, SurfaceSample,
+};
+
+// A triangle mesh. Vertex attributes are stored once in shared buffers and
+// each triangle refers to its three vertices by index.
+pub struct TriangleMesh {
+    positions: Vec<Point3<f32>>,
+
+    // Per-vertex normals, texture coordinates and linear RGB colors. Each is
+    // either empty or has one entry per position.
+    normals: Vec<Vector3<f32>>,
+    uvs: Vec<Point2<f32>>,
+    colors: Vec<Vector3<f32>>,
+
+    indices: Vec<[u32; 3]>,
+
+    // Hierarchy over the triangles, indexed the same as `indices`.
+    bvh: Bvh,
+
+    // Running total of triangle areas, used to sample points uniformly over
+    // the mesh.
+    area_cdf: Vec<f32>,
+}
+
+impl TriangleMesh {
+    // Create a mesh from vertex buffers and a list of triangles.
+    //
+    // Panics if an attribute buffer is non-empty but a different length to
+    // `positions`, or if a triangle refers to a vertex that doesn't exist.
+    pub fn new(
+        positions: Vec<Point3<f32>>,
+        normals: Vec<Vector3<f32>>,
+        uvs: Vec<Point2<f32>>,
+        indices: Vec<[u32; 3]>,
+    ) -> TriangleMesh {
+        assert!(normals.is_empty() || normals.len() == positions.len());
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+        assert!(indices
+            .iter()
+            .all(|t| t.iter().all(|&i| (i as usize) < positions.len())));
+
+        let bounds: Vec<Aabb> = indices
+            .iter()
+            .map(|t| {
+                Aabb::from_points(&[
+                    positions[t[0] as usize],
+                    positions[t[1] as usize],
+                    positions[t[2] as usize],
+                ])
+            })
+            .collect();
+
+        let mut total = 0.0;
+        let area_cdf = indices
+            .iter()
+            .map(|t| {
+                let p = [
+                    positions[t[0] as usize],
+                    positions[t[1] as usize],
+                    positions[t[2] as usize],
+                ];
+                total += (p[1] - p[0]).cross(&(p[2] - p[0])).norm() / 2.0;
+                total
+            })
+            .collect();
+
+        TriangleMesh {
+            bvh: Bvh::build(&bounds),
+            positions,
+            normals,
+            uvs,
+            colors: Vec::new(),
+            indices,
+            area_cdf,
+        }
+    }
+
+    // The same mesh, with a color for each vertex.
+    //
+    // Panics if there isn't one color per position.
+    pub fn with_colors(mut self, colors: Vec<Vector3<f32>>) -> TriangleMesh {
+        assert_eq!(colors.len(), self.positions.len());
+        self.colors = colors;
+        self
+    }
+
+    // The total surface area of the mesh.
+    pub fn area(&self) -> f32 {
+        self.area_cdf.last().cloned().unwrap_or(0.0)
+    }
+
+    pub fn positions(&self) -> &[Point3<f32>] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[Vector3<f32>] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &[Point2<f32>] {
+        &self.uvs
+    }
+
+    pub fn colors(&self) -> &[Vector3<f32>] {
+        &self.colors
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    fn vertices(&self, triangle: usize) -> [&Point3<f32>; 3] {
+        let t = &self.indices[triangle];
+
+        [
+            &self.positions[t[0] as usize],
+            &self.positions[t[1] as usize],
+            &self.positions[t[2] as usize],
+        ]
+    }
+
+    // The normal of a triangle's plane, following the winding order.
+    fn face_normal(&self, triangle: usize) -> Vector3<f32> {
+        let p = self.vertices(triangle);
+        (p[1] - p[0]).cross(&(p[2] - p[0])).normalize()
+    }
+
+    // Find the closest triangle hit by the ray, returning its index.
+    fn intersects_triangle(&self, ray: &Ray) -> Option<(Intersection, usize)> {
+        self.bvh.intersects(ray, |triangle| {
+            intersect_triangle(ray, self.vertices(triangle)).map(|(t, u, v)| {
+                let (position, error) = triangle_point(self.vertices(triangle), (u, v));
+                let normal = self.normal_at(triangle, u, v);
+
+                // Vertex normals decide which side of the surface is the
+                // outside, even if the triangle is wound the other way.
+                let mut geometric_normal = self.face_normal(triangle);
+                if geometric_normal.dot(&normal) < 0.0 {
+                    geometric_normal = -geometric_normal;
+                }
+
+                Intersection::new(
+                    t,
+                    position,
+                    normal,
+                    self.uv_at(triangle, u, v),
+                    self.uv_density(triangle),
+                )
+                .with_error(error)
+                .with_geometric_normal(geometric_normal)
+                .with_barycentric(Point2::new(u, v))
+                .with_color(self.color_at(triangle, u, v))
+            })
+        })
+    }
+
+    // The normal at a point on a triangle given by its barycentric
+    // coordinates. Vertex normals are interpolated if the mesh has them,
+    // otherwise the face normal, following the winding order, is used.
+    fn normal_at(&self, triangle: usize, u: f32, v: f32) -> Vector3<f32> {
+        if self.normals.is_empty() {
+            return self.face_normal(triangle);
+        }
+
+        let t = &self.indices[triangle];
+        let normal = self.normals[t[0] as usize] * (1.0 - u - v)
+            + self.normals[t[1] as usize] * u
+            + self.normals[t[2] as usize] * v;
+
+        normal.normalize()
+    }
+
+    // The texture coordinates at a point on a triangle given by its
+    // barycentric coordinates. Without per-vertex texture coordinates, the
+    // barycentric coordinates themselves are used.
+    fn uv_at(&self, triangle: usize, u: f32, v: f32) -> Point2<f32> {
+        if self.uvs.is_empty() {
+            return Point2::new(u, v);
+        }
+
+        let t = &self.indices[triangle];
+        let uv = self.uvs[t[0] as usize].coords * (1.0 - u - v)
+            + self.uvs[t[1] as usize].coords * u
+            + self.uvs[t[2] as usize].coords * v;
+
+        Point2::from(uv)
+    }
+
+    // The vertex color at a point on a triangle given by its barycentric
+    // coordinates, if the mesh has vertex colors.
+    fn color_at(&self, triangle: usize, u: f32, v: f32) -> Option<Vector3<f32>> {
+        if self.colors.is_empty() {
+            return None;
+        }
+
+        let t = &self.indices[triangle];
+        Some(
+            self.colors[t[0] as usize] * (1.0 - u - v)
+                + self.colors[t[1] as usize] * u
+                + self.colors[t[2] as usize] * v,
+        )
+    }
+
+    // How quickly the texture coordinates change across a triangle, from the
+    // ratio of its area in texture space to its area in the scene.
+    fn uv_density(&self, triangle: usize) -> f32 {
+        let p = self.vertices(triangle);
+        if self.uvs.is_empty() {
+            return barycentric_density(&p);
+        }
+
+        let t = &self.indices[triangle];
+        let uv = [
+            self.uvs[t[0] as usize],
+            self.uvs[t[1] as usize],
+            self.uvs[t[2] as usize],
+        ];
+        let (e1, e2) = (uv[1] - uv[0], uv[2] - uv[0]);
+        let uv_area = (e1.x * e2.y - e1.y * e2.x).abs() / 2.0;
+        let area = (p[1] - p[0]).cross(&(p[2] - p[0])).norm() / 2.0;
+
+        (uv_area / area).sqrt()
+    }
+}
+
+impl Surface for TriangleMesh {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        self.intersects_triangle(ray)
+            .map(|(intersection, _)| intersection)
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.bvh.occluded(ray, |triangle| {
+            intersect_triangle(ray, self.vertices(triangle)).is_some()
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.bvh.bounds()
+    }
+
+    fn sample_towards(
+        &self,
+        reference: &Point3<f32>,
+        (u1, u2): (f32, f32),
+    ) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area <= 0.0 {
+            return None;
+        }
+
+        // Pick a triangle with probability proportional to its area, then
+        // stretch the part of `u1` that fell within it back out to [0, 1) so it
+        // can be reused to pick a point on the triangle.
+        let target = u1 * area;
+        let triangle = self
+            .area_cdf
+            .partition_point(|&a| a <= target)
+            .min(self.area_cdf.len() - 1);
+        let below = if triangle > 0 {
+            self.area_cdf[triangle - 1]
+        } else {
+            0.0
+        };
+        let u1 = ((target - below) / (self.area_cdf[triangle] - below)).min(1.0);
+
+        let ((u, v), normal, _) = sample_triangle(self.vertices(triangle), (u1, u2));
+        let (position, error) = triangle_point(self.vertices(triangle), (u, v));
+        Some(SurfaceSample {
+            position,
+            error,
+            normal,
+            uv: self.uv_at(triangle, u, v),
+            color: self.color_at(triangle, u, v),
+            pdf: area_to_solid_angle(1.0 / area, reference, &position, &normal),
+        })
+    }
+
+    fn pdf_towards(&self, reference: &Point3<f32>, direction: &Vector3<f32>) -> f32 {
+        let ray = Ray::new_from_air(*reference, *direction);
+
+        match self.intersects_triangle(&ray) {
+            Some((intersection, triangle)) => area_to_solid_angle(
+                1.0 / self.area(),
+                reference,
+                &intersection.position,
+                &self.face_normal(triangle),
+            ),
+            None => 0.0,
+        }
+    }
+}
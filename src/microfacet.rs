@@ -0,0 +1,725 @@
+use bsdf::{same_hemisphere, Bsdf, BsdfSample};
+use na::Vector3;
+use std::f32::consts::PI;
+use std::str::FromStr;
+
+// The shape of the distribution of microfacet normals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DistributionKind {
+    // Trowbridge-Reitz, also known as GGX, which has long tails and so keeps a
+    // soft glow around highlights.
+    Ggx,
+
+    // Beckmann-Spizzichino, a Gaussian distribution of slopes.
+    Beckmann,
+}
+
+impl FromStr for DistributionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DistributionKind, String> {
+        match s.to_lowercase().as_str() {
+            "ggx" | "trowbridge_reitz" => Ok(DistributionKind::Ggx),
+            "beckmann" => Ok(DistributionKind::Beckmann),
+            _ => Err(format!("unknown microfacet distribution `{}`", s)),
+        }
+    }
+}
+
+// A distribution of microfacet normals, which models a rough surface as many
+// tiny mirrors. Directions are in the local shading frame, where the
+// macroscopic normal is +z.
+//
+// The roughness is the RMS slope of the microfacets along the tangent and
+// bitangent of the frame, which can differ for anisotropic surfaces such as
+// brushed metal.
+//
+// Reference: Heitz, "Understanding the Masking-Shadowing Function in
+// Microfacet-Based BRDFs" (2014)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MicrofacetDistribution {
+    pub kind: DistributionKind,
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl MicrofacetDistribution {
+    pub fn new(kind: DistributionKind, alpha_x: f32, alpha_y: f32) -> MicrofacetDistribution {
+        MicrofacetDistribution {
+            kind,
+            alpha_x,
+            alpha_y,
+        }
+    }
+
+    // Surfaces this smooth are treated as perfect mirrors, as the
+    // distribution is too peaked to be evaluated accurately.
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    // The density of microfacets with normal `wm`, per unit solid angle and
+    // unit macroscopic area.
+    pub fn d(&self, wm: &Vector3<f32>) -> f32 {
+        let tan2 = tan2_theta(wm);
+        let cos4 = cos2_theta(wm) * cos2_theta(wm);
+        if !tan2.is_finite() || cos4 < 1e-16 {
+            return 0.0;
+        }
+
+        let e = tan2
+            * (cos2_phi(wm) / (self.alpha_x * self.alpha_x)
+                + sin2_phi(wm) / (self.alpha_y * self.alpha_y));
+
+        match self.kind {
+            DistributionKind::Ggx => {
+                1.0 / (PI * self.alpha_x * self.alpha_y * cos4 * (1.0 + e) * (1.0 + e))
+            }
+            DistributionKind::Beckmann => (-e).exp() / (PI * self.alpha_x * self.alpha_y * cos4),
+        }
+    }
+
+    // Smith's auxiliary function, the area of microfacets facing away from
+    // `w` per unit area of those facing it.
+    pub fn lambda(&self, w: &Vector3<f32>) -> f32 {
+        let tan2 = tan2_theta(w);
+        if !tan2.is_finite() {
+            return 0.0;
+        }
+
+        let alpha2 =
+            cos2_phi(w) * self.alpha_x * self.alpha_x + sin2_phi(w) * self.alpha_y * self.alpha_y;
+
+        match self.kind {
+            DistributionKind::Ggx => ((1.0 + alpha2 * tan2).sqrt() - 1.0) / 2.0,
+            DistributionKind::Beckmann => {
+                if tan2 == 0.0 {
+                    return 0.0;
+                }
+
+                let a = 1.0 / (alpha2 * tan2).sqrt();
+                ((erf(a) - 1.0) / 2.0 + (-a * a).exp() / (2.0 * a * PI.sqrt())).max(0.0)
+            }
+        }
+    }
+
+    // The fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vector3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // The fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // The density of microfacet normals as seen from `w`, which is what
+    // `sample_wm` samples, with respect to solid angle. Directions below the
+    // surface see the normals that face away from them.
+    pub fn visible_d(&self, w: &Vector3<f32>, wm: &Vector3<f32>) -> f32 {
+        if w.z == 0.0 {
+            return 0.0;
+        }
+
+        let w = if w.z < 0.0 { -w } else { *w };
+        self.g1(&w) / w.z * self.d(wm) * w.dot(wm).max(0.0)
+    }
+
+    // Sample a microfacet normal that is visible from `w`, in the upper
+    // hemisphere.
+    pub fn sample_wm(&self, w: &Vector3<f32>, u: (f32, f32)) -> Vector3<f32> {
+        // Sampling is symmetric about the surface, so work from above it.
+        let w = if w.z < 0.0 { -w } else { *w };
+
+        // Stretch the direction so that the distribution becomes isotropic
+        // with unit roughness.
+        let stretched = Vector3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+
+        let wm = match self.kind {
+            DistributionKind::Ggx => {
+                // Sample the projection of the hemisphere of normals
+                // visible from the direction.
+                //
+                // Reference: Heitz, "Sampling the GGX Distribution of Visible
+                // Normals" (2018)
+                let t1 = if stretched.z < 0.99999 {
+                    Vector3::z().cross(&stretched).normalize()
+                } else {
+                    Vector3::x()
+                };
+                let t2 = stretched.cross(&t1);
+
+                let r = u.0.sqrt();
+                let phi = 2.0 * PI * u.1;
+                let (px, py) = (r * phi.cos(), r * phi.sin());
+
+                // Warp the disk to the visible part of the hemisphere.
+                let h = (1.0 - px * px).sqrt();
+                let s = (1.0 + stretched.z) / 2.0;
+                let py = (1.0 - s) * h + s * py;
+                let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+                let nh = t1 * px + t2 * py + stretched * pz;
+                Vector3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6))
+            }
+            DistributionKind::Beckmann => {
+                // Sample the slopes of visible microfacets for a direction in
+                // the xz-plane, then rotate them around to the direction.
+                //
+                // Reference: Jakob, "An Improved Visible Normal Sampling
+                // Routine for the Beckmann Distribution" (2014)
+                let (sx, sy) = beckmann_sample_slopes(stretched.z, u);
+
+                let (cos_phi, sin_phi) = (cos_phi(&stretched), sin_phi(&stretched));
+                let (sx, sy) = (cos_phi * sx - sin_phi * sy, sin_phi * sx + cos_phi * sy);
+
+                Vector3::new(-self.alpha_x * sx, -self.alpha_y * sy, 1.0)
+            }
+        };
+
+        wm.normalize()
+    }
+}
+
+// Sample the slopes of the Beckmann distribution with unit roughness, as seen
+// from a direction in the xz-plane with the given cosine.
+fn beckmann_sample_slopes(cos_theta: f32, (u1, u2): (f32, f32)) -> (f32, f32) {
+    // Seen from above, this is just the Gaussian.
+    if cos_theta > 0.9999 {
+        let r = (-(1.0 - u1).ln()).sqrt();
+        let phi = 2.0 * PI * u2;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1.0 / tan_theta;
+    let sqrt_pi_inv = 1.0 / PI.sqrt();
+
+    // Invert the CDF of the slope along x by Newton-bisection, starting from
+    // a fitted guess.
+    let mut a = -1.0;
+    let mut c = erf(cot_theta);
+    let sample_x = u1.max(1e-6);
+
+    let theta = cos_theta.acos();
+    let fit = 1.0 + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1.0 + c) * (1.0 - sample_x).powf(fit);
+
+    let normalization = 1.0 / (1.0 + c + sqrt_pi_inv * tan_theta * (-cot_theta * cot_theta).exp());
+
+    for _ in 0..10 {
+        if !(b >= a && b <= c) {
+            b = 0.5 * (a + c);
+        }
+
+        let inv_erf = erf_inv(b);
+        let value = normalization
+            * (1.0 + b + sqrt_pi_inv * tan_theta * (-inv_erf * inv_erf).exp())
+            - sample_x;
+        let derivative = normalization * (1.0 - inv_erf * tan_theta);
+
+        if value.abs() < 1e-5 {
+            break;
+        }
+
+        if value > 0.0 {
+            c = b;
+        } else {
+            a = b;
+        }
+        b -= value / derivative;
+    }
+
+    (erf_inv(b), erf_inv(2.0 * u2.max(1e-6) - 1.0))
+}
+
+// The error function, to within 1.5e-7.
+//
+// Reference: Abramowitz and Stegun, formula 7.1.26
+fn erf(x: f32) -> f32 {
+    let (a1, a2, a3, a4, a5, p) = (
+        0.254_829_6,
+        -0.284_496_74,
+        1.421_413_7,
+        -1.453_152,
+        1.061_405_4,
+        0.327_591_1,
+    );
+
+    let sign = x.signum();
+    let x = x.abs();
+    let t = 1.0 / (1.0 + p * x);
+    let y = 1.0 - ((((a5 * t + a4) * t + a3) * t + a2) * t + a1) * t * (-x * x).exp();
+
+    sign * y
+}
+
+// The inverse of the error function.
+//
+// Reference: Giles, "Approximating the erfinv function" (2010)
+fn erf_inv(x: f32) -> f32 {
+    let x = x.clamp(-0.99999, 0.99999);
+    let mut w = -((1.0 - x) * (1.0 + x)).ln();
+
+    let p = if w < 5.0 {
+        w -= 2.5;
+        let mut p = 2.810_226_4e-08;
+        p = 3.432_739_4e-07 + p * w;
+        p = -3.523_387_7e-06 + p * w;
+        p = -4.391_506_5e-06 + p * w;
+        p = 0.000_218_580_87 + p * w;
+        p = -0.001_253_725 + p * w;
+        p = -0.004_177_681_6 + p * w;
+        p = 0.246_640_73 + p * w;
+        1.501_409_4 + p * w
+    } else {
+        w = w.sqrt() - 3.0;
+        let mut p = -0.000_200_214_26;
+        p = 0.000_100_950_56 + p * w;
+        p = 0.001_349_343_2 + p * w;
+        p = -0.003_673_428_4 + p * w;
+        p = 0.005_739_507_7 + p * w;
+        p = -0.007_622_461 + p * w;
+        p = 0.009_438_870_5 + p * w;
+        p = 1.001_674 + p * w;
+        2.832_976_8 + p * w
+    };
+
+    p * x
+}
+
+fn cos2_theta(w: &Vector3<f32>) -> f32 {
+    w.z * w.z
+}
+
+fn sin2_theta(w: &Vector3<f32>) -> f32 {
+    (1.0 - cos2_theta(w)).max(0.0)
+}
+
+fn tan2_theta(w: &Vector3<f32>) -> f32 {
+    sin2_theta(w) / cos2_theta(w)
+}
+
+fn cos_phi(w: &Vector3<f32>) -> f32 {
+    let sin_theta = sin2_theta(w).sqrt();
+    if sin_theta == 0.0 {
+        1.0
+    } else {
+        (w.x / sin_theta).clamp(-1.0, 1.0)
+    }
+}
+
+fn sin_phi(w: &Vector3<f32>) -> f32 {
+    let sin_theta = sin2_theta(w).sqrt();
+    if sin_theta == 0.0 {
+        0.0
+    } else {
+        (w.y / sin_theta).clamp(-1.0, 1.0)
+    }
+}
+
+fn cos2_phi(w: &Vector3<f32>) -> f32 {
+    cos_phi(w) * cos_phi(w)
+}
+
+fn sin2_phi(w: &Vector3<f32>) -> f32 {
+    sin_phi(w) * sin_phi(w)
+}
+
+// Mirror `wo` about the microfacet normal `wm`.
+fn reflect_about(wo: &Vector3<f32>, wm: &Vector3<f32>) -> Vector3<f32> {
+    -wo + wm * (2.0 * wo.dot(wm))
+}
+
+// Refract `wo` through a microfacet with normal `wm`, where `eta` is the
+// index of refraction below the surface relative to that above it. Returns
+// the direction and the relative index of refraction along the path, or
+// `None` for total internal reflection.
+fn refract_about(wo: &Vector3<f32>, wm: &Vector3<f32>, eta: f32) -> Option<(Vector3<f32>, f32)> {
+    let mut cos_i = wo.dot(wm);
+    let (mut eta, mut n) = (eta, *wm);
+    if cos_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+        n = -n;
+    }
+
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    Some((-wo / eta + n * (cos_i / eta - cos_t), eta))
+}
+
+// The fraction of light reflected by a smooth boundary between two
+// dielectrics, where `eta` is the index of refraction below the surface
+// relative to that above it, and `cos_i` is the cosine of the angle of the
+// light to the normal, negative for light from below.
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let mut cos_i = cos_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    if cos_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_i = -cos_i;
+    }
+
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // Total internal reflection.
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).max(0.0).sqrt();
+
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+// The fraction of light reflected by a conductor with the complex index of
+// refraction `eta + ik`, for each color channel.
+//
+// Reference: https://seblagarde.wordpress.com/2013/04/29/memo-on-fresnel-equations/
+pub fn fresnel_conductor(cos_i: f32, eta: &Vector3<f32>, k: &Vector3<f32>) -> Vector3<f32> {
+    let cos_i = cos_i.abs().min(1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+
+    eta.zip_map(k, |eta, k| {
+        let (eta2, k2) = (eta * eta, k * k);
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        (rp + rs) / 2.0
+    })
+}
+
+// Schlick's approximation of the Fresnel equations, from the reflectance at
+// normal incidence.
+pub fn fresnel_schlick(cos_i: f32, f0: &Vector3<f32>) -> Vector3<f32> {
+    let weight = (1.0 - cos_i.abs().min(1.0)).powi(5);
+    f0 + (Vector3::repeat(1.0) - f0) * weight
+}
+
+// The fraction of light reflected by a microfacet, depending on the angle it
+// is seen at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fresnel {
+    // A conductor with the complex index of refraction eta + ik for each
+    // color channel.
+    Conductor { eta: Vector3<f32>, k: Vector3<f32> },
+
+    // Schlick's approximation, from the reflectance at normal incidence.
+    Schlick(Vector3<f32>),
+}
+
+impl Fresnel {
+    pub fn evaluate(&self, cos_i: f32) -> Vector3<f32> {
+        match *self {
+            Fresnel::Conductor { ref eta, ref k } => fresnel_conductor(cos_i, eta, k),
+            Fresnel::Schlick(ref f0) => fresnel_schlick(cos_i, f0),
+        }
+    }
+}
+
+// Reflection from a rough surface, such as a metal, which doesn't let any
+// light through. Both sides of the surface reflect.
+pub struct MicrofacetReflectionBsdf {
+    pub distribution: MicrofacetDistribution,
+    pub fresnel: Fresnel,
+}
+
+// Reflect a direction below the surface to above it, so that two-sided
+// BSDFs can be evaluated from above.
+fn upper(w: &Vector3<f32>, flip: bool) -> Vector3<f32> {
+    if flip {
+        Vector3::new(w.x, w.y, -w.z)
+    } else {
+        *w
+    }
+}
+
+impl Bsdf for MicrofacetReflectionBsdf {
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        if !same_hemisphere(wo, wi) || self.distribution.effectively_smooth() {
+            return Vector3::zeros();
+        }
+
+        let flip = wo.z < 0.0;
+        let (wo, wi) = (upper(wo, flip), upper(wi, flip));
+
+        let wm = wo + wi;
+        if wm.norm_squared() == 0.0 {
+            return Vector3::zeros();
+        }
+        let wm = wm.normalize();
+
+        let fresnel = self.fresnel.evaluate(wo.dot(&wm));
+        fresnel * (self.distribution.d(&wm) * self.distribution.g(&wo, &wi) / (4.0 * wo.z * wi.z))
+    }
+
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        if !same_hemisphere(wo, wi) || self.distribution.effectively_smooth() {
+            return 0.0;
+        }
+
+        let flip = wo.z < 0.0;
+        let (wo, wi) = (upper(wo, flip), upper(wi, flip));
+
+        let wm = wo + wi;
+        if wm.norm_squared() == 0.0 {
+            return 0.0;
+        }
+        let wm = wm.normalize();
+
+        self.distribution.visible_d(&wo, &wm) / (4.0 * wo.dot(&wm).abs())
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, _u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        let flip = wo.z < 0.0;
+
+        if self.distribution.effectively_smooth() {
+            let wi = Vector3::new(-wo.x, -wo.y, wo.z);
+            return Some(BsdfSample {
+                wi,
+                f: self.fresnel.evaluate(wi.z) / wi.z.abs(),
+                pdf: 1.0,
+                specular: true,
+                index_of_refraction: None,
+            });
+        }
+
+        let wo_upper = upper(wo, flip);
+        let wm = self.distribution.sample_wm(&wo_upper, u);
+        let wi_upper = reflect_about(&wo_upper, &wm);
+        if wi_upper.z <= 0.0 {
+            return None;
+        }
+
+        let wi = upper(&wi_upper, flip);
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf,
+            specular: false,
+            index_of_refraction: None,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        self.distribution.effectively_smooth()
+    }
+}
+
+// A boundary between two transparent media, such as frosted glass, which
+// reflects and refracts light in proportion to the Fresnel equations. When
+// the distribution is smooth, this is a perfectly clear surface.
+//
+// Reference: Walter et al., "Microfacet Models for Refraction through Rough
+// Surfaces" (2007)
+pub struct DielectricBsdf {
+    pub distribution: MicrofacetDistribution,
+
+    // The indices of refraction on the side the normal points to and on the
+    // inside of the surface.
+    pub outside: f32,
+    pub inside: f32,
+}
+
+impl DielectricBsdf {
+    // The index of refraction inside relative to outside.
+    fn eta(&self) -> f32 {
+        self.inside / self.outside
+    }
+
+    // Whether the surface scatters like a smooth one. A boundary between
+    // media with the same index of refraction doesn't bend light at all, and
+    // its half vector is undefined.
+    fn is_smooth(&self) -> bool {
+        self.distribution.effectively_smooth() || self.eta() == 1.0
+    }
+
+    // The index of refraction on the far side of the surface from `wo`.
+    fn far_side(&self, wo: &Vector3<f32>) -> f32 {
+        if wo.z > 0.0 {
+            self.inside
+        } else {
+            self.outside
+        }
+    }
+
+    // The microfacet normal which scatters light between `wo` and `wi`, in
+    // the upper hemisphere, along with the relative index of refraction
+    // along the path. Returns `None` for pairs of directions which can't be
+    // connected by a microfacet facing both.
+    fn half_vector(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Option<(Vector3<f32>, f32)> {
+        if wo.z == 0.0 || wi.z == 0.0 {
+            return None;
+        }
+
+        let etap = if same_hemisphere(wo, wi) {
+            1.0
+        } else if wo.z > 0.0 {
+            self.eta()
+        } else {
+            1.0 / self.eta()
+        };
+
+        let wm = wi * etap + wo;
+        if wm.norm_squared() == 0.0 {
+            return None;
+        }
+        let wm = wm.normalize();
+        let wm = if wm.z < 0.0 { -wm } else { wm };
+
+        // Microfacets seen from behind don't contribute.
+        if wm.dot(wi) * wi.z < 0.0 || wm.dot(wo) * wo.z < 0.0 {
+            return None;
+        }
+
+        Some((wm, etap))
+    }
+
+    fn sample_smooth(&self, wo: &Vector3<f32>, u_lobe: f32) -> Option<BsdfSample> {
+        let r = fresnel_dielectric(wo.z, self.eta());
+        let t = 1.0 - r;
+
+        if u_lobe < r {
+            let wi = Vector3::new(-wo.x, -wo.y, wo.z);
+            return Some(BsdfSample {
+                wi,
+                f: Vector3::repeat(r / wi.z.abs()),
+                pdf: r,
+                specular: true,
+                index_of_refraction: None,
+            });
+        }
+
+        let (wi, etap) = refract_about(wo, &Vector3::z(), self.eta())?;
+        if wi.z == 0.0 {
+            return None;
+        }
+
+        // Radiance is compressed into a smaller solid angle on entering a
+        // denser medium.
+        Some(BsdfSample {
+            wi,
+            f: Vector3::repeat(t / wi.z.abs() / (etap * etap)),
+            pdf: t,
+            specular: true,
+            index_of_refraction: Some(self.far_side(wo)),
+        })
+    }
+}
+
+impl Bsdf for DielectricBsdf {
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        if self.is_smooth() {
+            return Vector3::zeros();
+        }
+
+        let (wm, etap) = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return Vector3::zeros(),
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(&wm), self.eta());
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(wo, wi);
+
+        let f = if same_hemisphere(wo, wi) {
+            d * g * fresnel / (4.0 * wi.z * wo.z).abs()
+        } else {
+            let denominator = (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2) * wi.z * wo.z;
+            d * (1.0 - fresnel) * g * (wi.dot(&wm) * wo.dot(&wm) / denominator).abs()
+                / (etap * etap)
+        };
+
+        Vector3::repeat(f)
+    }
+
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        if self.is_smooth() {
+            return 0.0;
+        }
+
+        let (wm, etap) = match self.half_vector(wo, wi) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+
+        // Reflection and transmission are chosen in proportion to the
+        // Fresnel equations.
+        let r = fresnel_dielectric(wo.dot(&wm), self.eta());
+        let visible = self.distribution.visible_d(wo, &wm);
+
+        if same_hemisphere(wo, wi) {
+            visible / (4.0 * wo.dot(&wm).abs()) * r
+        } else {
+            let denominator = (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2);
+            visible * wi.dot(&wm).abs() / denominator * (1.0 - r)
+        }
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+        if self.is_smooth() {
+            return self.sample_smooth(wo, u_lobe);
+        }
+
+        let wm = self.distribution.sample_wm(wo, u);
+        let r = fresnel_dielectric(wo.dot(&wm), self.eta());
+
+        let (wi, index_of_refraction) = if u_lobe < r {
+            let wi = reflect_about(wo, &wm);
+            if !same_hemisphere(wo, &wi) {
+                return None;
+            }
+            (wi, None)
+        } else {
+            let (wi, _) = refract_about(wo, &wm, self.eta())?;
+            if same_hemisphere(wo, &wi) || wi.z == 0.0 {
+                return None;
+            }
+            (wi, Some(self.far_side(wo)))
+        };
+
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf,
+            specular: false,
+            index_of_refraction,
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        self.is_smooth()
+    }
+}
@@ -5,3 +5,15 @@ pub struct Object {
     pub surface: Box<dyn Surface>,
     pub material: MaterialBox,
 }
+
+impl Object {
+    // Whether the object should be sampled directly as a light source. Only
+    // finite emitters are sampled; infinite ones, like planes, are only found
+    // by rays that happen to hit them.
+    pub fn is_light(&self) -> bool {
+        match self.material {
+            MaterialBox::Emissive(_) => self.surface.bounds().is_some(),
+            MaterialBox::Reflective(_) => false,
+        }
+    }
+}
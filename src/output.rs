@@ -0,0 +1,163 @@
+use exr;
+use image::{self, DynamicImage, ImageError, ImageOutputFormat};
+use na::{DMatrix, Vector3};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+use tonemap::ToneMapping;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    Jpeg,
+    Bmp,
+    Tga,
+
+    // High dynamic range formats, which store the rendered radiance as
+    // floating point without clamping.
+    Hdr,
+    Exr,
+}
+
+impl OutputFormat {
+    // Guess the output format from the extension of a file name.
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse().ok())
+    }
+
+    // Whether the format stores colors beyond [0, 1] without loss.
+    pub fn is_hdr(&self) -> bool {
+        matches!(*self, OutputFormat::Hdr | OutputFormat::Exr)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<OutputFormat, String> {
+        match s.to_lowercase().as_str() {
+            "ppm" => Ok(OutputFormat::Ppm),
+            "png" => Ok(OutputFormat::Png),
+            "jpg" | "jpeg" => Ok(OutputFormat::Jpeg),
+            "bmp" => Ok(OutputFormat::Bmp),
+            "tga" => Ok(OutputFormat::Tga),
+            "hdr" => Ok(OutputFormat::Hdr),
+            "exr" => Ok(OutputFormat::Exr),
+            _ => Err(format!("unknown output format `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    Io(io::Error),
+    Image(ImageError),
+    Exr(exr::error::Error),
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OutputError::Io(ref err) => write!(f, "{}", err),
+            OutputError::Image(ref err) => write!(f, "{}", err),
+            OutputError::Exr(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for OutputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            OutputError::Io(ref err) => Some(err),
+            OutputError::Image(ref err) => Some(err),
+            OutputError::Exr(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for OutputError {
+    fn from(err: io::Error) -> OutputError {
+        OutputError::Io(err)
+    }
+}
+
+impl From<ImageError> for OutputError {
+    fn from(err: ImageError) -> OutputError {
+        OutputError::Image(err)
+    }
+}
+
+impl From<exr::error::Error> for OutputError {
+    fn from(err: exr::error::Error) -> OutputError {
+        OutputError::Exr(err)
+    }
+}
+
+// Write the rendered screen to a file. For low dynamic range formats, colors
+// are tone mapped, sRGB encoded and quantized to 8 bits per channel. High
+// dynamic range formats store the radiance as is and ignore `tonemap`.
+pub fn write_image(
+    path: &Path,
+    screen: &DMatrix<Vector3<f32>>,
+    format: OutputFormat,
+    tonemap: &ToneMapping,
+) -> Result<(), OutputError> {
+    // The screen is indexed by (x, y), so rows are columns of the image.
+    let size = (screen.nrows(), screen.ncols());
+
+    let format = match format {
+        OutputFormat::Hdr => return write_hdr(path, screen),
+        OutputFormat::Exr => {
+            exr::prelude::write_rgb_file(path, size.0, size.1, |x, y| {
+                let color = screen[(x, y)];
+                (color.x, color.y, color.z)
+            })?;
+            return Ok(());
+        }
+        OutputFormat::Ppm => ImageOutputFormat::Pnm(image::pnm::PNMSubtype::Pixmap(
+            image::pnm::SampleEncoding::Binary,
+        )),
+        OutputFormat::Png => ImageOutputFormat::Png,
+        OutputFormat::Jpeg => ImageOutputFormat::Jpeg(95),
+        OutputFormat::Bmp => ImageOutputFormat::Bmp,
+        OutputFormat::Tga => ImageOutputFormat::Tga,
+    };
+
+    let mut imbuf = image::ImageBuffer::new(size.0 as u32, size.1 as u32);
+    for x in 0..size.0 {
+        for y in 0..size.1 {
+            let pixel = tonemap.to_srgb8(screen[(x, y)], x, y);
+            imbuf.put_pixel(x as u32, y as u32, image::Rgb(pixel));
+        }
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    DynamicImage::ImageRgb8(imbuf).write_to(&mut writer, format)?;
+
+    Ok(())
+}
+
+// Write a Radiance RGBE image.
+fn write_hdr(path: &Path, screen: &DMatrix<Vector3<f32>>) -> Result<(), OutputError> {
+    let (width, height) = (screen.nrows(), screen.ncols());
+
+    // The encoder takes pixels in row-major order.
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = screen[(x, y)];
+            pixels.push(image::Rgb([color.x, color.y, color.z]));
+        }
+    }
+
+    let writer = BufWriter::new(File::create(path)?);
+    image::hdr::HdrEncoder::new(writer).encode(&pixels, width, height)?;
+
+    Ok(())
+}
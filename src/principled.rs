@@ -0,0 +1,416 @@
+use bsdf::{same_hemisphere, Bsdf, BsdfSample};
+use microfacet::{
+    fresnel_schlick, DielectricBsdf, DistributionKind, Fresnel, MicrofacetDistribution,
+    MicrofacetReflectionBsdf,
+};
+use na::Vector3;
+use sampling::cosine_hemisphere;
+use std::f32::consts::PI;
+use tonemap::luminance;
+
+// The parameters of the principled BSDF. Apart from the color and the index of
+// refraction, each is in [0, 1].
+//
+// Reference: Burley, "Physically Based Shading at Disney" (2012)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrincipledParameters {
+    // The color of diffuse reflection or, for metals, of specular reflection.
+    pub base_color: Vector3<f32>,
+
+    // Blends from a dielectric, such as plastic, to a metal.
+    pub metallic: f32,
+
+    // Perceptual roughness of the specular and diffuse reflection.
+    pub roughness: f32,
+
+    // Stretches highlights along the tangent of the surface.
+    pub anisotropic: f32,
+
+    // The strength of specular reflection from dielectrics, with 0.5 for a
+    // reflectance of 4% at normal incidence.
+    pub specular: f32,
+
+    // Tints specular reflection from dielectrics towards the base color.
+    pub specular_tint: f32,
+
+    // A soft glow at grazing angles, as on cloth.
+    pub sheen: f32,
+    pub sheen_tint: f32,
+
+    // A second, colorless specular layer, as on car paint.
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+
+    // Blends from an opaque surface to one that refracts light, such as
+    // glass.
+    pub transmission: f32,
+
+    // Flattens the diffuse reflection to imitate light scattering beneath the
+    // surface, as in skin or wax.
+    pub subsurface: f32,
+
+    // The index of refraction of transmissive materials.
+    pub index_of_refraction: f32,
+}
+
+impl Default for PrincipledParameters {
+    fn default() -> PrincipledParameters {
+        PrincipledParameters {
+            base_color: Vector3::repeat(0.8),
+            metallic: 0.0,
+            roughness: 0.5,
+            anisotropic: 0.0,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            subsurface: 0.0,
+            index_of_refraction: 1.5,
+        }
+    }
+}
+
+fn lerp(t: f32, a: Vector3<f32>, b: Vector3<f32>) -> Vector3<f32> {
+    a * (1.0 - t) + b * t
+}
+
+fn schlick_weight(cos: f32) -> f32 {
+    (1.0 - cos.abs().min(1.0)).powi(5)
+}
+
+// The lobes of the principled BSDF, in the order that they are chosen from
+// when sampling.
+const DIFFUSE: usize = 0;
+const SPECULAR: usize = 1;
+const CLEARCOAT: usize = 2;
+const TRANSMISSION: usize = 3;
+
+// A single BSDF that covers most materials, from plastics and metals to glass,
+// as a weighted sum of diffuse, specular, clearcoat and transmission lobes.
+pub struct PrincipledBsdf {
+    parameters: PrincipledParameters,
+    sheen_color: Vector3<f32>,
+    transmission_color: Vector3<f32>,
+    specular: MicrofacetReflectionBsdf,
+    clearcoat: ClearcoatBsdf,
+    transmission: DielectricBsdf,
+
+    // The weight of each lobe in the sum, and the probability of sampling it.
+    weights: [f32; 4],
+    probabilities: [f32; 4],
+}
+
+impl PrincipledBsdf {
+    // `outside` is the index of refraction of the medium on the side the
+    // normal points to.
+    pub fn new(parameters: &PrincipledParameters, outside: f32) -> PrincipledBsdf {
+        let p = parameters;
+        let white = Vector3::repeat(1.0);
+
+        // The hue and saturation of the base color, without its brightness.
+        let base_luminance = luminance(&p.base_color);
+        let tint = if base_luminance > 0.0 {
+            p.base_color / base_luminance
+        } else {
+            white
+        };
+
+        // Dielectrics reflect 8% at most at normal incidence, while metals
+        // reflect their base color.
+        let specular_color = lerp(
+            p.metallic,
+            lerp(p.specular_tint, white, tint) * (0.08 * p.specular),
+            p.base_color,
+        );
+
+        let aspect = (1.0 - 0.9 * p.anisotropic).sqrt();
+        let alpha = p.roughness * p.roughness;
+        let distribution =
+            MicrofacetDistribution::new(DistributionKind::Ggx, alpha / aspect, alpha * aspect);
+
+        let dielectric = (1.0 - p.metallic) * (1.0 - p.transmission);
+        let transmissive = (1.0 - p.metallic) * p.transmission;
+        let weights = [
+            dielectric,
+            1.0 - transmissive,
+            0.25 * p.clearcoat,
+            transmissive,
+        ];
+
+        let total: f32 = weights.iter().sum();
+        let mut probabilities = [0.0; 4];
+        for (probability, weight) in probabilities.iter_mut().zip(&weights) {
+            *probability = weight / total;
+        }
+
+        PrincipledBsdf {
+            parameters: *p,
+            sheen_color: lerp(p.sheen_tint, white, tint) * p.sheen,
+            // Light is tinted once on the way into an object, and once on the
+            // way out.
+            transmission_color: p.base_color.map(|c| c.max(0.0).sqrt()),
+            specular: MicrofacetReflectionBsdf {
+                distribution,
+                fresnel: Fresnel::Schlick(specular_color),
+            },
+            clearcoat: ClearcoatBsdf {
+                alpha: 0.1 + (0.001 - 0.1) * p.clearcoat_gloss,
+            },
+            transmission: DielectricBsdf {
+                distribution,
+                outside,
+                inside: p.index_of_refraction,
+            },
+            weights,
+            probabilities,
+        }
+    }
+
+    // Burley's diffuse reflection, which darkens at grazing angles for smooth
+    // surfaces and brightens for rough ones, plus sheen.
+    fn diffuse(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        if !same_hemisphere(wo, wi) {
+            return Vector3::zeros();
+        }
+
+        let p = &self.parameters;
+        let (cos_o, cos_i) = (wo.z.abs(), wi.z.abs());
+        let half = wo + wi;
+        let cos_d = if half.norm_squared() > 0.0 {
+            wi.dot(&half.normalize()).abs()
+        } else {
+            1.0
+        };
+
+        let (fo, fi) = (schlick_weight(cos_o), schlick_weight(cos_i));
+        let fd90 = 0.5 + 2.0 * cos_d * cos_d * p.roughness;
+        let fd = (1.0 + (fd90 - 1.0) * fo) * (1.0 + (fd90 - 1.0) * fi);
+
+        // Hanrahan-Krueger inspired approximation of subsurface scattering.
+        let fss90 = cos_d * cos_d * p.roughness;
+        let fss = (1.0 + (fss90 - 1.0) * fo) * (1.0 + (fss90 - 1.0) * fi);
+        let ss = 1.25 * (fss * (1.0 / (cos_o + cos_i) - 0.5) + 0.5);
+
+        let diffuse = p.base_color * ((fd + (ss - fd) * p.subsurface) / PI);
+        diffuse + self.sheen_color * schlick_weight(cos_d)
+    }
+
+    fn lobe_pdf(&self, lobe: usize, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        match lobe {
+            DIFFUSE => {
+                if same_hemisphere(wo, wi) {
+                    wi.z.abs() / PI
+                } else {
+                    0.0
+                }
+            }
+            SPECULAR => self.specular.pdf(wo, wi),
+            CLEARCOAT => self.clearcoat.pdf(wo, wi),
+            _ => self.transmission.pdf(wo, wi),
+        }
+    }
+
+    fn transmission_eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        let f = self.transmission.eval(wo, wi);
+        if same_hemisphere(wo, wi) {
+            f
+        } else {
+            f.component_mul(&self.transmission_color)
+        }
+    }
+}
+
+impl Bsdf for PrincipledBsdf {
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        let w = &self.weights;
+        let mut f = Vector3::zeros();
+
+        if w[DIFFUSE] > 0.0 {
+            f += self.diffuse(wo, wi) * w[DIFFUSE];
+        }
+        if w[SPECULAR] > 0.0 {
+            f += self.specular.eval(wo, wi) * w[SPECULAR];
+        }
+        if w[CLEARCOAT] > 0.0 {
+            f += self.clearcoat.eval(wo, wi) * w[CLEARCOAT];
+        }
+        if w[TRANSMISSION] > 0.0 {
+            f += self.transmission_eval(wo, wi) * w[TRANSMISSION];
+        }
+
+        f
+    }
+
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        (0..4)
+            .filter(|&lobe| self.probabilities[lobe] > 0.0)
+            .map(|lobe| self.probabilities[lobe] * self.lobe_pdf(lobe, wo, wi))
+            .sum()
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, u_lobe: f32, u: (f32, f32)) -> Option<BsdfSample> {
+        if wo.z == 0.0 {
+            return None;
+        }
+
+        // Choose a lobe, and stretch what is left of `u_lobe` back over
+        // [0, 1) for the lobe to use.
+        let (mut lobe, mut u_lobe) = (0, u_lobe);
+        for (i, &probability) in self.probabilities.iter().enumerate() {
+            if probability == 0.0 {
+                continue;
+            }
+
+            lobe = i;
+            if u_lobe < probability {
+                break;
+            }
+            u_lobe -= probability;
+        }
+        let probability = self.probabilities[lobe];
+        let u_lobe = (u_lobe / probability).clamp(0.0, 1.0 - f32::EPSILON);
+
+        let sample = match lobe {
+            DIFFUSE => {
+                let mut wi = cosine_hemisphere(u);
+                if wo.z < 0.0 {
+                    wi.z = -wi.z;
+                }
+                BsdfSample {
+                    wi,
+                    f: Vector3::zeros(),
+                    pdf: 0.0,
+                    specular: false,
+                    index_of_refraction: None,
+                }
+            }
+            SPECULAR => self.specular.sample(wo, u_lobe, u)?,
+            CLEARCOAT => self.clearcoat.sample(wo, u_lobe, u)?,
+            _ => self.transmission.sample(wo, u_lobe, u)?,
+        };
+
+        // Specular directions can only have come from the chosen lobe.
+        if sample.specular {
+            let f = if lobe == TRANSMISSION && !same_hemisphere(wo, &sample.wi) {
+                sample.f.component_mul(&self.transmission_color)
+            } else {
+                sample.f
+            };
+
+            return Some(BsdfSample {
+                f: f * self.weights[lobe],
+                pdf: sample.pdf * probability,
+                ..sample
+            });
+        }
+
+        let pdf = self.pdf(wo, &sample.wi);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            f: self.eval(wo, &sample.wi),
+            pdf,
+            ..sample
+        })
+    }
+
+    fn is_specular(&self) -> bool {
+        let specular = [
+            false,
+            self.specular.is_specular(),
+            false,
+            self.transmission.is_specular(),
+        ];
+
+        (0..4).all(|lobe| self.probabilities[lobe] == 0.0 || specular[lobe])
+    }
+}
+
+// A colorless specular layer, with the long-tailed GTR1 distribution of
+// normals and a reflectance of 4% at normal incidence.
+struct ClearcoatBsdf {
+    alpha: f32,
+}
+
+impl ClearcoatBsdf {
+    // Berry's distribution of microfacet normals, GTR with γ = 1.
+    fn d(&self, cos_h: f32) -> f32 {
+        let a2 = self.alpha * self.alpha;
+        if a2 >= 1.0 {
+            return 1.0 / PI;
+        }
+
+        (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+    }
+
+    // The half vector between two directions on the same side, flipped
+    // above the surface.
+    fn half_vector(wo: &Vector3<f32>, wi: &Vector3<f32>) -> Option<Vector3<f32>> {
+        if !same_hemisphere(wo, wi) {
+            return None;
+        }
+
+        let half = wo + wi;
+        if half.norm_squared() == 0.0 {
+            return None;
+        }
+
+        let half = half.normalize();
+        Some(if half.z < 0.0 { -half } else { half })
+    }
+}
+
+impl Bsdf for ClearcoatBsdf {
+    fn eval(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> Vector3<f32> {
+        let half = match ClearcoatBsdf::half_vector(wo, wi) {
+            Some(half) => half,
+            None => return Vector3::zeros(),
+        };
+
+        // Masking is approximated by GGX with a fixed roughness.
+        let masking = MicrofacetDistribution::new(DistributionKind::Ggx, 0.25, 0.25);
+        let g = masking.g1(wo) * masking.g1(wi);
+        let f = fresnel_schlick(wo.dot(&half), &Vector3::repeat(0.04));
+
+        f * (self.d(half.z) * g / (4.0 * wo.z.abs() * wi.z.abs()))
+    }
+
+    fn pdf(&self, wo: &Vector3<f32>, wi: &Vector3<f32>) -> f32 {
+        match ClearcoatBsdf::half_vector(wo, wi) {
+            Some(half) => self.d(half.z) * half.z / (4.0 * wo.dot(&half).abs()),
+            None => 0.0,
+        }
+    }
+
+    fn sample(&self, wo: &Vector3<f32>, _u_lobe: f32, (u1, u2): (f32, f32)) -> Option<BsdfSample> {
+        // Sample the distribution of normals, weighted by their cosine.
+        let a2 = self.alpha * self.alpha;
+        let cos_h = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        let mut half = Vector3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h);
+        if wo.z < 0.0 {
+            half = -half;
+        }
+
+        let wi = -wo + half * (2.0 * wo.dot(&half));
+        let pdf = self.pdf(wo, &wi);
+        if pdf == 0.0 {
+            return None;
+        }
+
+        Some(BsdfSample {
+            wi,
+            f: self.eval(wo, &wi),
+            pdf,
+            specular: false,
+            index_of_refraction: None,
+        })
+    }
+}
@@ -1,7 +1,67 @@
 use na::{Point3, Vector3};
+use std::f32;
 
 pub static INDEX_OF_REFRACTION_AIR: f32 = 1.000293;
 
+// How deeply transparent objects can be nested inside each other before the
+// innermost are forgotten.
+const MAX_NESTED_MEDIA: usize = 8;
+
+// The indices of refraction of the transparent objects that a ray is inside
+// of, innermost last. Air is always at the bottom of the stack, so a ray that
+// leaves every object is back in air.
+#[derive(Clone, Copy, Debug)]
+pub struct MediumStack {
+    indices: [f32; MAX_NESTED_MEDIA],
+    len: usize,
+}
+
+impl MediumStack {
+    pub fn air() -> MediumStack {
+        MediumStack {
+            indices: [INDEX_OF_REFRACTION_AIR; MAX_NESTED_MEDIA],
+            len: 1,
+        }
+    }
+
+    // The index of refraction of the medium the ray is travelling through.
+    pub fn current(&self) -> f32 {
+        self.indices[self.len - 1]
+    }
+
+    // The index of refraction of the medium the ray returns to when it leaves
+    // the innermost object.
+    pub fn outside(&self) -> f32 {
+        self.indices[self.len.saturating_sub(2)]
+    }
+
+    // The stack after passing into an object with the given index of
+    // refraction.
+    pub fn enter(&self, index_of_refraction: f32) -> MediumStack {
+        let mut media = *self;
+        if media.len < MAX_NESTED_MEDIA {
+            media.len += 1;
+        }
+        media.indices[media.len - 1] = index_of_refraction;
+        media
+    }
+
+    // The stack after passing out of the innermost object.
+    pub fn exit(&self) -> MediumStack {
+        let mut media = *self;
+        if media.len > 1 {
+            media.len -= 1;
+        }
+        media
+    }
+}
+
+impl PartialEq for MediumStack {
+    fn eq(&self, other: &MediumStack) -> bool {
+        self.indices[..self.len] == other.indices[..other.len]
+    }
+}
+
 pub struct Ray {
     // The starting point of the ray.
     pub origin: Point3<f32>,
@@ -9,18 +69,80 @@ pub struct Ray {
     // The normalized direction of the ray.
     pub direction: Vector3<f32>,
 
-    // The index of refraction of the material from which this ray is cast.
-    pub index_of_refraction: f32,
+    // The media that the ray is cast from within.
+    pub media: MediumStack,
+
+    // Only intersections at distances strictly between these count.
+    pub t_min: f32,
+    pub t_max: f32,
 }
 
 impl Ray {
-    // Create a new ray from the given origin in the given direction where the
-    // index of refraction is assumed to be that of air.
-    pub fn new_from_air(origin: Point3<f32>, direction: Vector3<f32>) -> Ray {
+    // Create a new ray from the given origin in the given direction, which
+    // extends infinitely far.
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>, media: MediumStack) -> Ray {
         Ray {
             origin,
             direction,
-            index_of_refraction: INDEX_OF_REFRACTION_AIR,
+            media,
+            t_min: 0.0,
+            t_max: f32::INFINITY,
+        }
+    }
+
+    // Create a new ray from the given origin in the given direction where the
+    // index of refraction is assumed to be that of air.
+    pub fn new_from_air(origin: Point3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray::new(origin, direction, MediumStack::air())
+    }
+
+    // The point at the given distance along the ray.
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // Whether an intersection at the given distance along the ray counts.
+    pub fn contains(&self, distance: f32) -> bool {
+        distance > self.t_min && distance < self.t_max
+    }
+}
+
+// A bound on the relative error after `n` rounded floating point operations.
+//
+// Reference: Pharr et al., "Physically Based Rendering", section 3.9.1
+pub fn gamma(n: u32) -> f32 {
+    let e = n as f32 * f32::EPSILON * 0.5;
+    e / (1.0 - e)
+}
+
+// The origin for a ray leaving a surface at `position` in `direction`. Points
+// computed on a surface are only accurate to within `error` along each axis,
+// so may lie slightly on either side of it. The origin is pushed along the
+// geometric `normal` just far enough to be sure it is on the side the ray
+// leaves from, so the ray can't hit the surface it starts on again.
+pub fn offset_origin(
+    position: &Point3<f32>,
+    error: &Vector3<f32>,
+    normal: &Vector3<f32>,
+    direction: &Vector3<f32>,
+) -> Point3<f32> {
+    let distance = normal.abs().dot(error);
+    let offset = if direction.dot(normal) < 0.0 {
+        -normal * distance
+    } else {
+        normal * distance
+    };
+
+    // Round away from the surface, so that rounding the sum can't bring the
+    // origin back towards it.
+    let mut origin = position + offset;
+    for axis in 0..3 {
+        if offset[axis] > 0.0 {
+            origin[axis] = origin[axis].next_up();
+        } else if offset[axis] < 0.0 {
+            origin[axis] = origin[axis].next_down();
         }
     }
+
+    origin
 }
@@ -1,36 +1,260 @@
+use film::{Film, FilmTile, Filter};
 use integrator::Integrator;
 use na::{DMatrix, Vector3};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// The width and height of the square tiles that the image is split into. Each
+// tile is rendered as a single unit of work by one thread.
+pub const TILE_SIZE: usize = 16;
 
 pub struct RenderProperties {
     pub width: usize,
     pub height: usize,
+
+    // Only render this region of the image, if set.
+    pub crop: Option<CropWindow>,
+
+    // How many worker threads to render with. Zero uses one thread per CPU.
+    pub threads: usize,
+
+    // How samples are weighted into the pixels around them.
+    pub filter: Filter,
+
+    // How many samples should be collected for each pixel.
+    pub samples_per_pixel: u32,
+
+    // Render progressively in passes of this many samples per pixel across
+    // the whole image, rather than finishing each pixel in one go.
+    pub samples_per_pass: Option<u32>,
+
+    // Stop after the pass during which this much time has passed, even if
+    // not all samples have been collected.
+    pub time_limit: Option<Duration>,
+}
+
+impl RenderProperties {
+    // The region of the image to be rendered.
+    pub fn window(&self) -> CropWindow {
+        self.crop.unwrap_or(CropWindow {
+            x0: 0,
+            y0: 0,
+            x1: self.width,
+            y1: self.height,
+        })
+    }
+
+    // The number of samples per pixel collected by each pass. The last pass
+    // may collect fewer, to finish on exactly `samples_per_pixel`.
+    pub fn pass_size(&self) -> u32 {
+        self.samples_per_pass
+            .unwrap_or(self.samples_per_pixel)
+            .min(self.samples_per_pixel)
+    }
+
+    // The number of passes needed to collect every sample.
+    pub fn passes(&self) -> u32 {
+        let size = self.pass_size();
+        self.samples_per_pixel.div_ceil(size)
+    }
+}
+
+// The state of a progressive render after a pass has finished.
+pub struct Pass<'a> {
+    // Zero-based index of the pass.
+    pub index: u32,
+
+    // Samples per pixel collected so far, over all passes.
+    pub samples_per_pixel: u32,
+
+    // Time spent rendering so far.
+    pub elapsed: Duration,
+
+    // Whether this is the last pass, either because every sample has been
+    // collected or because the time limit has been reached.
+    pub last: bool,
+
+    // The image averaged over all passes so far.
+    pub image: &'a DMatrix<Vector3<f32>>,
+}
+
+// A rectangular region of the image in pixels. The minimum corner is inclusive
+// and the maximum corner is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropWindow {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl CropWindow {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    // Check that the window is non-empty and lies within an image of the given
+    // size.
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        self.x0 < self.x1 && self.y0 < self.y1 && self.x1 <= width && self.y1 <= height
+    }
+
+    // Extend the window by `margin` pixels on every side, without going
+    // outside of an image of the given size.
+    pub fn grow(&self, margin: usize, width: usize, height: usize) -> CropWindow {
+        CropWindow {
+            x0: self.x0.saturating_sub(margin),
+            y0: self.y0.saturating_sub(margin),
+            x1: (self.x1 + margin).min(width),
+            y1: (self.y1 + margin).min(height),
+        }
+    }
+
+    // Split the window into tiles of at most `size` pixels on each side, in
+    // row-major order.
+    pub fn tiles(&self, size: usize) -> Vec<CropWindow> {
+        let mut tiles = Vec::new();
+
+        for y0 in (self.y0..self.y1).step_by(size) {
+            for x0 in (self.x0..self.x1).step_by(size) {
+                tiles.push(CropWindow {
+                    x0,
+                    y0,
+                    x1: (x0 + size).min(self.x1),
+                    y1: (y0 + size).min(self.y1),
+                });
+            }
+        }
+
+        tiles
+    }
+}
+
+impl FromStr for CropWindow {
+    type Err = String;
+
+    // Parse a window from the form `x0,y0,x1,y1`.
+    fn from_str(s: &str) -> Result<CropWindow, String> {
+        let coords = s
+            .split(',')
+            .map(|c| c.trim().parse())
+            .collect::<Result<Vec<usize>, ParseIntError>>()
+            .map_err(|err| err.to_string())?;
+
+        match coords[..] {
+            [x0, y0, x1, y1] => Ok(CropWindow { x0, y0, x1, y1 }),
+            _ => Err("expected four comma-separated values x0,y0,x1,y1".to_string()),
+        }
+    }
 }
 
+// Render the image, or the cropped region of it if one is set. The returned
+// matrix is indexed by (x, y) relative to the corner of the region.
 pub fn render(properties: &RenderProperties, integrator: &dyn Integrator) -> DMatrix<Vector3<f32>> {
-    let mut screen = DMatrix::zeros(properties.width, properties.height);
+    render_progressive(properties, integrator, |_| {})
+}
+
+// Render the image in passes, calling `on_pass` with the image so far after
+// each one. Rendering stops once every sample has been collected or the time
+// limit is reached, and the final image is returned.
+//
+// Each pass splits the region into tiles which are rendered in parallel. The
+// tiling doesn't depend on the number of threads, and tiles are always merged
+// into the film in the same order, so the thread count has no effect on the
+// output.
+pub fn render_progressive<F>(
+    properties: &RenderProperties,
+    integrator: &dyn Integrator,
+    mut on_pass: F,
+) -> DMatrix<Vector3<f32>>
+where
+    F: FnMut(&Pass),
+{
+    let window = properties.window();
+    let mut film = Film::new(window, properties.filter);
+
+    // Pixels just outside of the window have samples that are filtered into
+    // it, so render those too.
+    let tiles = window
+        .grow(film.margin(), properties.width, properties.height)
+        .tiles(TILE_SIZE);
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(properties.threads)
+        .build()
+        .expect("failed to start render threads");
+
+    let start = Instant::now();
+    let passes = properties.passes();
+
+    let mut image = film.image();
+    let mut collected = 0;
 
-    for x in 0..properties.width {
-        for y in 0..properties.height {
-            // Scale the axes to be on the range of [-1, 1]. Also invert the y
-            // axis, as positive y needs to be towards the top of the screen.
-            let normalized_position = (
-                1.0 * (x as f32 / properties.width as f32 * 2.0 - 1.0),
-                -1.0 * (y as f32 / properties.height as f32 * 2.0 - 1.0),
-            );
+    for index in 0..passes {
+        let samples = properties
+            .pass_size()
+            .min(properties.samples_per_pixel - collected);
 
-            let color = integrator.integrate((normalized_position.0, normalized_position.1));
+        let rendered: Vec<FilmTile> = pool.install(|| {
+            tiles
+                .par_iter()
+                .map(|tile| {
+                    let mut film_tile = film.tile(tile);
+                    render_tile(
+                        integrator,
+                        tile,
+                        collected..collected + samples,
+                        &mut film_tile,
+                    );
+                    film_tile
+                })
+                .collect()
+        });
 
-            screen[(x, y)] = color;
+        for film_tile in &rendered {
+            film.merge(film_tile);
         }
 
-        let current_pixels = x;
-        let total_pixels = properties.width;
+        collected += samples;
+        image = film.image();
 
-        println!(
-            "{}% finished",
-            current_pixels as f32 / total_pixels as f32 * 100.0
-        );
+        let elapsed = start.elapsed();
+        let out_of_time = properties.time_limit.is_some_and(|limit| elapsed >= limit);
+        let last = collected == properties.samples_per_pixel || out_of_time;
+
+        on_pass(&Pass {
+            index,
+            samples_per_pixel: collected,
+            elapsed,
+            last,
+            image: &image,
+        });
+
+        if last {
+            break;
+        }
     }
 
-    screen
+    image
+}
+
+fn render_tile(
+    integrator: &dyn Integrator,
+    tile: &CropWindow,
+    samples: Range<u32>,
+    film_tile: &mut FilmTile,
+) {
+    for x in tile.x0..tile.x1 {
+        for y in tile.y0..tile.y1 {
+            integrator.integrate((x, y), samples.clone(), film_tile);
+        }
+    }
 }
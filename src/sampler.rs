@@ -0,0 +1,404 @@
+use std::str::FromStr;
+
+// A source of sample values for a single sample of a single pixel.
+//
+// Each call draws the next dimension of the sample. Low-discrepancy samplers
+// spread the values of each dimension evenly over the samples of a pixel, so
+// integrands converge faster than with independent random numbers, as long as
+// the same dimension is used for the same purpose in every sample.
+pub trait Sampler {
+    // The next dimension of the sample, in [0, 1).
+    fn get_1d(&mut self) -> f32;
+
+    // The next two dimensions of the sample, in [0, 1)^2. These are
+    // stratified jointly rather than separately.
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+// The available sample generators.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplerKind {
+    // Independent uniform random numbers.
+    Uniform,
+
+    // Jittered samples, with each dimension split into one stratum per sample
+    // and pairs of dimensions split into a grid.
+    Stratified,
+
+    // The Halton sequence, randomized per pixel by a random shift.
+    Halton,
+
+    // The Sobol sequence with Owen scrambling, padded to any number of
+    // dimensions by shuffling.
+    Sobol,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SamplerKind, String> {
+        match s.to_lowercase().as_str() {
+            "uniform" | "random" => Ok(SamplerKind::Uniform),
+            "stratified" | "jittered" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("unknown sampler `{}`", s)),
+        }
+    }
+}
+
+// How the samples of a render are generated.
+//
+// Every sample gets its own sampler, seeded from the global seed, the pixel
+// and the index of the sample. The values used for a sample therefore don't
+// depend on which thread renders it or in what order, so renders with the same
+// seed are reproducible.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerProperties {
+    pub kind: SamplerKind,
+    pub seed: u64,
+
+    // The number of samples that will be taken for each pixel, which the
+    // stratified sampler divides the sample space by.
+    pub samples_per_pixel: u32,
+}
+
+impl SamplerProperties {
+    pub fn sampler(&self, pixel: (usize, usize), sample: u32) -> Box<dyn Sampler> {
+        let mut hash = mix(self.seed);
+        hash = mix(hash ^ pixel.0 as u64);
+        hash = mix(hash ^ pixel.1 as u64);
+
+        // The pixel seed is shared by every sample of the pixel, so that
+        // low-discrepancy samplers can spread the samples out between them.
+        let pixel_seed = hash;
+        let random = UniformSampler::new(mix(hash ^ u64::from(sample)));
+
+        match self.kind {
+            SamplerKind::Uniform => Box::new(random),
+            SamplerKind::Stratified => Box::new(StratifiedSampler {
+                random,
+                pixel_seed,
+                sample,
+                samples_per_pixel: self.samples_per_pixel.max(1),
+                dimension: 0,
+            }),
+            SamplerKind::Halton => Box::new(HaltonSampler {
+                random,
+                pixel_seed,
+                sample,
+                dimension: 0,
+            }),
+            SamplerKind::Sobol => Box::new(SobolSampler {
+                pixel_seed,
+                sample,
+                dimension: 0,
+            }),
+        }
+    }
+}
+
+// Independent uniform random numbers from a PCG32 generator, see
+// http://www.pcg-random.org.
+pub struct UniformSampler {
+    state: u64,
+    increment: u64,
+}
+
+impl UniformSampler {
+    pub fn new(seed: u64) -> UniformSampler {
+        let mut sampler = UniformSampler {
+            state: 0,
+            // The increment selects one of 2^63 sequences, and must be odd.
+            increment: (mix(seed) << 1) | 1,
+        };
+        sampler.next_u32();
+        sampler.state = sampler.state.wrapping_add(seed);
+        sampler.next_u32();
+
+        sampler
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rotation = (old >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+}
+
+impl Sampler for UniformSampler {
+    fn get_1d(&mut self) -> f32 {
+        to_unit(self.next_u32())
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let u = self.get_1d();
+        (u, self.get_1d())
+    }
+}
+
+// Jittered stratified samples. Each dimension is split into one stratum per
+// sample, and each sample of the pixel takes a different stratum. The strata
+// are shuffled differently for each dimension so that the dimensions aren't
+// correlated with each other.
+pub struct StratifiedSampler {
+    random: UniformSampler,
+    pixel_seed: u64,
+    sample: u32,
+    samples_per_pixel: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    // The stratum, out of `count`, that this sample takes in the current
+    // dimension.
+    fn stratum(&mut self, count: u32) -> u32 {
+        let seed = mix(self.pixel_seed ^ u64::from(self.dimension)) as u32;
+        self.dimension += 1;
+
+        permute(self.sample % count, count, seed)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn get_1d(&mut self) -> f32 {
+        let n = self.samples_per_pixel;
+        let stratum = self.stratum(n);
+
+        jitter(stratum, n, self.random.get_1d())
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        // Use the squarest grid with at least one cell per sample. Cells are
+        // picked by a random permutation, so any unused cells don't bias the
+        // distribution.
+        let nx = (f64::from(self.samples_per_pixel).sqrt() as u32).max(1);
+        let ny = self.samples_per_pixel.div_ceil(nx);
+        let cell = self.stratum(nx * ny);
+
+        let (u, v) = self.random.get_2d();
+        (jitter(cell % nx, nx, u), jitter(cell / nx, ny, v))
+    }
+}
+
+// The first primes, which are the bases of the Halton sequence's dimensions.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+// The Halton sequence, with the sample index of the pixel as the index into
+// the sequence. Each dimension is shifted by a random amount per pixel
+// (Cranley-Patterson rotation), so that neighboring pixels don't share the same
+// pattern. Dimensions beyond the table of primes fall back to random numbers.
+pub struct HaltonSampler {
+    random: UniformSampler,
+    pixel_seed: u64,
+    sample: u32,
+    dimension: u32,
+}
+
+impl Sampler for HaltonSampler {
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.dimension as usize;
+        self.dimension += 1;
+
+        if dimension >= PRIMES.len() {
+            return self.random.get_1d();
+        }
+
+        let value = radical_inverse(self.sample, PRIMES[dimension]);
+        let shift = to_unit(mix(self.pixel_seed ^ dimension as u64) as u32);
+
+        let shifted = value + shift;
+        let wrapped = if shifted >= 1.0 {
+            shifted - 1.0
+        } else {
+            shifted
+        };
+        wrapped.min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let u = self.get_1d();
+        (u, self.get_1d())
+    }
+}
+
+// The first two dimensions of the Sobol sequence with nested uniform (Owen)
+// scrambling. Every request uses these same two dimensions, with the index of
+// the sample shuffled and the values scrambled differently, so there is no
+// limit on the number of dimensions. See Burley, "Practical Hash-based Owen
+// Scrambling", 2020.
+pub struct SobolSampler {
+    pixel_seed: u64,
+    sample: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    fn next_seed(&mut self) -> u32 {
+        let seed = mix(self.pixel_seed ^ u64::from(self.dimension)) as u32;
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn get_1d(&mut self) -> f32 {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample, seed);
+
+        to_unit(nested_uniform_scramble(
+            sobol_0(index),
+            hash_combine(seed, 0),
+        ))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample, seed);
+
+        (
+            to_unit(nested_uniform_scramble(
+                sobol_0(index),
+                hash_combine(seed, 0),
+            )),
+            to_unit(nested_uniform_scramble(
+                sobol_1(index),
+                hash_combine(seed, 1),
+            )),
+        )
+    }
+}
+
+// The largest f32 below one.
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+// Map 32 random bits to [0, 1), keeping the top 24 bits, which is all an f32
+// can represent in that range.
+fn to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// A uniformly distributed point within stratum `i` out of `n`.
+fn jitter(i: u32, n: u32, u: f32) -> f32 {
+    ((i as f32 + u) / n as f32).min(ONE_MINUS_EPSILON)
+}
+
+// Reflect the digits of `i` in the given base about the decimal point.
+fn radical_inverse(mut i: u32, base: u32) -> f32 {
+    let inverse_base = 1.0 / f64::from(base);
+    let mut reversed = 0u64;
+    let mut scale = 1.0;
+
+    while i > 0 {
+        reversed = reversed * u64::from(base) + u64::from(i % base);
+        scale *= inverse_base;
+        i /= base;
+    }
+
+    ((reversed as f64 * scale) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// The first dimension of the Sobol sequence, the van der Corput sequence in
+// base two, as a 0.32 fixed point number.
+fn sobol_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// The second dimension of the Sobol sequence, whose direction numbers are
+// generated by the primitive polynomial x + 1.
+fn sobol_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+
+    result
+}
+
+// Owen scramble the bits of a 0.32 fixed point number, by hashing each bit
+// with all of the bits above it.
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// A hash in which each bit only depends on the bits below it. Burley's
+// improved constants.
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20_adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x0552_6c56);
+    x ^= x.wrapping_mul(0x53a2_2864);
+    x
+}
+
+fn hash_combine(seed: u32, v: u32) -> u32 {
+    seed ^ (v
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2)
+        .wrapping_add(0x9e37_79b9))
+}
+
+// Element `i` of a random permutation of [0, n), chosen by `seed`, without
+// generating the whole permutation. See Kensler, "Correlated Multi-Jittered
+// Sampling", 2013.
+fn permute(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // Hash within the next power of two, and retry until the result is in
+    // range. Each step is invertible, so this is a permutation.
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < n {
+            break;
+        }
+    }
+
+    (i.wrapping_add(seed)) % n
+}
+
+// The SplitMix64 finalizer, which spreads every bit of the input over the
+// whole output.
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
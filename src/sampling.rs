@@ -0,0 +1,72 @@
+use na::Vector3;
+use std::f32::consts::PI;
+
+// Build two unit vectors which, along with the given unit normal, form an
+// orthonormal basis.
+//
+// Reference: Duff et al., "Building an Orthonormal Basis, Revisited" (2017)
+pub fn orthonormal_basis(n: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let sign = 1.0f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    (
+        Vector3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vector3::new(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
+// Express a vector given relative to the basis (s, t, n) in world space.
+pub fn from_basis(v: &Vector3<f32>, n: &Vector3<f32>) -> Vector3<f32> {
+    let (s, t) = orthonormal_basis(n);
+    s * v.x + t * v.y + n * v.z
+}
+
+// Sample a direction on the hemisphere about +z with probability proportional
+// to the cosine of the angle to +z, i.e. a density of cos(theta) / pi.
+//
+// Reference: http://www.rorydriscoll.com/2009/01/07/better-sampling/
+pub fn cosine_hemisphere((u1, u2): (f32, f32)) -> Vector3<f32> {
+    let r = u1.sqrt();
+    let theta = 2.0 * PI * u2;
+
+    // Project the point on the unit disk up onto the hemisphere.
+    let x = r * theta.cos();
+    let y = r * theta.sin();
+    let z = (1.0 - u1).max(0.0).sqrt();
+
+    Vector3::new(x, y, z)
+}
+
+// Sample a direction uniformly within the cone about +z whose half-angle has
+// the given cosine.
+pub fn uniform_cone((u1, u2): (f32, f32), cos_theta_max: f32) -> Vector3<f32> {
+    let cos_theta = 1.0 - u1 + u1 * cos_theta_max;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+
+    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Sample a point uniformly on a triangle, returned as the barycentric weights
+// of the second and third vertices.
+pub fn uniform_triangle((u1, u2): (f32, f32)) -> (f32, f32) {
+    let su1 = u1.sqrt();
+    (su1 * (1.0 - u2), su1 * u2)
+}
+
+// Weight for a sample drawn from one of two strategies, with density `f_pdf`,
+// when the other strategy would have drawn it with density `g_pdf`.
+//
+// Reference: Veach, "Robust Monte Carlo Methods for Light Transport
+// Simulation" (1997), section 9.2.
+pub fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f2 = f_pdf * f_pdf;
+    let g2 = g_pdf * g_pdf;
+
+    if f2 + g2 > 0.0 {
+        f2 / (f2 + g2)
+    } else {
+        0.0
+    }
+}
@@ -1,29 +1,151 @@
+use bvh::Bvh;
 use intersection::Intersection;
+use na::{Point3, Vector3};
 use object::Object;
 use ray::Ray;
 use std::f32;
+use surface::SurfaceSample;
 
 pub struct Scene {
-    pub objects: Vec<Object>,
+    objects: Vec<Object>,
+
+    // Hierarchy over every object that has a bounding box. Its indices refer
+    // to `bounded`.
+    bvh: Bvh,
+    bounded: Vec<usize>,
+
+    // Objects without a bounding box, such as planes, which are always tested.
+    unbounded: Vec<usize>,
+
+    // Objects which are sampled directly as lights.
+    lights: Vec<usize>,
 }
 
 impl Scene {
-    pub fn intersects(&self, ray: &Ray) -> Option<(Intersection, &Object)> {
-        // Check if this ray intersects any objects in the scene.
-        // TODO: This could perhaps be done easier with a filter_map and a
-        // sort_by.
-        self.objects.iter().fold(None, |a, ref b| {
-            let prev_distance = match a {
-                Some((ref intersection, _)) => intersection.distance,
-                None => f32::INFINITY,
-            };
-
-            match b.surface.intersects(&ray) {
-                Some(ref intersection) if intersection.distance < prev_distance => {
-                    Some((*intersection, b))
+    // Create a scene from a list of objects, building the acceleration
+    // structure used to intersect them.
+    pub fn new(objects: Vec<Object>) -> Scene {
+        let mut bounded = Vec::new();
+        let mut bounds = Vec::new();
+        let mut unbounded = Vec::new();
+
+        for (i, object) in objects.iter().enumerate() {
+            match object.surface.bounds() {
+                Some(aabb) => {
+                    bounded.push(i);
+                    bounds.push(aabb);
                 }
-                _ => a,
+                None => unbounded.push(i),
             }
-        })
+        }
+
+        let lights = (0..objects.len())
+            .filter(|&i| objects[i].is_light())
+            .collect();
+
+        Scene {
+            bvh: Bvh::build(&bounds),
+            objects,
+            bounded,
+            unbounded,
+            lights,
+        }
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    // Pick a light uniformly at random and sample a point on it as seen from
+    // `reference`. The density of the returned sample includes the chance of
+    // picking the light. `u_light` and `u` are uniformly distributed random
+    // numbers in [0, 1).
+    pub fn sample_light(
+        &self,
+        reference: &Point3<f32>,
+        u_light: f32,
+        u: (f32, f32),
+    ) -> Option<(SurfaceSample, &Object)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let count = self.lights.len();
+        let light = &self.objects[self.lights[((u_light * count as f32) as usize).min(count - 1)]];
+
+        light
+            .surface
+            .sample_towards(reference, u)
+            .filter(|sample| sample.pdf > 0.0)
+            .map(|mut sample| {
+                sample.pdf /= count as f32;
+                (sample, light)
+            })
+    }
+
+    // The density with which `sample_light` would choose the point on `light`
+    // hit by a ray from `reference` in the given direction.
+    pub fn light_pdf(
+        &self,
+        light: &Object,
+        reference: &Point3<f32>,
+        direction: &Vector3<f32>,
+    ) -> f32 {
+        if !light.is_light() {
+            return 0.0;
+        }
+
+        light.surface.pdf_towards(reference, direction) / self.lights.len() as f32
+    }
+
+    // Find the closest object that the ray intersects.
+    pub fn intersects(&self, ray: &Ray) -> Option<(Intersection, &Object)> {
+        let bounded = self
+            .bvh
+            .intersects(ray, |i| {
+                self.objects[self.bounded[i]].surface.intersects(ray)
+            })
+            .map(|(intersection, i)| (intersection, &self.objects[self.bounded[i]]));
+
+        self.unbounded
+            .iter()
+            .map(|&i| &self.objects[i])
+            .fold(bounded, |a, b| closest(a, b, ray))
+    }
+
+    // Check if the ray hits anything at all, which is cheaper than finding
+    // what it hits first.
+    pub fn occluded(&self, ray: &Ray) -> bool {
+        self.unbounded
+            .iter()
+            .any(|&i| self.objects[i].surface.occluded(ray))
+            || self
+                .bvh
+                .occluded(ray, |i| self.objects[self.bounded[i]].surface.occluded(ray))
+    }
+
+    // Find the closest object that the ray intersects by testing every object
+    // in the scene. This gives the same result as `intersects`, and is only
+    // useful for checking or benchmarking it.
+    pub fn intersects_brute_force(&self, ray: &Ray) -> Option<(Intersection, &Object)> {
+        self.objects.iter().fold(None, |a, b| closest(a, b, ray))
+    }
+}
+
+// Pick the closer of an existing intersection and the intersection of the ray
+// with another object.
+fn closest<'a>(
+    a: Option<(Intersection, &'a Object)>,
+    b: &'a Object,
+    ray: &Ray,
+) -> Option<(Intersection, &'a Object)> {
+    let prev_distance = match a {
+        Some((ref intersection, _)) => intersection.distance,
+        None => f32::INFINITY,
+    };
+
+    match b.surface.intersects(ray) {
+        Some(ref intersection) if intersection.distance < prev_distance => Some((*intersection, b)),
+        _ => a,
     }
 }
@@ -0,0 +1,1400 @@
+use camera::Camera;
+use film::{Filter, FilterKind};
+use import::{self, ImportError, ImportedMesh};
+use integrator::{IntegratorKind, IntegratorProperties};
+use material::{
+    ConductorMaterial, EmissiveMaterial, MaterialBox, PerfectDiffuseMaterial,
+    PerfectRefractiveMaterial, PerfectSpecularMaterial, PrincipledMaterial,
+    RoughDielectricMaterial,
+};
+use microfacet::{DistributionKind, MicrofacetDistribution};
+use na::{Matrix4, Point3, Vector2, Vector3};
+use object::Object;
+use principled::PrincipledParameters;
+use render::{CropWindow, RenderProperties};
+use sampler::SamplerKind;
+use scene::Scene;
+use spectrum::blackbody;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f32;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use surface::{Plane, Sphere, Surface, Triangle};
+use texture::{
+    CheckerboardTexture, ConstantTexture, GradientTexture, ImageTexture, NoiseTexture,
+    ScaledTexture, Texture, TextureError, TextureFilter, UvScaledTexture, VertexColorTexture,
+    WrapMode,
+};
+use toml;
+use tonemap::{ToneMapOperator, ToneMapping};
+use transform::{Transform, TransformedSurface};
+
+// Everything needed to render a scene, as described by a scene file.
+pub struct SceneConfig {
+    pub camera: Camera,
+    pub scene: Scene,
+    pub render: RenderProperties,
+    pub integrator: IntegratorProperties,
+    pub tonemap: ToneMapping,
+
+    // Problems with the scene which didn't stop it from loading, such as
+    // deprecated keys or parts of imported files which aren't supported.
+    // They're left to the caller to report.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    // The scene file could not be read from disk.
+    Io(PathBuf, io::Error),
+
+    // The scene file is not valid TOML or does not match the expected
+    // structure. The underlying error carries the line and column.
+    Parse(toml::de::Error),
+
+    // The scene file is well-formed but a value is out of range, e.g. a
+    // negative sphere radius. `field` is the dotted path to the value.
+    Invalid { field: String, message: String },
+
+    // A mesh referred to by the scene file could not be loaded.
+    Import(PathBuf, ImportError),
+
+    // A texture referred to by the scene file could not be loaded.
+    Texture(PathBuf, TextureError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref path, ref err) => {
+                write!(f, "could not read {}: {}", path.display(), err)
+            }
+            LoadError::Parse(ref err) => write!(f, "{}", err),
+            LoadError::Invalid {
+                ref field,
+                ref message,
+            } => write!(f, "invalid value for `{}`: {}", field, message),
+            LoadError::Import(ref path, ref err) => {
+                write!(f, "could not load {}: {}", path.display(), err)
+            }
+            LoadError::Texture(ref path, ref err) => {
+                write!(f, "could not load {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io(_, ref err) => Some(err),
+            LoadError::Parse(ref err) => Some(err),
+            LoadError::Invalid { .. } => None,
+            LoadError::Import(_, ref err) => Some(err),
+            LoadError::Texture(_, ref err) => Some(err),
+        }
+    }
+}
+
+impl From<toml::de::Error> for LoadError {
+    fn from(err: toml::de::Error) -> LoadError {
+        LoadError::Parse(err)
+    }
+}
+
+// Load a scene file from disk. A glTF file can also be rendered on its own,
+// from the first camera in it.
+pub fn load(path: &Path) -> Result<SceneConfig, LoadError> {
+    if is_gltf(path) {
+        return load_gltf(path);
+    }
+
+    let contents =
+        fs::read_to_string(path).map_err(|err| LoadError::Io(path.to_path_buf(), err))?;
+
+    parse(&contents, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+// Whether a file is glTF, going by its extension.
+fn is_gltf(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb"),
+        None => false,
+    }
+}
+
+// A glTF file has no render settings, so the image is 480 pixels high with the
+// aspect ratio of the camera, and everything else is left at its defaults.
+fn load_gltf(path: &Path) -> Result<SceneConfig, LoadError> {
+    let import_error = |err| LoadError::Import(path.to_path_buf(), err);
+    let imported = import::gltf::load(path).map_err(import_error)?;
+    let imported_camera = imported.camera.ok_or_else(|| {
+        import_error(ImportError::Gltf(
+            "there is no perspective camera to render from".to_string(),
+        ))
+    })?;
+
+    let height = 480;
+    let aspect_ratio = imported_camera.aspect_ratio.unwrap_or(4.0 / 3.0);
+    let integrator = IntegratorDesc::default();
+    let render = RenderDesc {
+        width: ((height as f32 * aspect_ratio).round() as usize).max(1),
+        height,
+        crop: None,
+        samples_per_pass: None,
+        time_limit: None,
+        filter: None,
+        filter_radius: None,
+    }
+    .build(&integrator)?;
+
+    let mut camera = imported_camera.camera;
+    camera.set_aspect_ratio(render.width as f32 / render.height as f32);
+
+    Ok(SceneConfig {
+        camera,
+        scene: Scene::new(imported.objects),
+        render,
+        integrator: integrator.build()?,
+        tonemap: ToneMapDesc::default().build()?,
+        warnings: imported.warnings,
+    })
+}
+
+// Parse the contents of a scene file. Relative paths to other files, such as
+// meshes, are resolved against `base_dir`.
+pub fn parse(contents: &str, base_dir: &Path) -> Result<SceneConfig, LoadError> {
+    let file: SceneFile = toml::from_str(contents)?;
+    file.build(base_dir)
+}
+
+// The on-disk representation of a scene. These mirror the renderer types, but
+// are kept separate so that the file format can stay stable as the renderer
+// internals change.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    camera: CameraDesc,
+    render: RenderDesc,
+    #[serde(default)]
+    integrator: IntegratorDesc,
+    #[serde(default)]
+    tonemap: ToneMapDesc,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    prototypes: BTreeMap<String, PrototypeDesc>,
+    #[serde(default)]
+    instances: Vec<InstanceDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    position: [f32; 3],
+    // Exactly one of `target` and `direction` must be given.
+    target: Option<[f32; 3]>,
+    direction: Option<[f32; 3]>,
+    #[serde(default = "default_up")]
+    up: [f32; 3],
+    // Vertical field of view, in degrees.
+    #[serde(default = "default_fov")]
+    fov: f32,
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_fov() -> f32 {
+    90.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RenderDesc {
+    width: usize,
+    height: usize,
+    crop: Option<[usize; 4]>,
+    // Render progressively, in passes of this many samples per pixel.
+    samples_per_pass: Option<u32>,
+    // Time budget for progressive rendering, in seconds.
+    time_limit: Option<f32>,
+    // Reconstruction filter, with a radius in pixels.
+    filter: Option<String>,
+    filter_radius: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IntegratorDesc {
+    // Which integrator to use, path tracing by default.
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default = "default_samples_per_pixel")]
+    samples_per_pixel: u32,
+    #[serde(default = "default_max_bounces")]
+    max_bounces: u32,
+    sampler: Option<String>,
+    #[serde(default)]
+    seed: u64,
+    // How far away surfaces can occlude each other for ambient occlusion,
+    // which is unlimited by default.
+    occlusion_distance: Option<f32>,
+}
+
+fn default_samples_per_pixel() -> u32 {
+    100
+}
+
+fn default_max_bounces() -> u32 {
+    5
+}
+
+impl Default for IntegratorDesc {
+    fn default() -> IntegratorDesc {
+        IntegratorDesc {
+            kind: None,
+            samples_per_pixel: default_samples_per_pixel(),
+            max_bounces: default_max_bounces(),
+            sampler: None,
+            seed: 0,
+            occlusion_distance: None,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToneMapDesc {
+    #[serde(default)]
+    exposure: f32,
+    operator: Option<String>,
+    #[serde(default)]
+    dither: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDesc {
+    surface: SurfaceDesc,
+    material: MaterialDesc,
+    transform: Option<TransformDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum SurfaceDesc {
+    Sphere { center: [f32; 3], radius: f32 },
+    Plane { normal: [f32; 3], offset: f32 },
+    Triangle { vertices: [[f32; 3]; 3] },
+}
+
+// Meshes loaded from a file. Each object in the file becomes a separate object
+// in the scene, or for glTF files, each placement of a primitive by a node. The
+// material, if given, is used for every object; otherwise materials come from
+// the file itself.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    path: PathBuf,
+    material: Option<MaterialDesc>,
+    transform: Option<TransformDesc>,
+}
+
+// Objects and meshes which aren't rendered themselves, but can be placed in
+// the scene any number of times by instances. Their geometry is only stored
+// once, however many instances there are.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrototypeDesc {
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDesc {
+    // The name of the prototype to place.
+    prototype: String,
+    transform: Option<TransformDesc>,
+}
+
+// Moves a surface from where it is defined to where it is placed in the scene.
+// It is scaled first, then rotated, then translated. Alternatively, a 4 x 4
+// affine matrix can be given by rows instead.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDesc {
+    translate: Option<[f32; 3]>,
+    rotate: Option<RotationDesc>,
+    scale: Option<ScaleDesc>,
+    matrix: Option<[[f32; 4]; 4]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotationDesc {
+    axis: [f32; 3],
+    // Counterclockwise, in degrees.
+    angle: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Diffuse {
+        color: ColorDesc,
+    },
+    Specular,
+    Refractive {
+        index_of_refraction: f32,
+
+        // Reflection used to happen with a fixed probability; it now follows
+        // the Fresnel equations. The key is still accepted so that older
+        // scene files load, but it has no effect beyond a warning.
+        reflect_prob: Option<f32>,
+    },
+    Conductor {
+        eta: [f32; 3],
+        k: [f32; 3],
+        #[serde(default)]
+        roughness: RoughnessDesc,
+        distribution: Option<String>,
+    },
+    RoughDielectric {
+        index_of_refraction: f32,
+        roughness: RoughnessDesc,
+        distribution: Option<String>,
+    },
+    Principled(PrincipledDesc),
+    Emissive {
+        #[serde(default = "default_emissivity")]
+        emissivity: f32,
+        color: Option<ColorDesc>,
+        temperature: Option<f32>,
+        texture: Option<PathBuf>,
+        #[serde(default)]
+        one_sided: bool,
+    },
+}
+
+fn default_emissivity() -> f32 {
+    1.0
+}
+
+// The parameters of the principled material, any of which can be left out.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PrincipledDesc {
+    base_color: ColorDesc,
+    metallic: f32,
+    roughness: f32,
+    anisotropic: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_gloss: f32,
+    transmission: f32,
+    subsurface: f32,
+    index_of_refraction: f32,
+}
+
+impl Default for PrincipledDesc {
+    fn default() -> PrincipledDesc {
+        let defaults = PrincipledParameters::default();
+
+        PrincipledDesc {
+            base_color: ColorDesc::Constant([
+                defaults.base_color.x,
+                defaults.base_color.y,
+                defaults.base_color.z,
+            ]),
+            metallic: defaults.metallic,
+            roughness: defaults.roughness,
+            anisotropic: defaults.anisotropic,
+            specular: defaults.specular,
+            specular_tint: defaults.specular_tint,
+            sheen: defaults.sheen,
+            sheen_tint: defaults.sheen_tint,
+            clearcoat: defaults.clearcoat,
+            clearcoat_gloss: defaults.clearcoat_gloss,
+            transmission: defaults.transmission,
+            subsurface: defaults.subsurface,
+            index_of_refraction: defaults.index_of_refraction,
+        }
+    }
+}
+
+impl PrincipledDesc {
+    fn build(&self, field: &str, base_dir: &Path) -> Result<PrincipledMaterial, LoadError> {
+        // A constant base color is kept in the parameters, rather than looked
+        // up from a texture at every intersection.
+        let color_field = format!("{}.base_color", field);
+        let (base_color, base_color_texture) = match self.base_color {
+            ColorDesc::Constant(color) => (check_color(&color_field, color)?, None),
+            ColorDesc::Texture(ref texture) => (
+                Vector3::zeros(),
+                Some(texture.build(&color_field, base_dir)?),
+            ),
+        };
+
+        for &(name, value) in &[
+            ("metallic", self.metallic),
+            ("roughness", self.roughness),
+            ("anisotropic", self.anisotropic),
+            ("specular", self.specular),
+            ("specular_tint", self.specular_tint),
+            ("sheen", self.sheen),
+            ("sheen_tint", self.sheen_tint),
+            ("clearcoat", self.clearcoat),
+            ("clearcoat_gloss", self.clearcoat_gloss),
+            ("transmission", self.transmission),
+            ("subsurface", self.subsurface),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return invalid(
+                    &format!("{}.{}", field, name),
+                    "must be between 0.0 and 1.0",
+                );
+            }
+        }
+
+        if !(self.index_of_refraction.is_finite() && self.index_of_refraction > 0.0) {
+            return invalid(
+                &format!("{}.index_of_refraction", field),
+                "must be a positive number",
+            );
+        }
+
+        let parameters = PrincipledParameters {
+            base_color,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            anisotropic: self.anisotropic,
+            specular: self.specular,
+            specular_tint: self.specular_tint,
+            sheen: self.sheen,
+            sheen_tint: self.sheen_tint,
+            clearcoat: self.clearcoat,
+            clearcoat_gloss: self.clearcoat_gloss,
+            transmission: self.transmission,
+            subsurface: self.subsurface,
+            index_of_refraction: self.index_of_refraction,
+        };
+
+        Ok(PrincipledMaterial {
+            parameters,
+            base_color: base_color_texture,
+            metallic_roughness: None,
+        })
+    }
+}
+
+// The roughness of a microfacet material, either the same in every direction
+// or separately along the tangent and bitangent.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum RoughnessDesc {
+    Isotropic(f32),
+    Anisotropic([f32; 2]),
+}
+
+impl Default for RoughnessDesc {
+    fn default() -> RoughnessDesc {
+        RoughnessDesc::Isotropic(0.0)
+    }
+}
+
+fn build_distribution(
+    field: &str,
+    roughness: RoughnessDesc,
+    distribution: &Option<String>,
+) -> Result<MicrofacetDistribution, LoadError> {
+    let kind = match distribution {
+        Some(kind) => match kind.parse::<DistributionKind>() {
+            Ok(kind) => kind,
+            Err(err) => return invalid(&format!("{}.distribution", field), &err),
+        },
+        None => DistributionKind::Ggx,
+    };
+
+    let (alpha_x, alpha_y) = match roughness {
+        RoughnessDesc::Isotropic(alpha) => (alpha, alpha),
+        RoughnessDesc::Anisotropic([alpha_x, alpha_y]) => (alpha_x, alpha_y),
+    };
+    if !(alpha_x.is_finite() && alpha_x >= 0.0 && alpha_y.is_finite() && alpha_y >= 0.0) {
+        return invalid(
+            &format!("{}.roughness", field),
+            "must be a non-negative number or a pair of them",
+        );
+    }
+
+    Ok(MicrofacetDistribution::new(kind, alpha_x, alpha_y))
+}
+
+// A color, given either directly or as a texture.
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+enum ColorDesc {
+    Constant([f32; 3]),
+    Texture(Box<TextureDesc>),
+}
+
+impl ColorDesc {
+    fn build(&self, field: &str, base_dir: &Path) -> Result<Box<dyn Texture>, LoadError> {
+        match *self {
+            ColorDesc::Constant(color) => Ok(Box::new(ConstantTexture {
+                color: check_color(field, color)?,
+            })),
+            ColorDesc::Texture(ref texture) => texture.build(field, base_dir),
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Image {
+        path: PathBuf,
+        filter: Option<String>,
+        wrap: Option<String>,
+        #[serde(default)]
+        scale: UvScaleDesc,
+    },
+    Checkerboard {
+        even: ColorDesc,
+        odd: ColorDesc,
+        #[serde(default)]
+        scale: UvScaleDesc,
+    },
+    Gradient {
+        from: [f32; 3],
+        to: [f32; 3],
+        #[serde(default = "default_gradient_direction")]
+        direction: [f32; 2],
+    },
+    Noise {
+        low: [f32; 3],
+        high: [f32; 3],
+        #[serde(default = "default_noise_scale")]
+        scale: f32,
+        #[serde(default = "default_noise_octaves")]
+        octaves: u32,
+    },
+    // The colors of a mesh's vertices, or `fallback` for surfaces without
+    // them.
+    VertexColor {
+        #[serde(default = "default_vertex_color_fallback")]
+        fallback: [f32; 3],
+    },
+}
+
+fn default_gradient_direction() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+fn default_noise_scale() -> f32 {
+    1.0
+}
+
+fn default_noise_octaves() -> u32 {
+    4
+}
+
+fn default_vertex_color_fallback() -> [f32; 3] {
+    [0.8, 0.8, 0.8]
+}
+
+// How many times a texture repeats per unit of texture coordinates, either
+// the same along both axes or separately along u and v.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(untagged)]
+enum UvScaleDesc {
+    Uniform(f32),
+    Axes([f32; 2]),
+}
+
+impl Default for UvScaleDesc {
+    fn default() -> UvScaleDesc {
+        UvScaleDesc::Uniform(1.0)
+    }
+}
+
+impl UvScaleDesc {
+    // Wrap a texture so that its texture coordinates are scaled, unless the
+    // scale is one.
+    fn apply(self, field: &str, texture: Box<dyn Texture>) -> Result<Box<dyn Texture>, LoadError> {
+        let scale = match self {
+            UvScaleDesc::Uniform(scale) => Vector2::new(scale, scale),
+            UvScaleDesc::Axes([u, v]) => Vector2::new(u, v),
+        };
+        if !scale.iter().all(|s| s.is_finite() && *s != 0.0) {
+            return invalid(
+                &format!("{}.scale", field),
+                "must be a non-zero number or a pair of them",
+            );
+        }
+
+        if scale == Vector2::new(1.0, 1.0) {
+            Ok(texture)
+        } else {
+            Ok(Box::new(UvScaledTexture { texture, scale }))
+        }
+    }
+}
+
+impl TextureDesc {
+    fn build(&self, field: &str, base_dir: &Path) -> Result<Box<dyn Texture>, LoadError> {
+        match *self {
+            TextureDesc::Image {
+                ref path,
+                ref filter,
+                ref wrap,
+                scale,
+            } => {
+                let filter = match filter {
+                    Some(filter) => match filter.parse::<TextureFilter>() {
+                        Ok(filter) => filter,
+                        Err(err) => return invalid(&format!("{}.filter", field), &err),
+                    },
+                    None => TextureFilter::Trilinear,
+                };
+                let wrap = match wrap {
+                    Some(wrap) => match wrap.parse::<WrapMode>() {
+                        Ok(wrap) => wrap,
+                        Err(err) => return invalid(&format!("{}.wrap", field), &err),
+                    },
+                    None => WrapMode::Repeat,
+                };
+
+                let path = base_dir.join(path);
+                let texture = ImageTexture::open(&path, filter, wrap)
+                    .map_err(|err| LoadError::Texture(path.clone(), err))?;
+                scale.apply(field, Box::new(texture))
+            }
+            TextureDesc::Checkerboard {
+                ref even,
+                ref odd,
+                scale,
+            } => {
+                let texture = CheckerboardTexture {
+                    even: even.build(&format!("{}.even", field), base_dir)?,
+                    odd: odd.build(&format!("{}.odd", field), base_dir)?,
+                };
+                scale.apply(field, Box::new(texture))
+            }
+            TextureDesc::Gradient {
+                from,
+                to,
+                direction,
+            } => {
+                check_finite(&format!("{}.direction", field), &direction)?;
+
+                Ok(Box::new(GradientTexture {
+                    from: check_color(&format!("{}.from", field), from)?,
+                    to: check_color(&format!("{}.to", field), to)?,
+                    direction: Vector2::new(direction[0], direction[1]),
+                }))
+            }
+            TextureDesc::Noise {
+                low,
+                high,
+                scale,
+                octaves,
+            } => {
+                if !(scale.is_finite() && scale > 0.0) {
+                    return invalid(&format!("{}.scale", field), "must be a positive number");
+                }
+                if !(1..=16).contains(&octaves) {
+                    return invalid(&format!("{}.octaves", field), "must be between 1 and 16");
+                }
+
+                Ok(Box::new(NoiseTexture {
+                    low: check_color(&format!("{}.low", field), low)?,
+                    high: check_color(&format!("{}.high", field), high)?,
+                    scale,
+                    octaves,
+                }))
+            }
+            TextureDesc::VertexColor { fallback } => Ok(Box::new(VertexColorTexture {
+                fallback: check_color(&format!("{}.fallback", field), fallback)?,
+            })),
+        }
+    }
+}
+
+fn invalid<T>(field: &str, message: &str) -> Result<T, LoadError> {
+    Err(LoadError::Invalid {
+        field: field.to_string(),
+        message: message.to_string(),
+    })
+}
+
+fn point(v: [f32; 3]) -> Point3<f32> {
+    Point3::new(v[0], v[1], v[2])
+}
+
+fn vector(v: [f32; 3]) -> Vector3<f32> {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+fn check_finite(field: &str, values: &[f32]) -> Result<(), LoadError> {
+    if values.iter().all(|v| v.is_finite()) {
+        Ok(())
+    } else {
+        invalid(field, "must be a finite number")
+    }
+}
+
+// Check that a color is finite and not negative.
+fn check_color(field: &str, color: [f32; 3]) -> Result<Vector3<f32>, LoadError> {
+    check_finite(field, &color)?;
+    if color.iter().any(|c| *c < 0.0) {
+        return invalid(field, "color components must not be negative");
+    }
+
+    Ok(vector(color))
+}
+
+fn check_nonzero(field: &str, v: [f32; 3]) -> Result<Vector3<f32>, LoadError> {
+    check_finite(field, &v)?;
+
+    let v = vector(v);
+    if v.norm_squared() > 0.0 {
+        Ok(v)
+    } else {
+        invalid(field, "must not be the zero vector")
+    }
+}
+
+impl SceneFile {
+    fn build(self, base_dir: &Path) -> Result<SceneConfig, LoadError> {
+        let render = self.render.build(&self.integrator)?;
+        let camera = self
+            .camera
+            .build(render.width as f32 / render.height as f32)?;
+        let integrator = self.integrator.build()?;
+        let tonemap = self.tonemap.build()?;
+
+        let mut warnings = Vec::new();
+        let mut objects = build_objects("", self.objects, self.meshes, base_dir, &mut warnings)?;
+
+        let mut prototypes = BTreeMap::new();
+        for (name, prototype) in self.prototypes {
+            let field = format!("prototypes.{}.", name);
+            let prototype = build_objects(
+                &field,
+                prototype.objects,
+                prototype.meshes,
+                base_dir,
+                &mut warnings,
+            )?;
+            let shared: Vec<(Arc<dyn Surface>, MaterialBox)> = prototype
+                .into_iter()
+                .map(|object| (Arc::from(object.surface), object.material))
+                .collect();
+            prototypes.insert(name, shared);
+        }
+
+        for (i, instance) in self.instances.into_iter().enumerate() {
+            let field = format!("instances[{}]", i);
+            let prototype = match prototypes.get(&instance.prototype) {
+                Some(prototype) => prototype,
+                None => {
+                    return invalid(
+                        &format!("{}.prototype", field),
+                        &format!("there is no prototype named `{}`", instance.prototype),
+                    )
+                }
+            };
+            let transform = build_transform(&field, instance.transform)?;
+
+            for (surface, material) in prototype {
+                objects.push(Object {
+                    surface: Box::new(TransformedSurface::new(surface.clone(), transform)),
+                    material: material.clone(),
+                });
+            }
+        }
+
+        Ok(SceneConfig {
+            camera,
+            scene: Scene::new(objects),
+            render,
+            integrator,
+            tonemap,
+            warnings,
+        })
+    }
+}
+
+// Build a list of objects and meshes, whose fields are found under `prefix`.
+fn build_objects(
+    prefix: &str,
+    object_descs: Vec<ObjectDesc>,
+    mesh_descs: Vec<MeshDesc>,
+    base_dir: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Object>, LoadError> {
+    let mut objects = Vec::with_capacity(object_descs.len());
+    for (i, object) in object_descs.into_iter().enumerate() {
+        let field = format!("{}objects[{}]", prefix, i);
+        let surface = object.surface.build(&format!("{}.surface", field))?;
+        objects.push(Object {
+            surface: transformed(surface, build_transform(&field, object.transform)?),
+            material: object
+                .material
+                .build(&format!("{}.material", field), base_dir, warnings)?,
+        });
+    }
+
+    for (i, mesh) in mesh_descs.into_iter().enumerate() {
+        let field = format!("{}meshes[{}]", prefix, i);
+        objects.extend(mesh.build(&field, base_dir, warnings)?);
+    }
+
+    Ok(objects)
+}
+
+// The transform of the object at `field`, which is the identity if it doesn't
+// have one.
+fn build_transform(field: &str, desc: Option<TransformDesc>) -> Result<Transform, LoadError> {
+    match desc {
+        Some(desc) => desc.build(&format!("{}.transform", field)),
+        None => Ok(Transform::identity()),
+    }
+}
+
+fn transformed(surface: Box<dyn Surface>, transform: Transform) -> Box<dyn Surface> {
+    if transform == Transform::identity() {
+        surface
+    } else {
+        Box::new(TransformedSurface::new(Arc::from(surface), transform))
+    }
+}
+
+impl TransformDesc {
+    fn build(self, field: &str) -> Result<Transform, LoadError> {
+        if let Some(matrix) = self.matrix {
+            if self.translate.is_some() || self.rotate.is_some() || self.scale.is_some() {
+                return invalid(
+                    field,
+                    "`matrix` can't be combined with `translate`, `rotate` or `scale`",
+                );
+            }
+
+            let matrix_field = format!("{}.matrix", field);
+            for (i, row) in matrix.iter().enumerate() {
+                check_finite(&format!("{}[{}]", matrix_field, i), row)?;
+            }
+            if matrix[3] != [0.0, 0.0, 0.0, 1.0] {
+                return invalid(&matrix_field, "the bottom row must be [0, 0, 0, 1]");
+            }
+
+            let rows: Vec<f32> = matrix.iter().flat_map(|row| row.iter().cloned()).collect();
+            return match Transform::new(Matrix4::from_row_slice(&rows)) {
+                Some(transform) => Ok(transform),
+                None => invalid(&matrix_field, "must be invertible"),
+            };
+        }
+
+        let mut transform = Transform::identity();
+
+        if let Some(scale) = self.scale {
+            let scale_field = format!("{}.scale", field);
+            let factors = match scale {
+                ScaleDesc::Uniform(factor) => [factor; 3],
+                ScaleDesc::Axes(factors) => factors,
+            };
+            check_finite(&scale_field, &factors)?;
+            if factors.contains(&0.0) {
+                return invalid(&scale_field, "must not be zero");
+            }
+            transform = transform.then(&Transform::scaling(&vector(factors)));
+        }
+
+        if let Some(rotate) = self.rotate {
+            let axis = check_nonzero(&format!("{}.rotate.axis", field), rotate.axis)?;
+            check_finite(&format!("{}.rotate.angle", field), &[rotate.angle])?;
+            transform = transform.then(&Transform::rotation(&axis, rotate.angle.to_radians()));
+        }
+
+        if let Some(translate) = self.translate {
+            check_finite(&format!("{}.translate", field), &translate)?;
+            transform = transform.then(&Transform::translation(&vector(translate)));
+        }
+
+        Ok(transform)
+    }
+}
+
+impl CameraDesc {
+    fn build(self, aspect_ratio: f32) -> Result<Camera, LoadError> {
+        check_finite("camera.position", &self.position)?;
+        let position = point(self.position);
+
+        let direction = match (self.target, self.direction) {
+            (Some(target), None) => {
+                check_finite("camera.target", &target)?;
+                if point(target) == position {
+                    return invalid("camera.target", "must not be the camera position");
+                }
+                point(target) - position
+            }
+            (None, Some(direction)) => check_nonzero("camera.direction", direction)?,
+            _ => {
+                return invalid(
+                    "camera",
+                    "exactly one of `target` and `direction` must be given",
+                )
+            }
+        };
+
+        let up = check_nonzero("camera.up", self.up)?;
+        if up.cross(&direction).norm_squared() == 0.0 {
+            return invalid("camera.up", "must not be parallel to the viewing direction");
+        }
+
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return invalid("camera.fov", "must be between 0 and 180 degrees");
+        }
+
+        Ok(Camera::new(
+            position,
+            direction,
+            up,
+            self.fov.to_radians(),
+            aspect_ratio,
+        ))
+    }
+}
+
+impl RenderDesc {
+    // The number of samples is read from the integrator settings, where it has
+    // always been in the file format.
+    fn build(self, integrator: &IntegratorDesc) -> Result<RenderProperties, LoadError> {
+        if self.width == 0 {
+            return invalid("render.width", "must be greater than zero");
+        }
+        if self.height == 0 {
+            return invalid("render.height", "must be greater than zero");
+        }
+
+        let crop = self.crop.map(|c| CropWindow {
+            x0: c[0],
+            y0: c[1],
+            x1: c[2],
+            y1: c[3],
+        });
+        if let Some(crop) = crop {
+            if !crop.fits(self.width, self.height) {
+                return invalid("render.crop", "must be a non-empty region within the image");
+            }
+        }
+
+        if integrator.samples_per_pixel == 0 {
+            return invalid("integrator.samples_per_pixel", "must be greater than zero");
+        }
+        if self.samples_per_pass == Some(0) {
+            return invalid("render.samples_per_pass", "must be greater than zero");
+        }
+
+        // Durations can't be infinite, or more than a few hundred billion
+        // years.
+        let time_limit = match self.time_limit {
+            Some(limit) => match Duration::try_from_secs_f32(limit) {
+                Ok(limit) if limit > Duration::ZERO => Some(limit),
+                _ => return invalid("render.time_limit", "must be a positive number of seconds"),
+            },
+            None => None,
+        };
+
+        let mut filter = match self.filter {
+            Some(kind) => match kind.parse::<FilterKind>() {
+                Ok(kind) => Filter::new(kind),
+                Err(err) => return invalid("render.filter", &err),
+            },
+            None => Filter::default(),
+        };
+        if let Some(radius) = self.filter_radius {
+            if !(radius.is_finite() && radius > 0.0) {
+                return invalid("render.filter_radius", "must be a positive number");
+            }
+            filter.radius = radius;
+        }
+
+        Ok(RenderProperties {
+            width: self.width,
+            height: self.height,
+            crop,
+            threads: 0,
+            filter,
+            samples_per_pixel: integrator.samples_per_pixel,
+            samples_per_pass: self.samples_per_pass,
+            time_limit,
+        })
+    }
+}
+
+impl IntegratorDesc {
+    fn build(self) -> Result<IntegratorProperties, LoadError> {
+        let kind = match self.kind {
+            Some(kind) => match kind.parse::<IntegratorKind>() {
+                Ok(kind) => kind,
+                Err(err) => return invalid("integrator.type", &err),
+            },
+            None => IntegratorKind::Path,
+        };
+
+        let sampler = match self.sampler {
+            Some(sampler) => match sampler.parse::<SamplerKind>() {
+                Ok(sampler) => sampler,
+                Err(err) => return invalid("integrator.sampler", &err),
+            },
+            None => SamplerKind::Sobol,
+        };
+
+        let occlusion_distance = match self.occlusion_distance {
+            Some(distance) if distance.is_nan() || distance <= 0.0 => {
+                return invalid("integrator.occlusion_distance", "must be a positive number")
+            }
+            Some(distance) => distance,
+            None => f32::INFINITY,
+        };
+
+        Ok(IntegratorProperties {
+            kind,
+            max_bounces: self.max_bounces,
+            sampler,
+            seed: self.seed,
+            occlusion_distance,
+        })
+    }
+}
+
+impl ToneMapDesc {
+    fn build(self) -> Result<ToneMapping, LoadError> {
+        check_finite("tonemap.exposure", &[self.exposure])?;
+
+        let operator = match self.operator {
+            Some(operator) => match operator.parse::<ToneMapOperator>() {
+                Ok(operator) => operator,
+                Err(err) => return invalid("tonemap.operator", &err),
+            },
+            None => ToneMapping::default().operator,
+        };
+
+        Ok(ToneMapping {
+            exposure: self.exposure,
+            operator,
+            dither: self.dither,
+        })
+    }
+}
+
+impl SurfaceDesc {
+    fn build(self, field: &str) -> Result<Box<dyn Surface>, LoadError> {
+        match self {
+            SurfaceDesc::Sphere { center, radius } => {
+                check_finite(&format!("{}.center", field), &center)?;
+                if !(radius.is_finite() && radius > 0.0) {
+                    return invalid(&format!("{}.radius", field), "must be a positive number");
+                }
+
+                Ok(Box::new(Sphere {
+                    center: point(center),
+                    radius,
+                }))
+            }
+            SurfaceDesc::Plane { normal, offset } => {
+                // Only the direction of the normal matters, as the plane
+                // passes through (offset, offset, offset) whatever its length.
+                let normal = check_nonzero(&format!("{}.normal", field), normal)?.normalize();
+                check_finite(&format!("{}.offset", field), &[offset])?;
+
+                Ok(Box::new(Plane { normal, offset }))
+            }
+            SurfaceDesc::Triangle { vertices } => {
+                for (i, v) in vertices.iter().enumerate() {
+                    check_finite(&format!("{}.vertices[{}]", field, i), v)?;
+                }
+
+                let vertices = [point(vertices[0]), point(vertices[1]), point(vertices[2])];
+                let area = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0]));
+                if area.norm_squared() == 0.0 {
+                    return invalid(
+                        &format!("{}.vertices", field),
+                        "triangle must not be degenerate",
+                    );
+                }
+
+                Ok(Box::new(Triangle { vertices }))
+            }
+        }
+    }
+}
+
+impl MeshDesc {
+    fn build(
+        self,
+        field: &str,
+        base_dir: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<Object>, LoadError> {
+        let path = base_dir.join(&self.path);
+
+        let format = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        // The lights and node transforms of a glTF file are kept, but its
+        // camera isn't used.
+        let mut file_warnings = Vec::new();
+        let imported = match format.as_deref() {
+            Some("obj") => {
+                import::obj::load(&path).map(|meshes| surfaces(meshes, &mut file_warnings))
+            }
+            Some("ply") => {
+                import::ply::load(&path).map(|meshes| surfaces(meshes, &mut file_warnings))
+            }
+            Some("stl") => {
+                import::stl::load(&path).map(|meshes| surfaces(meshes, &mut file_warnings))
+            }
+            Some("gltf") | Some("glb") => import::gltf::load(&path).map(|scene| {
+                file_warnings = scene.warnings;
+                scene
+                    .objects
+                    .into_iter()
+                    .map(|object| (object.surface, Some(object.material)))
+                    .collect()
+            }),
+            _ => return invalid(&format!("{}.path", field), "unsupported mesh format"),
+        }
+        .map_err(|err| LoadError::Import(path.clone(), err))?;
+
+        // The meshes of a file often share a warning, such as a missing
+        // material library, so each is only reported once.
+        for warning in file_warnings {
+            let warning = format!("{}: {}", path.display(), warning);
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+
+        let transform = build_transform(field, self.transform)?;
+
+        let mut objects = Vec::with_capacity(imported.len());
+        for (surface, imported_material) in imported {
+            let material = match (&self.material, imported_material) {
+                (Some(material), _) => {
+                    material.build(&format!("{}.material", field), base_dir, warnings)?
+                }
+                (None, Some(material)) => material,
+                (None, None) => default_material(),
+            };
+
+            objects.push(Object {
+                surface: transformed(surface, transform),
+                material,
+            });
+        }
+
+        Ok(objects)
+    }
+}
+
+// The surfaces of imported meshes, with the materials they come with. Their
+// warnings are added to `warnings`.
+fn surfaces(
+    meshes: Vec<ImportedMesh>,
+    warnings: &mut Vec<String>,
+) -> Vec<(Box<dyn Surface>, Option<MaterialBox>)> {
+    meshes
+        .into_iter()
+        .map(|mesh| {
+            warnings.extend(mesh.warnings);
+            (Box::new(mesh.mesh) as Box<dyn Surface>, mesh.material)
+        })
+        .collect()
+}
+
+// The material for meshes that don't come with one.
+fn default_material() -> MaterialBox {
+    MaterialBox::Reflective(Arc::new(PerfectDiffuseMaterial {
+        color: Box::new(ConstantTexture {
+            color: Vector3::new(0.8, 0.8, 0.8),
+        }),
+    }))
+}
+
+impl MaterialDesc {
+    fn build(
+        &self,
+        field: &str,
+        base_dir: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<MaterialBox, LoadError> {
+        match *self {
+            MaterialDesc::Diffuse { ref color } => {
+                Ok(MaterialBox::Reflective(Arc::new(PerfectDiffuseMaterial {
+                    color: color.build(&format!("{}.color", field), base_dir)?,
+                })))
+            }
+            MaterialDesc::Specular => {
+                Ok(MaterialBox::Reflective(Arc::new(PerfectSpecularMaterial)))
+            }
+            MaterialDesc::Refractive {
+                index_of_refraction,
+                reflect_prob,
+            } => {
+                if reflect_prob.is_some() {
+                    warnings.push(format!(
+                        "{}.reflect_prob is no longer used and is ignored",
+                        field
+                    ));
+                }
+
+                if !(index_of_refraction.is_finite() && index_of_refraction > 0.0) {
+                    return invalid(
+                        &format!("{}.index_of_refraction", field),
+                        "must be a positive number",
+                    );
+                }
+
+                Ok(MaterialBox::Reflective(Arc::new(
+                    PerfectRefractiveMaterial {
+                        index_of_refraction,
+                    },
+                )))
+            }
+            MaterialDesc::Conductor {
+                eta,
+                k,
+                roughness,
+                ref distribution,
+            } => {
+                let eta_field = format!("{}.eta", field);
+                check_finite(&eta_field, &eta)?;
+                if eta.iter().any(|c| *c <= 0.0) {
+                    return invalid(&eta_field, "components must be positive");
+                }
+
+                let k_field = format!("{}.k", field);
+                check_finite(&k_field, &k)?;
+                if k.iter().any(|c| *c < 0.0) {
+                    return invalid(&k_field, "components must not be negative");
+                }
+
+                Ok(MaterialBox::Reflective(Arc::new(ConductorMaterial {
+                    distribution: build_distribution(field, roughness, distribution)?,
+                    eta: vector(eta),
+                    k: vector(k),
+                })))
+            }
+            MaterialDesc::RoughDielectric {
+                index_of_refraction,
+                roughness,
+                ref distribution,
+            } => {
+                if !(index_of_refraction.is_finite() && index_of_refraction > 0.0) {
+                    return invalid(
+                        &format!("{}.index_of_refraction", field),
+                        "must be a positive number",
+                    );
+                }
+
+                Ok(MaterialBox::Reflective(Arc::new(RoughDielectricMaterial {
+                    distribution: build_distribution(field, roughness, distribution)?,
+                    index_of_refraction,
+                })))
+            }
+            MaterialDesc::Principled(ref principled) => Ok(MaterialBox::Reflective(Arc::new(
+                principled.build(field, base_dir)?,
+            ))),
+            MaterialDesc::Emissive {
+                emissivity,
+                ref color,
+                temperature,
+                ref texture,
+                one_sided,
+            } => {
+                if !(emissivity.is_finite() && emissivity >= 0.0) {
+                    return invalid(
+                        &format!("{}.emissivity", field),
+                        "must be a non-negative number",
+                    );
+                }
+
+                // The emission is white unless a color or texture is given,
+                // and may be tinted by the color of a black body.
+                let emission: Box<dyn Texture> = match (color, texture) {
+                    (Some(_), Some(_)) => {
+                        return invalid(
+                            &format!("{}.texture", field),
+                            "cannot be given along with `color`",
+                        )
+                    }
+                    (Some(color), None) => color.build(&format!("{}.color", field), base_dir)?,
+                    (None, Some(path)) => {
+                        let path = base_dir.join(path);
+                        let texture =
+                            ImageTexture::open(&path, TextureFilter::Trilinear, WrapMode::Repeat)
+                                .map_err(|err| LoadError::Texture(path.clone(), err))?;
+                        Box::new(texture)
+                    }
+                    (None, None) => Box::new(ConstantTexture {
+                        color: Vector3::new(1.0, 1.0, 1.0),
+                    }),
+                };
+
+                let tint = match temperature {
+                    Some(_) if color.is_some() => {
+                        return invalid(
+                            &format!("{}.temperature", field),
+                            "cannot be given along with `color`",
+                        )
+                    }
+                    Some(temperature) => {
+                        if !(temperature.is_finite() && temperature > 0.0) {
+                            return invalid(
+                                &format!("{}.temperature", field),
+                                "must be a positive number of kelvins",
+                            );
+                        }
+                        blackbody(temperature)
+                    }
+                    None => Vector3::new(1.0, 1.0, 1.0),
+                };
+
+                let emission = Box::new(ScaledTexture {
+                    texture: emission,
+                    scale: tint * emissivity,
+                });
+
+                Ok(MaterialBox::Emissive(Arc::new(EmissiveMaterial {
+                    emission,
+                    one_sided,
+                })))
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use na::{Matrix3, Vector3};
+
+// The second radiation constant, hc / k, in meter kelvins.
+const C2: f64 = 1.438_777e-2;
+
+// The color of light emitted by a black body at the given temperature in
+// kelvins, in linear sRGB with a luminance of one. Temperatures around 6500 K
+// are close to white; lower ones are redder and higher ones bluer. Colors
+// outside the sRGB gamut, such as very low temperatures, are clamped.
+pub fn blackbody(temperature: f32) -> Vector3<f32> {
+    let temperature = f64::from(temperature);
+
+    // Integrate Planck's law against the CIE color matching functions.
+    let mut xyz = Vector3::<f64>::zeros();
+    for nm in 360..=830 {
+        let lambda = f64::from(nm) * 1e-9;
+        let radiance = 1.0 / (lambda.powi(5) * ((C2 / (lambda * temperature)).exp_m1()));
+        xyz += color_matching(f64::from(nm)) * radiance;
+    }
+
+    if !(xyz.y > 0.0 && xyz.y.is_finite()) {
+        return Vector3::zeros();
+    }
+
+    let rgb = xyz_to_srgb() * (xyz / xyz.y).map(|c| c as f32);
+    rgb.map(|c| c.max(0.0))
+}
+
+// The matrix from CIE XYZ to linear sRGB, with a D65 white point.
+fn xyz_to_srgb() -> Matrix3<f32> {
+    Matrix3::new(
+        3.240_6, -1.537_2, -0.498_6, //
+        -0.968_9, 1.875_8, 0.041_5, //
+        0.055_7, -0.204_0, 1.057_0,
+    )
+}
+
+// The CIE 1931 2° color matching functions at the given wavelength in
+// nanometers.
+//
+// Reference: Wyman et al., "Simple Analytic Approximations to the CIE XYZ
+// Color Matching Functions" (2013)
+fn color_matching(lambda: f64) -> Vector3<f64> {
+    // A Gaussian with different widths either side of its peak.
+    let g = |mu: f64, below: f64, above: f64| {
+        let sigma = if lambda < mu { below } else { above };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    Vector3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
@@ -1,11 +1,77 @@
+use aabb::Aabb;
 use intersection::Intersection;
-use na::{Point3, Vector3};
-use ray::Ray;
+use na::{Point2, Point3, Vector3};
+use ray::{gamma, Ray};
+use sampling::{from_basis, orthonormal_basis, uniform_cone, uniform_triangle};
+use std::f32::consts::PI;
 
-pub trait Surface {
+pub trait Surface: Send + Sync {
     // Check if a ray intersects with the surface.
     fn intersects(&self, ray: &Ray) -> Option<Intersection>;
-    fn normal_towards(&self, point: Point3<f32>) -> Vector3<f32>;
+
+    // Check if a ray hits the surface anywhere, without finding the closest
+    // hit or anything about it. This is all shadow rays need to know, so
+    // surfaces should override it with something cheaper where they can.
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.intersects(ray).is_some()
+    }
+
+    // The bounding box of the surface, or `None` if the surface is infinite.
+    fn bounds(&self) -> Option<Aabb>;
+
+    // Sample a point on the surface as seen from `reference`, so that the
+    // surface can be used as a light. `u` is a pair of uniformly distributed
+    // random numbers in [0, 1). Surfaces which can't be sampled return `None`.
+    fn sample_towards(&self, _reference: &Point3<f32>, _u: (f32, f32)) -> Option<SurfaceSample> {
+        None
+    }
+
+    // The density, with respect to solid angle, with which `sample_towards`
+    // would choose the point where a ray from `reference` in the given
+    // direction hits the surface.
+    fn pdf_towards(&self, _reference: &Point3<f32>, _direction: &Vector3<f32>) -> f32 {
+        0.0
+    }
+}
+
+// A point sampled on a surface.
+pub struct SurfaceSample {
+    pub position: Point3<f32>,
+
+    // A bound on the absolute error in each coordinate of `position`.
+    pub error: Vector3<f32>,
+
+    pub normal: Vector3<f32>,
+
+    // The texture coordinates of the surface at the point.
+    pub uv: Point2<f32>,
+
+    // The color given to the surface by its vertices, if it has one.
+    pub color: Option<Vector3<f32>>,
+
+    // The density of having chosen this point, with respect to solid angle as
+    // seen from the reference point.
+    pub pdf: f32,
+}
+
+// Convert a density with respect to surface area at `position` into a density
+// with respect to solid angle as seen from `reference`. Points seen exactly
+// edge-on have a density of zero, as they can't be sampled.
+pub fn area_to_solid_angle(
+    pdf: f32,
+    reference: &Point3<f32>,
+    position: &Point3<f32>,
+    normal: &Vector3<f32>,
+) -> f32 {
+    let offset = position - reference;
+    let distance2 = offset.norm_squared();
+    let cos = normal.dot(&offset).abs() / distance2.sqrt();
+
+    if cos > 0.0 {
+        pdf * distance2 / cos
+    } else {
+        0.0
+    }
 }
 
 pub struct Sphere {
@@ -13,8 +79,34 @@ pub struct Sphere {
     pub radius: f32,
 }
 
-impl Surface for Sphere {
-    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+impl Sphere {
+    pub fn area(&self) -> f32 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    pub fn normal_towards(&self, point: Point3<f32>) -> Vector3<f32> {
+        // The normal is a ray traced from the center of the sphere to the given
+        // point, normalized.
+        (point - self.center).normalize()
+    }
+
+    // Move a point which is roughly on the sphere onto it, which is much more
+    // accurate than the point a ray hits the sphere at. Returns the point and
+    // a bound on its error.
+    fn refine(&self, point: &Point3<f32>) -> (Point3<f32>, Vector3<f32>) {
+        let local = point - self.center;
+        let local = local * (self.radius / local.norm());
+        let position = self.center + local;
+
+        (
+            position,
+            local.abs() * gamma(5) + position.coords.abs() * gamma(1),
+        )
+    }
+
+    // The distance along the ray to the closest point where it hits the
+    // sphere, if it does.
+    fn hit_distance(&self, ray: &Ray) -> Option<f32> {
         // Equations taken from http://en.wikipedia.org/wiki/Line–sphere_intersection
         let oc = ray.origin - self.center;
 
@@ -23,7 +115,14 @@ impl Surface for Sphere {
         let b = 2.0 * ray.direction.dot(&oc);
         let c = oc.norm_squared() - self.radius.powi(2);
 
-        let det2 = b.powi(2) - 4.0 * a * c;
+        // b² - 4ac loses most of its precision to cancellation when the ray
+        // passes near the edge of the sphere, so it is found from how closely
+        // the ray passes the center instead.
+        //
+        // Reference: Haines et al., "Precision Improvements for Ray/Sphere
+        // Intersection", Ray Tracing Gems (2019)
+        let closest = (oc - ray.direction * (b / (2.0 * a))).norm();
+        let det2 = 4.0 * a * (self.radius + closest) * (self.radius - closest);
 
         // Three options based on the value of det^2:
         // 1) det^2 < 0.0: no solutions
@@ -33,39 +132,183 @@ impl Surface for Sphere {
             // Only sqrt det once we know we have to.
             let det = det2.sqrt();
 
-            // Calculate the two solutions to the quadratic equation.
-            let t1 = (-b + det) / (2.0 * a);
-            let t2 = (-b - det) / (2.0 * a);
+            // Calculate the two solutions to the quadratic equation. Adding
+            // `b` and `det` with the same sign avoids cancellation, which
+            // would make the solution near the ray origin inaccurate.
+            let q = -0.5 * (b + b.signum() * det);
+            let (t1, t2) = (q / a, c / q);
+            let (near, far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
 
-            // Choose the positive intersection with the minimum distance.
-            let dist = if t1 > 0.0 && t1 < t2 {
-                Some(t1)
-            } else if t2 > 0.0 && t2 < t1 {
-                Some(t2)
+            // Choose the closest intersection within the ray. Only the far one
+            // is ahead of rays that start inside the sphere.
+            if ray.contains(near) {
+                Some(near)
+            } else if ray.contains(far) {
+                Some(far)
             } else {
                 None
-            };
-
-            dist.map(|d| Intersection::new_from_distance(d, ray, self))
+            }
         } else {
             None
         }
     }
 
-    fn normal_towards(&self, point: Point3<f32>) -> Vector3<f32> {
-        // The normal is a ray traced from the center of the sphere to the given
-        // point, normalized.
-        (point - self.center).normalize()
+    // The texture coordinates of the point in the given direction from the
+    // center: u is the longitude about the y axis, and v the latitude from the
+    // bottom pole up to the top.
+    pub fn uv(normal: &Vector3<f32>) -> Point2<f32> {
+        let phi = normal.z.atan2(normal.x);
+        let u = if phi < 0.0 { phi + 2.0 * PI } else { phi } / (2.0 * PI);
+        let v = 1.0 - normal.y.clamp(-1.0, 1.0).acos() / PI;
+
+        Point2::new(u, v)
+    }
+
+    // How quickly the texture coordinates change around the point in the
+    // given direction from the center. v changes at the same rate everywhere,
+    // but u changes faster towards the poles, where the circles of latitude
+    // are smaller.
+    pub fn uv_density(&self, normal: &Vector3<f32>) -> f32 {
+        let sin_theta = (1.0 - normal.y * normal.y).max(0.0).sqrt();
+        let du = 1.0 / (2.0 * PI * self.radius * sin_theta);
+        let dv = 1.0 / (PI * self.radius);
+
+        du.max(dv)
+    }
+}
+
+impl Surface for Sphere {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        self.hit_distance(ray).map(|d| {
+            let (position, error) = self.refine(&ray.at(d));
+            let normal = self.normal_towards(position);
+            Intersection::new(
+                d,
+                position,
+                normal,
+                Sphere::uv(&normal),
+                self.uv_density(&normal),
+            )
+            .with_error(error)
+        })
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.hit_distance(ray).is_some()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+
+        Some(Aabb {
+            min: self.center - r,
+            max: self.center + r,
+        })
+    }
+
+    fn sample_towards(
+        &self,
+        reference: &Point3<f32>,
+        (u1, u2): (f32, f32),
+    ) -> Option<SurfaceSample> {
+        let offset = self.center - reference;
+        let distance2 = offset.norm_squared();
+        let radius2 = self.radius.powi(2);
+
+        if distance2 <= radius2 {
+            // From inside the sphere all of it is visible, so sample the whole
+            // surface uniformly.
+            let z = 1.0 - 2.0 * u1;
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            let phi = 2.0 * PI * u2;
+
+            let normal = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+            let (position, error) = self.refine(&(self.center + normal * self.radius));
+            let pdf = area_to_solid_angle(1.0 / self.area(), reference, &position, &normal);
+
+            return Some(SurfaceSample {
+                position,
+                error,
+                normal,
+                uv: Sphere::uv(&normal),
+                color: None,
+                pdf,
+            });
+        }
+
+        // From outside, sample directions within the cone that the sphere
+        // subtends, which only picks visible points.
+        let distance = distance2.sqrt();
+        let axis = offset / distance;
+        let cos_theta_max = (1.0 - radius2 / distance2).max(0.0).sqrt();
+
+        let local = uniform_cone((u1, u2), cos_theta_max);
+        let direction = from_basis(&local, &axis);
+
+        // Distance along the sampled direction to the near side of the sphere.
+        let sin2_theta = 1.0 - local.z * local.z;
+        let t = distance * local.z - (radius2 - distance2 * sin2_theta).max(0.0).sqrt();
+
+        let (position, error) = self.refine(&(reference + direction * t));
+        let normal = self.normal_towards(position);
+        Some(SurfaceSample {
+            position,
+            error,
+            normal,
+            uv: Sphere::uv(&normal),
+            color: None,
+            pdf: subtended_cone_pdf(radius2, distance2),
+        })
+    }
+
+    fn pdf_towards(&self, reference: &Point3<f32>, direction: &Vector3<f32>) -> f32 {
+        let distance2 = (self.center - reference).norm_squared();
+        let radius2 = self.radius.powi(2);
+
+        if distance2 <= radius2 {
+            let ray = Ray::new_from_air(*reference, *direction);
+            return match self.intersects(&ray) {
+                Some(i) => {
+                    area_to_solid_angle(1.0 / self.area(), reference, &i.position, &i.normal)
+                }
+                None => 0.0,
+            };
+        }
+
+        subtended_cone_pdf(radius2, distance2)
     }
 }
 
+// The density of directions sampled uniformly within the cone that a sphere
+// subtends. 1 - cos θ is found from sin² θ / (1 + cos θ), as subtracting from
+// one would round to zero for small or distant spheres.
+fn subtended_cone_pdf(radius2: f32, distance2: f32) -> f32 {
+    let sin2_theta_max = radius2 / distance2;
+    let cos_theta_max = (1.0 - sin2_theta_max).max(0.0).sqrt();
+
+    1.0 / (2.0 * PI * sin2_theta_max / (1.0 + cos_theta_max))
+}
+
 pub struct Plane {
     pub normal: Vector3<f32>,
     pub offset: f32,
 }
 
-impl Surface for Plane {
-    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+impl Plane {
+    // The normal doesn't have to be of unit length, so it's normalized here.
+    pub fn normal_towards(&self, _point: Point3<f32>) -> Vector3<f32> {
+        self.normal.normalize()
+    }
+
+    // The texture coordinates of a point on the plane, which are its distances
+    // along two perpendicular directions within the plane.
+    pub fn uv(&self, point: Point3<f32>) -> Point2<f32> {
+        let (s, t) = orthonormal_basis(&self.normal.normalize());
+        Point2::new(s.dot(&point.coords), t.dot(&point.coords))
+    }
+
+    // The distance along the ray to where it crosses the plane, if it does.
+    fn hit_distance(&self, ray: &Ray) -> Option<f32> {
         let origin = ray.origin.coords.map(|x| x - self.offset);
 
         let d = self.normal.dot(&ray.direction);
@@ -74,15 +317,38 @@ impl Surface for Plane {
         };
         let t = -self.normal.dot(&origin) / d;
 
-        if t > 0.0 {
-            Some(Intersection::new_from_distance(t, &ray, self))
+        if ray.contains(t) {
+            Some(t)
         } else {
             None
         }
     }
+}
 
-    fn normal_towards(&self, _point: Point3<f32>) -> Vector3<f32> {
-        self.normal
+impl Surface for Plane {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        self.hit_distance(ray).map(|t| {
+            // Project the point the ray reaches back onto the plane, as the
+            // distance along the ray is only accurate relative to how far it
+            // travelled.
+            let point = ray.at(t);
+            let normal = self.normal.normalize();
+            let height = normal.dot(&point.coords.map(|x| x - self.offset));
+            let position = point - normal * height;
+
+            let scale = position.coords.amax() + self.offset.abs();
+            Intersection::new(t, position, normal, self.uv(position), 1.0)
+                .with_error(Vector3::repeat(gamma(7) * scale))
+                .with_geometric_normal(normal)
+        })
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        self.hit_distance(ray).is_some()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
     }
 }
 
@@ -90,50 +356,149 @@ pub struct Triangle {
     pub vertices: [Point3<f32>; 3],
 }
 
-impl Surface for Triangle {
-    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
-        // Per http://www.cs.virginia.edu/~gfx/Courses/2003/ImageSynthesis/papers/Acceleration/Fast%20MinimumStorage%20RayTriangle%20Intersection.pdf
-        let e1 = self.vertices[1] - self.vertices[0];
-        let e2 = self.vertices[2] - self.vertices[0];
-        let pvec = ray.direction.cross(&e2);
+impl Triangle {
+    // The normal of the triangle, following the winding order of its
+    // vertices. Triangles are hit from either side, and materials use which
+    // side of the normal a ray comes from to tell the inside from the outside.
+    pub fn normal(&self) -> Vector3<f32> {
+        let u = self.vertices[1] - self.vertices[0];
+        let v = self.vertices[2] - self.vertices[0];
 
-        let det = pvec.dot(&e1);
-        let inv_det = 1.0 / det;
+        u.cross(&v).normalize()
+    }
+}
 
-        let tvec = ray.origin - self.vertices[0];
-        let u = tvec.dot(&pvec) * inv_det;
+// Intersect a ray with the triangle with the given vertices. Returns the
+// distance along the ray and the barycentric coordinates (u, v) of the hit
+// point, weighting the second and third vertices.
+pub fn intersect_triangle(ray: &Ray, vertices: [&Point3<f32>; 3]) -> Option<(f32, f32, f32)> {
+    // Per http://www.cs.virginia.edu/~gfx/Courses/2003/ImageSynthesis/papers/Acceleration/Fast%20MinimumStorage%20RayTriangle%20Intersection.pdf
+    let e1 = vertices[1] - vertices[0];
+    let e2 = vertices[2] - vertices[0];
+    let pvec = ray.direction.cross(&e2);
 
-        if u < 0.0 || u > 1.0 {
-            return None;
-        }
+    let det = pvec.dot(&e1);
+    let inv_det = 1.0 / det;
 
-        let qvec = tvec.cross(&e1);
-        let v = ray.direction.dot(&qvec) * inv_det;
+    let tvec = ray.origin - vertices[0];
+    let u = tvec.dot(&pvec) * inv_det;
 
-        if v < 0.0 || u + v > 1.0 {
-            return None;
-        }
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
 
-        let t = e2.dot(&qvec) * inv_det;
-        if t > 0.0 {
-            Some(Intersection::new_from_distance(t, &ray, self))
-        } else {
-            None
-        }
+    let qvec = tvec.cross(&e1);
+    let v = ray.direction.dot(&qvec) * inv_det;
+
+    if v < 0.0 || u + v > 1.0 {
+        return None;
     }
 
-    fn normal_towards(&self, point: Point3<f32>) -> Vector3<f32> {
-        let u = self.vertices[1] - self.vertices[0];
-        let v = self.vertices[2] - self.vertices[0];
+    let t = e2.dot(&qvec) * inv_det;
+    if ray.contains(t) {
+        Some((t, u, v))
+    } else {
+        None
+    }
+}
 
-        let normal = u.cross(&v).normalize();
+// How quickly the barycentric coordinates of a triangle change across it. The
+// triangle covers half of the unit square of texture coordinates.
+pub fn barycentric_density(vertices: &[&Point3<f32>; 3]) -> f32 {
+    let area = (vertices[1] - vertices[0])
+        .cross(&(vertices[2] - vertices[0]))
+        .norm()
+        / 2.0;
 
-        // The triangle is two-sided, so we need to figure out which normal we
-        // should return.
-        if normal.dot(&point.coords) > 0.0 {
-            normal
-        } else {
-            -normal
+    (0.5 / area).sqrt()
+}
+
+// The point on the triangle with the given vertices at the barycentric
+// coordinates (u, v), along with a bound on the error in each of its
+// coordinates. Interpolating the vertices is much more accurate than the point
+// a ray hits the triangle at.
+pub fn triangle_point(
+    vertices: [&Point3<f32>; 3],
+    (u, v): (f32, f32),
+) -> (Point3<f32>, Vector3<f32>) {
+    let weighted = [
+        vertices[0].coords * (1.0 - u - v),
+        vertices[1].coords * u,
+        vertices[2].coords * v,
+    ];
+    let error = (weighted[0].abs() + weighted[1].abs() + weighted[2].abs()) * gamma(7);
+
+    (Point3::from(weighted[0] + weighted[1] + weighted[2]), error)
+}
+
+// Sample a point uniformly on the triangle with the given vertices. Returns the
+// barycentric coordinates (u, v) of the point, the unit normal following the
+// winding order and the triangle's area.
+pub fn sample_triangle(
+    vertices: [&Point3<f32>; 3],
+    u: (f32, f32),
+) -> ((f32, f32), Vector3<f32>, f32) {
+    let cross = (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0]));
+
+    (uniform_triangle(u), cross.normalize(), cross.norm() / 2.0)
+}
+
+impl Surface for Triangle {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let v = &self.vertices;
+
+        // Without texture coordinates of its own, the triangle uses its
+        // barycentric coordinates.
+        intersect_triangle(ray, [&v[0], &v[1], &v[2]]).map(|(t, b1, b2)| {
+            let (position, error) = triangle_point([&v[0], &v[1], &v[2]], (b1, b2));
+            Intersection::new(
+                t,
+                position,
+                self.normal(),
+                Point2::new(b1, b2),
+                barycentric_density(&[&v[0], &v[1], &v[2]]),
+            )
+            .with_error(error)
+            .with_barycentric(Point2::new(b1, b2))
+        })
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        let v = &self.vertices;
+        intersect_triangle(ray, [&v[0], &v[1], &v[2]]).is_some()
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+
+    fn sample_towards(&self, reference: &Point3<f32>, u: (f32, f32)) -> Option<SurfaceSample> {
+        let v = &self.vertices;
+        let vertices = [&v[0], &v[1], &v[2]];
+        let ((b1, b2), normal, area) = sample_triangle(vertices, u);
+        let (position, error) = triangle_point(vertices, (b1, b2));
+
+        Some(SurfaceSample {
+            position,
+            error,
+            normal,
+            uv: Point2::new(b1, b2),
+            color: None,
+            pdf: area_to_solid_angle(1.0 / area, reference, &position, &normal),
+        })
+    }
+
+    fn pdf_towards(&self, reference: &Point3<f32>, direction: &Vector3<f32>) -> f32 {
+        let v = &self.vertices;
+        let ray = Ray::new_from_air(*reference, *direction);
+
+        match intersect_triangle(&ray, [&v[0], &v[1], &v[2]]) {
+            Some((t, _, _)) => {
+                let cross = (v[1] - v[0]).cross(&(v[2] - v[0]));
+                let area = cross.norm() / 2.0;
+                area_to_solid_angle(1.0 / area, reference, &ray.at(t), &cross.normalize())
+            }
+            None => 0.0,
         }
     }
 }
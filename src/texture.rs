@@ -0,0 +1,559 @@
+use exr;
+use image::{self, ImageError};
+use intersection::Intersection;
+use na::{DMatrix, Point2, Point3, Vector2, Vector3};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use tonemap::srgb_to_linear;
+
+// Where on a surface a texture is looked up.
+#[derive(Clone, Copy, Debug)]
+pub struct TexturePoint {
+    pub position: Point3<f32>,
+    pub uv: Point2<f32>,
+
+    // The color given to the surface by its vertices, if it has one.
+    pub color: Option<Vector3<f32>>,
+
+    // The width, in texture coordinates, of the area around `uv` seen by a
+    // single pixel, which the texture should be averaged over. Zero means the
+    // texture is looked up without blurring.
+    pub width: f32,
+}
+
+impl TexturePoint {
+    // The point at which a ray intersected a surface.
+    pub fn new(intersection: &Intersection) -> TexturePoint {
+        TexturePoint {
+            position: intersection.position,
+            uv: intersection.uv,
+            color: intersection.color,
+            width: intersection.footprint * intersection.uv_density,
+        }
+    }
+}
+
+// A color that varies over a surface.
+pub trait Texture: Send + Sync {
+    // The color of the texture at the given point.
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32>;
+}
+
+// The same color everywhere.
+pub struct ConstantTexture {
+    pub color: Vector3<f32>,
+}
+
+impl Texture for ConstantTexture {
+    fn evaluate(&self, _: &TexturePoint) -> Vector3<f32> {
+        self.color
+    }
+}
+
+// The color of the surface's vertices, interpolated across it, for meshes
+// which are colored by vertex, or `fallback` on surfaces without vertex colors.
+pub struct VertexColorTexture {
+    pub fallback: Vector3<f32>,
+}
+
+impl Texture for VertexColorTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        point.color.unwrap_or(self.fallback)
+    }
+}
+
+// Another texture with each channel multiplied by a constant.
+pub struct ScaledTexture {
+    pub texture: Box<dyn Texture>,
+    pub scale: Vector3<f32>,
+}
+
+impl Texture for ScaledTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        self.texture.evaluate(point).component_mul(&self.scale)
+    }
+}
+
+// Another texture with its texture coordinates multiplied by `scale`, so that
+// it repeats that many times as often.
+pub struct UvScaledTexture {
+    pub texture: Box<dyn Texture>,
+    pub scale: Vector2<f32>,
+}
+
+impl Texture for UvScaledTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        self.texture.evaluate(&TexturePoint {
+            uv: Point2::from(point.uv.coords.component_mul(&self.scale)),
+            width: point.width * self.scale.x.abs().max(self.scale.y.abs()),
+            ..*point
+        })
+    }
+}
+
+// How an image texture is looked up between its pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureFilter {
+    // The closest pixel, which gives a blocky look when magnified.
+    Nearest,
+
+    // Interpolate between the four closest pixels.
+    Bilinear,
+
+    // Interpolate bilinearly within the two mipmap levels closest to the size
+    // of a pixel, and between them. This avoids aliasing when the texture is
+    // seen from far away.
+    Trilinear,
+}
+
+impl FromStr for TextureFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TextureFilter, String> {
+        match s {
+            "nearest" => Ok(TextureFilter::Nearest),
+            "bilinear" => Ok(TextureFilter::Bilinear),
+            "trilinear" => Ok(TextureFilter::Trilinear),
+            _ => Err(format!("unknown texture filter `{}`", s)),
+        }
+    }
+}
+
+// What an image texture shows outside of the [0, 1] range of texture
+// coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    // The image repeats.
+    Repeat,
+
+    // The image repeats, flipped every other time, so that its edges meet.
+    Mirror,
+
+    // The pixels at the edges of the image continue forever.
+    Clamp,
+}
+
+impl WrapMode {
+    // Bring a pixel coordinate outside of [0, size) back within it.
+    fn wrap(self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        match self {
+            WrapMode::Repeat => i.rem_euclid(size) as usize,
+            WrapMode::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                (if i < size { i } else { 2 * size - 1 - i }) as usize
+            }
+            WrapMode::Clamp => i.clamp(0, size - 1) as usize,
+        }
+    }
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WrapMode, String> {
+        match s {
+            "repeat" => Ok(WrapMode::Repeat),
+            "mirror" => Ok(WrapMode::Mirror),
+            "clamp" => Ok(WrapMode::Clamp),
+            _ => Err(format!("unknown wrap mode `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Image(ImageError),
+    Exr(exr::error::Error),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureError::Image(ref err) => write!(f, "{}", err),
+            TextureError::Exr(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TextureError::Image(ref err) => Some(err),
+            TextureError::Exr(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<ImageError> for TextureError {
+    fn from(err: ImageError) -> TextureError {
+        TextureError::Image(err)
+    }
+}
+
+impl From<exr::error::Error> for TextureError {
+    fn from(err: exr::error::Error) -> TextureError {
+        TextureError::Exr(err)
+    }
+}
+
+// A texture looked up from an image. Texture coordinates (0, 0) are the bottom
+// left of the image, and (1, 1) the top right.
+pub struct ImageTexture {
+    // The image, followed by successively halved versions of it down to a
+    // single pixel if it is filtered trilinearly. Each is indexed by (x, y)
+    // with y increasing down the image, and holds linear colors.
+    levels: Vec<DMatrix<Vector3<f32>>>,
+
+    filter: TextureFilter,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    // Create a texture from linear colors indexed by (x, y), with y increasing
+    // down the image.
+    //
+    // Panics if the image is empty.
+    pub fn new(
+        pixels: DMatrix<Vector3<f32>>,
+        filter: TextureFilter,
+        wrap: WrapMode,
+    ) -> ImageTexture {
+        assert!(!pixels.is_empty());
+
+        let mut levels = vec![pixels];
+        if filter == TextureFilter::Trilinear {
+            loop {
+                let next = match levels.last() {
+                    Some(level) if level.nrows() > 1 || level.ncols() > 1 => {
+                        downsample(level, wrap)
+                    }
+                    _ => break,
+                };
+                levels.push(next);
+            }
+        }
+
+        ImageTexture {
+            levels,
+            filter,
+            wrap,
+        }
+    }
+
+    // Load an image file. OpenEXR and Radiance HDR images hold linear colors,
+    // while the colors of other formats, such as PNG and JPEG, are decoded
+    // from sRGB.
+    pub fn open(
+        path: &Path,
+        filter: TextureFilter,
+        wrap: WrapMode,
+    ) -> Result<ImageTexture, TextureError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+
+        let pixels = match extension.as_deref() {
+            Some("exr") => {
+                exr::prelude::read_first_rgba_layer_from_file(
+                    path,
+                    |size, _| DMatrix::from_element(size.0, size.1, Vector3::zeros()),
+                    |pixels: &mut DMatrix<Vector3<f32>>,
+                     position,
+                     (r, g, b, _a): (f32, f32, f32, f32)| {
+                        pixels[(position.0, position.1)] = Vector3::new(r, g, b);
+                    },
+                )?
+                .layer_data
+                .channel_data
+                .pixels
+            }
+            Some("hdr") => {
+                let file = File::open(path).map_err(ImageError::IoError)?;
+                let decoder = image::hdr::HdrDecoder::new(BufReader::new(file))?;
+                let width = decoder.metadata().width as usize;
+                let height = decoder.metadata().height as usize;
+                let data = decoder.read_image_hdr()?;
+
+                DMatrix::from_fn(width, height, |x, y| {
+                    let pixel = data[y * width + x];
+                    Vector3::new(pixel[0], pixel[1], pixel[2])
+                })
+            }
+            _ => rgb8_pixels(&image::open(path)?, true),
+        };
+
+        Ok(ImageTexture::new(pixels, filter, wrap))
+    }
+
+    // Decode an image held in memory, such as one embedded in another file,
+    // in whichever format its contents are recognized as. The colors are
+    // decoded from sRGB if `srgb` is set, and taken as linear otherwise, as
+    // for images which hold other data than colors.
+    pub fn decode(
+        contents: &[u8],
+        srgb: bool,
+        filter: TextureFilter,
+        wrap: WrapMode,
+    ) -> Result<ImageTexture, TextureError> {
+        let image = image::load_from_memory(contents)?;
+        Ok(ImageTexture::new(rgb8_pixels(&image, srgb), filter, wrap))
+    }
+
+    // The width and height of the image in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        (self.levels[0].nrows(), self.levels[0].ncols())
+    }
+
+    // How many mipmap levels there are, including the full size image.
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    // The pixel of a mipmap level at the given coordinates, which may be
+    // outside of the image.
+    fn pixel(&self, level: usize, x: i64, y: i64) -> Vector3<f32> {
+        let pixels = &self.levels[level];
+        pixels[(
+            self.wrap.wrap(x, pixels.nrows()),
+            self.wrap.wrap(y, pixels.ncols()),
+        )]
+    }
+
+    // Look up a mipmap level, interpolating bilinearly between pixels.
+    fn bilinear(&self, level: usize, uv: &Point2<f32>) -> Vector3<f32> {
+        let (width, height) = (self.levels[level].nrows(), self.levels[level].ncols());
+
+        // Continuous pixel coordinates, where pixel centers are at half
+        // integers.
+        let x = uv.x * width as f32 - 0.5;
+        let y = (1.0 - uv.y) * height as f32 - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        self.pixel(level, x0, y0) * ((1.0 - dx) * (1.0 - dy))
+            + self.pixel(level, x0 + 1, y0) * (dx * (1.0 - dy))
+            + self.pixel(level, x0, y0 + 1) * ((1.0 - dx) * dy)
+            + self.pixel(level, x0 + 1, y0 + 1) * (dx * dy)
+    }
+}
+
+// The pixels of an image with 8 bits per channel, as linear colors.
+fn rgb8_pixels(image: &image::DynamicImage, srgb: bool) -> DMatrix<Vector3<f32>> {
+    let image = image.to_rgb8();
+    let (width, height) = image.dimensions();
+    let decode = |value: u8| {
+        let value = f32::from(value) / 255.0;
+        if srgb {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+
+    DMatrix::from_fn(width as usize, height as usize, |x, y| {
+        let pixel = image.get_pixel(x as u32, y as u32);
+        Vector3::new(decode(pixel[0]), decode(pixel[1]), decode(pixel[2]))
+    })
+}
+
+// Halve an image in each dimension by averaging blocks of 2 x 2 pixels. An odd
+// width or height rounds up, with the last blocks extending past the edge of
+// the image according to the wrap mode.
+fn downsample(pixels: &DMatrix<Vector3<f32>>, wrap: WrapMode) -> DMatrix<Vector3<f32>> {
+    let (width, height) = (pixels.nrows(), pixels.ncols());
+    let at = |x: usize, y: usize| pixels[(wrap.wrap(x as i64, width), wrap.wrap(y as i64, height))];
+
+    DMatrix::from_fn(width.div_ceil(2), height.div_ceil(2), |x, y| {
+        let (x, y) = (2 * x, 2 * y);
+        (at(x, y) + at(x + 1, y) + at(x, y + 1) + at(x + 1, y + 1)) / 4.0
+    })
+}
+
+impl Texture for ImageTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        match self.filter {
+            TextureFilter::Nearest => {
+                let (width, height) = self.size();
+                let x = (point.uv.x * width as f32).floor() as i64;
+                let y = ((1.0 - point.uv.y) * height as f32).floor() as i64;
+                self.pixel(0, x, y)
+            }
+            TextureFilter::Bilinear => self.bilinear(0, &point.uv),
+            TextureFilter::Trilinear => {
+                // Pick the level whose pixels are about as wide as the area to
+                // average over.
+                let (width, height) = self.size();
+                let texels = point.width * width.max(height) as f32;
+                let last = self.levels.len() - 1;
+                let level = texels.max(1.0).log2().min(last as f32);
+
+                let below = level.floor() as usize;
+                if below == last {
+                    return self.bilinear(below, &point.uv);
+                }
+
+                let t = level - below as f32;
+                self.bilinear(below, &point.uv) * (1.0 - t)
+                    + self.bilinear(below + 1, &point.uv) * t
+            }
+        }
+    }
+}
+
+// Alternating squares of two textures, one square per unit of the texture
+// coordinates. Squares that are smaller than a pixel are blended together.
+pub struct CheckerboardTexture {
+    pub even: Box<dyn Texture>,
+    pub odd: Box<dyn Texture>,
+}
+
+impl Texture for CheckerboardTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        let (u, v) = (point.uv.x, point.uv.y);
+        let half_width = point.width / 2.0;
+
+        // The fraction of the pixel covered by odd squares, from integrating a
+        // box filter over the checkerboard.
+        //
+        // Reference: Pharr et al., "Physically Based Rendering" (3rd ed.),
+        // section 10.5.1
+        let odd = if half_width == 0.0 {
+            (u.floor() + v.floor()).rem_euclid(2.0)
+        } else if half_width >= 1.0 {
+            0.5
+        } else {
+            // The integral from 0 to x of a function which is 0 on even unit
+            // intervals and 1 on odd ones.
+            let integral = |x: f32| {
+                let half = x / 2.0;
+                half.floor() + 2.0 * (half - half.floor() - 0.5).max(0.0)
+            };
+
+            let s = (integral(u + half_width) - integral(u - half_width)) / (2.0 * half_width);
+            let t = (integral(v + half_width) - integral(v - half_width)) / (2.0 * half_width);
+            s + t - 2.0 * s * t
+        };
+
+        let mut color = Vector3::zeros();
+        if odd < 1.0 {
+            color += self.even.evaluate(point) * (1.0 - odd);
+        }
+        if odd > 0.0 {
+            color += self.odd.evaluate(point) * odd;
+        }
+
+        color
+    }
+}
+
+// A blend from one color to another across the texture coordinates. The
+// blend goes from `from` to `to` as the dot product of the texture coordinates
+// with `direction` goes from 0 to 1, and is constant beyond that.
+pub struct GradientTexture {
+    pub from: Vector3<f32>,
+    pub to: Vector3<f32>,
+    pub direction: Vector2<f32>,
+}
+
+impl Texture for GradientTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        let t = point.uv.coords.dot(&self.direction).clamp(0.0, 1.0);
+        self.from * (1.0 - t) + self.to * t
+    }
+}
+
+// A blend between two colors following fractal Perlin noise over positions in
+// the scene, for irregular materials like marble or stone. Each octave adds
+// noise at twice the frequency and half the amplitude of the last.
+pub struct NoiseTexture {
+    pub low: Vector3<f32>,
+    pub high: Vector3<f32>,
+
+    // The frequency of the first octave, in features per unit of distance.
+    pub scale: f32,
+    pub octaves: u32,
+}
+
+impl Texture for NoiseTexture {
+    fn evaluate(&self, point: &TexturePoint) -> Vector3<f32> {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (self.scale, 1.0);
+        for _ in 0..self.octaves {
+            sum += perlin(&(point.position * frequency)) * amplitude;
+            total += amplitude;
+            frequency *= 2.0;
+            amplitude /= 2.0;
+        }
+
+        let t = if total > 0.0 {
+            (0.5 + 0.5 * sum / total).clamp(0.0, 1.0)
+        } else {
+            0.5
+        };
+        self.low * (1.0 - t) + self.high * t
+    }
+}
+
+// Perlin's improved gradient noise, which varies smoothly between about -1 and
+// 1, and is zero at integer coordinates.
+//
+// Reference: Perlin, "Improving Noise" (2002). Gradients are picked by hashing
+// the lattice coordinates rather than with a permutation table.
+pub fn perlin(p: &Point3<f32>) -> f32 {
+    let cell = p.coords.map(|c| c.floor());
+    let f = p.coords - cell;
+    let cell = cell.map(|c| c as i32);
+
+    // The gradient at a corner of the cell, dotted with the offset to it.
+    let grad = |dx: i32, dy: i32, dz: i32| {
+        let h = hash(cell.x + dx, cell.y + dy, cell.z + dz) & 15;
+        let (x, y, z) = (f.x - dx as f32, f.y - dy as f32, f.z - dz as f32);
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 {
+            y
+        } else if h == 12 || h == 14 {
+            x
+        } else {
+            z
+        };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    };
+
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+    let (u, v, w) = (fade(f.x), fade(f.y), fade(f.z));
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(0, 0, 0), grad(1, 0, 0)),
+            lerp(u, grad(0, 1, 0), grad(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, grad(0, 0, 1), grad(1, 0, 1)),
+            lerp(u, grad(0, 1, 1), grad(1, 1, 1)),
+        ),
+    )
+}
+
+// Mix the coordinates of a lattice point into a pseudorandom integer.
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
@@ -0,0 +1,146 @@
+use na::Vector3;
+use std::str::FromStr;
+
+// How radiance beyond the displayable range is compressed into [0, 1].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapOperator {
+    // Clip each channel to [0, 1].
+    Clamp,
+
+    // Reinhard's global operator, applied to luminance so that hues are
+    // preserved: L' = L / (1 + L).
+    Reinhard,
+
+    // Krzysztof Narkowicz's curve fit of the ACES filmic reference
+    // rendering transform.
+    Aces,
+}
+
+impl FromStr for ToneMapOperator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ToneMapOperator, String> {
+        match s.to_lowercase().as_str() {
+            "clamp" | "none" => Ok(ToneMapOperator::Clamp),
+            "reinhard" => Ok(ToneMapOperator::Reinhard),
+            "aces" | "filmic" => Ok(ToneMapOperator::Aces),
+            _ => Err(format!("unknown tone mapping operator `{}`", s)),
+        }
+    }
+}
+
+// The post-process that turns rendered radiance into 8-bit display values:
+// exposure, tone mapping, sRGB encoding and, optionally, dithering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    // Exposure adjustment in stops; each stop doubles the brightness.
+    pub exposure: f32,
+
+    pub operator: ToneMapOperator,
+
+    // Add triangular noise of one quantization step before rounding, which
+    // trades banding in smooth gradients for fine grain.
+    pub dither: bool,
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping {
+            exposure: 0.0,
+            operator: ToneMapOperator::Clamp,
+            dither: false,
+        }
+    }
+}
+
+impl ToneMapping {
+    // Map linear radiance to linear display values in [0, 1].
+    pub fn map(&self, color: Vector3<f32>) -> Vector3<f32> {
+        let color = color.map(|c| c.max(0.0)) * self.exposure.exp2();
+
+        let mapped = match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => {
+                let l = luminance(&color);
+                if l > 0.0 {
+                    color / (1.0 + l)
+                } else {
+                    color
+                }
+            }
+            ToneMapOperator::Aces => color.map(aces),
+        };
+
+        mapped.map(|c| c.min(1.0))
+    }
+
+    // Tone map, encode and quantize a color for display. The pixel position
+    // seeds the dither noise, so the same image is always produced.
+    pub fn to_srgb8(&self, color: Vector3<f32>, x: usize, y: usize) -> [u8; 3] {
+        let mapped = self.map(color);
+
+        let mut pixel = [0; 3];
+        for (c, value) in pixel.iter_mut().enumerate() {
+            let mut encoded = linear_to_srgb(mapped[c]) * 255.0;
+            if self.dither {
+                encoded += triangular_noise(x, y, c);
+            }
+
+            *value = encoded.round().clamp(0.0, 255.0) as u8;
+        }
+
+        pixel
+    }
+}
+
+// Relative luminance of a linear Rec. 709 color.
+pub fn luminance(color: &Vector3<f32>) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+// The sRGB transfer function, from linear [0, 1] to encoded [0, 1].
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// The inverse of `linear_to_srgb`.
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn aces(x: f32) -> f32 {
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    (x * (a * x + b)) / (x * (c * x + d) + e)
+}
+
+// Noise in (-1, 1) with a triangular distribution, which makes the
+// quantization error independent of the signal.
+fn triangular_noise(x: usize, y: usize, channel: usize) -> f32 {
+    let seed = (x as u32)
+        .wrapping_mul(0x8da6_b343)
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841))
+        .wrapping_add((channel as u32).wrapping_mul(0xcb1a_b31f));
+
+    let u1 = unit_hash(seed);
+    let u2 = unit_hash(seed ^ 0x68e3_1da4);
+    u1 - u2
+}
+
+// Hash an integer to a float in [0, 1).
+fn unit_hash(mut h: u32) -> f32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+
+    (h >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,270 @@
+use aabb::Aabb;
+use intersection::Intersection;
+use na::{Matrix3, Matrix4, Point3, Rotation3, Unit, Vector3, U1, U3};
+use ray::{gamma, Ray};
+use std::sync::Arc;
+use surface::{Surface, SurfaceSample};
+
+// An affine transform, kept together with its inverse so that points can be
+// moved both ways without inverting the matrix each time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4<f32>,
+    inverse: Matrix4<f32>,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    // Create a transform from a matrix, or `None` if it can't be inverted. The
+    // bottom row should be (0, 0, 0, 1); projective matrices aren't supported.
+    pub fn new(matrix: Matrix4<f32>) -> Option<Transform> {
+        matrix
+            .try_inverse()
+            .map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn translation(offset: &Vector3<f32>) -> Transform {
+        Transform {
+            matrix: Matrix4::new_translation(offset),
+            inverse: Matrix4::new_translation(&-offset),
+        }
+    }
+
+    // A rotation by `angle` radians counterclockwise about `axis`, looking
+    // down the axis towards the origin.
+    pub fn rotation(axis: &Vector3<f32>, angle: f32) -> Transform {
+        let rotation = Rotation3::from_axis_angle(&Unit::new_normalize(*axis), angle);
+
+        Transform {
+            matrix: rotation.to_homogeneous(),
+            inverse: rotation.inverse().to_homogeneous(),
+        }
+    }
+
+    // A scale along each axis. None of the factors may be zero.
+    pub fn scaling(factors: &Vector3<f32>) -> Transform {
+        Transform {
+            matrix: Matrix4::new_nonuniform_scaling(factors),
+            inverse: Matrix4::new_nonuniform_scaling(&factors.map(|f| 1.0 / f)),
+        }
+    }
+
+    // The transform which applies this one and then `other`.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4<f32> {
+        &self.matrix
+    }
+
+    pub fn point(&self, point: &Point3<f32>) -> Point3<f32> {
+        self.linear() * point + self.matrix.fixed_slice::<U3, U1>(0, 3)
+    }
+
+    pub fn vector(&self, vector: &Vector3<f32>) -> Vector3<f32> {
+        self.linear() * vector
+    }
+
+    // A bound on the error in each coordinate of a transformed point, given
+    // the bound `error` on the point before it was transformed.
+    pub fn point_error(&self, point: &Point3<f32>, error: &Vector3<f32>) -> Vector3<f32> {
+        let linear = self.linear().abs();
+        let translation = self.matrix.fixed_slice::<U3, U1>(0, 3).abs();
+
+        linear * error * (gamma(3) + 1.0) + (linear * point.coords.abs() + translation) * gamma(3)
+    }
+
+    // Transform a surface normal, which has to be done by the inverse
+    // transpose to stay perpendicular to the surface. The result isn't
+    // normalized.
+    pub fn normal(&self, normal: &Vector3<f32>) -> Vector3<f32> {
+        self.inverse.fixed_slice::<U3, U3>(0, 0).tr_mul(normal)
+    }
+
+    // The box around the transformed corners of `bounds`.
+    pub fn bounds(&self, bounds: &Aabb) -> Aabb {
+        let corners: Vec<Point3<f32>> = (0..8)
+            .map(|i| {
+                Point3::new(
+                    if i & 1 == 0 {
+                        bounds.min.x
+                    } else {
+                        bounds.max.x
+                    },
+                    if i & 2 == 0 {
+                        bounds.min.y
+                    } else {
+                        bounds.max.y
+                    },
+                    if i & 4 == 0 {
+                        bounds.min.z
+                    } else {
+                        bounds.max.z
+                    },
+                )
+            })
+            .map(|corner| self.point(&corner))
+            .collect();
+
+        Aabb::from_points(&corners)
+    }
+
+    fn linear(&self) -> Matrix3<f32> {
+        self.matrix.fixed_slice::<U3, U3>(0, 0).into_owned()
+    }
+}
+
+// A surface moved into the scene by a transform. The surface itself is shared,
+// so the same geometry can be placed many times while only being stored once.
+pub struct TransformedSurface {
+    surface: Arc<dyn Surface>,
+
+    // From the surface's own space into the scene.
+    transform: Transform,
+
+    // How much the transform scales volumes, used to convert densities.
+    determinant: f32,
+}
+
+impl TransformedSurface {
+    pub fn new(surface: Arc<dyn Surface>, transform: Transform) -> TransformedSurface {
+        TransformedSurface {
+            surface,
+            determinant: transform.linear().determinant().abs(),
+            transform,
+        }
+    }
+
+    pub fn surface(&self) -> &Arc<dyn Surface> {
+        &self.surface
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    // The ray in the surface's space, along with how much shorter the
+    // direction became before it was normalized again, and how far the origin
+    // was moved forwards. Distances along the ray in the surface's space are
+    // converted back to the scene by `world_distance`.
+    //
+    // Transforming the origin is inexact, which could move it to the other
+    // side of the surface a ray was spawned from, so it is moved forwards by
+    // as much as it may have moved.
+    fn object_ray(&self, ray: &Ray) -> (Ray, f32, f32) {
+        let inverse = self.transform.inverse();
+        let direction = inverse.vector(&ray.direction);
+        let scale = direction.norm();
+        let direction = direction / scale;
+
+        let error = inverse.point_error(&ray.origin, &Vector3::zeros());
+        let advance = direction.abs().dot(&error);
+
+        let object_ray = Ray {
+            origin: inverse.point(&ray.origin) + direction * advance,
+            direction,
+            media: ray.media,
+            t_min: (ray.t_min * scale - advance).max(0.0),
+            t_max: ray.t_max * scale - advance,
+        };
+
+        (object_ray, scale, advance)
+    }
+
+    // Convert a density with respect to solid angle in the surface's space to
+    // one in the scene, for a unit `direction` in the scene. The transform
+    // stretches some directions apart and squeezes others together, by the
+    // determinant of the inverse over the cube of how much it scales the
+    // direction.
+    fn world_pdf(&self, pdf: f32, direction: &Vector3<f32>) -> f32 {
+        let stretch = self.transform.inverse().vector(direction).norm();
+        pdf / (self.determinant * stretch.powi(3))
+    }
+}
+
+impl Surface for TransformedSurface {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let (object_ray, scale, advance) = self.object_ray(ray);
+
+        self.surface.intersects(&object_ray).map(|hit| {
+            let normal = self.transform.normal(&hit.normal);
+
+            // Texture coordinates change more slowly across a surface that has
+            // been scaled up, by the square root of how much its area grew.
+            let area_scale = self.determinant * normal.norm();
+
+            let intersection = Intersection::new(
+                (hit.distance + advance) / scale,
+                self.transform.point(&hit.position),
+                normal.normalize(),
+                hit.uv,
+                hit.uv_density / area_scale.sqrt(),
+            )
+            .with_error(self.transform.point_error(&hit.position, &hit.error))
+            .with_geometric_normal(self.transform.normal(&hit.geometric_normal).normalize());
+
+            Intersection {
+                barycentric: hit.barycentric,
+                color: hit.color,
+                ..intersection
+            }
+        })
+    }
+
+    fn occluded(&self, ray: &Ray) -> bool {
+        let (object_ray, _, _) = self.object_ray(ray);
+        self.surface.occluded(&object_ray)
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.surface
+            .bounds()
+            .map(|bounds| self.transform.bounds(&bounds))
+    }
+
+    fn sample_towards(&self, reference: &Point3<f32>, u: (f32, f32)) -> Option<SurfaceSample> {
+        let object_reference = self.transform.inverse().point(reference);
+
+        self.surface
+            .sample_towards(&object_reference, u)
+            .map(|sample| {
+                let position = self.transform.point(&sample.position);
+                let direction = (position - reference).normalize();
+
+                SurfaceSample {
+                    position,
+                    error: self.transform.point_error(&sample.position, &sample.error),
+                    normal: self.transform.normal(&sample.normal).normalize(),
+                    uv: sample.uv,
+                    color: sample.color,
+                    pdf: self.world_pdf(sample.pdf, &direction),
+                }
+            })
+    }
+
+    fn pdf_towards(&self, reference: &Point3<f32>, direction: &Vector3<f32>) -> f32 {
+        let (object_ray, _, _) = self.object_ray(&Ray::new_from_air(*reference, *direction));
+        let pdf = self
+            .surface
+            .pdf_towards(&object_ray.origin, &object_ray.direction);
+
+        self.world_pdf(pdf, &direction.normalize())
+    }
+}
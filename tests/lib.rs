@@ -1,3 +1,22 @@
 extern crate nalgebra as na;
 extern crate rand;
 extern crate renderer;
+
+use na::Vector3;
+use std::f32::consts::PI;
+
+// Integrate a function over the sphere with the midpoint rule.
+pub fn integrate_sphere<F: Fn(&Vector3<f32>) -> f32>(f: F) -> f32 {
+    let n = 300;
+    let mut sum = 0.0f64;
+    for i in 0..n {
+        for j in 0..n {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+            let phi = 2.0 * PI * (j as f32 + 0.5) / n as f32;
+            let r = (1.0 - z * z).sqrt();
+            sum += f64::from(f(&Vector3::new(r * phi.cos(), r * phi.sin(), z)));
+        }
+    }
+
+    (sum * 4.0 * f64::from(PI) / f64::from(n * n)) as f32
+}
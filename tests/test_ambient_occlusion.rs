@@ -0,0 +1,78 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{DMatrix, Vector3};
+use renderer::integrator::{AmbientOcclusionIntegrator, IntegratorKind};
+use renderer::render;
+use renderer::sampler::SamplerProperties;
+use renderer::scene_file;
+use std::path::Path;
+
+// Looking straight down at a floor, with a ball resting on it in the middle.
+const FLOOR: &str = r#"
+[camera]
+position = [0.0, 3.0, 0.0]
+target = [0.0, 0.0, 0.0]
+up = [0.0, 0.0, 1.0]
+
+[render]
+width = 32
+height = 32
+
+[integrator]
+type = "ambient_occlusion"
+samples_per_pixel = 64
+occlusion_distance = 1.0
+
+[[objects]]
+surface = { type = "plane", normal = [0.0, 1.0, 0.0], offset = 0.0 }
+material = { type = "diffuse", color = [1.0, 1.0, 1.0] }
+"#;
+
+const BALL: &str = r#"
+[[objects]]
+surface = { type = "sphere", center = [0.0, 0.5, 0.0], radius = 0.5 }
+material = { type = "diffuse", color = [1.0, 1.0, 1.0] }
+"#;
+
+fn render_occlusion(contents: &str) -> DMatrix<Vector3<f32>> {
+    let config = scene_file::parse(contents, Path::new("")).unwrap();
+    assert_eq!(config.integrator.kind, IntegratorKind::AmbientOcclusion);
+
+    let integrator = AmbientOcclusionIntegrator {
+        camera: &config.camera,
+        scene: &config.scene,
+        width: config.render.width,
+        height: config.render.height,
+        distance: config.integrator.occlusion_distance,
+        sampler: SamplerProperties {
+            kind: config.integrator.sampler,
+            seed: config.integrator.seed,
+            samples_per_pixel: config.render.samples_per_pixel,
+        },
+    };
+
+    render::render(&config.render, &integrator)
+}
+
+#[test]
+fn open_floor_test() {
+    let image = render_occlusion(FLOOR);
+
+    // Nothing can block the view of the sky from an empty floor.
+    assert!(image
+        .iter()
+        .all(|c| (c - Vector3::repeat(1.0)).norm() < 1e-5));
+}
+
+#[test]
+fn ball_darkens_floor_test() {
+    let image = render_occlusion(&format!("{}{}", FLOOR, BALL));
+
+    // The floor just beside the ball is partly hidden from the sky by it, the
+    // top of the ball isn't, and the corners are too far away to be affected.
+    assert!(image[(16, 12)].x < 0.9 && image[(16, 19)].x < 0.9);
+    assert_eq!(image[(15, 15)], Vector3::repeat(1.0));
+    assert_eq!(image[(0, 0)], Vector3::repeat(1.0));
+    assert_eq!(image[(31, 31)], Vector3::repeat(1.0));
+}
@@ -0,0 +1,143 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point2, Point3, Vector3};
+use renderer::bsdf::{Bsdf, LambertianBsdf, ShadingFrame, SpecularReflectionBsdf};
+use renderer::intersection::Intersection;
+use renderer::material::{PerfectRefractiveMaterial, ReflectiveMaterial};
+use renderer::microfacet::fresnel_dielectric;
+use renderer::ray::{MediumStack, Ray, INDEX_OF_REFRACTION_AIR};
+use renderer::sampler::{Sampler, UniformSampler};
+use std::f32::consts::PI;
+
+#[test]
+fn shading_frame_test() {
+    let normal = Vector3::new(1.0, 2.0, -3.0).normalize();
+    let frame = ShadingFrame::new(&normal);
+
+    assert!((frame.to_local(&normal) - Vector3::z()).norm() < 1e-6);
+
+    let v = Vector3::new(0.3, -0.2, 0.9);
+    assert!((frame.to_world(&frame.to_local(&v)) - v).norm() < 1e-6);
+}
+
+#[test]
+fn lambertian_sampling_test() {
+    let bsdf = LambertianBsdf {
+        color: Vector3::new(0.5, 0.25, 1.0),
+    };
+    let mut sampler = UniformSampler::new(3);
+
+    for &wo in &[Vector3::new(0.0, 0.6, 0.8), Vector3::new(0.0, 0.6, -0.8)] {
+        // Every sample is on the viewer's side, and agrees with eval and pdf.
+        let mut estimate = Vector3::zeros();
+        for _ in 0..10_000 {
+            let u_lobe = sampler.get_1d();
+            let sample = bsdf.sample(&wo, u_lobe, sampler.get_2d()).unwrap();
+
+            assert!(sample.wi.z * wo.z > 0.0);
+            assert!(!sample.specular);
+            assert!((sample.pdf - bsdf.pdf(&wo, &sample.wi)).abs() < 1e-5);
+            assert_eq!(sample.f, bsdf.eval(&wo, &sample.wi));
+
+            estimate += sample.f * (sample.wi.z.abs() / sample.pdf) / 10_000.0;
+        }
+
+        // Light arriving equally from every direction is reflected by the
+        // albedo.
+        assert!((estimate - bsdf.color).norm() < 1e-4);
+    }
+
+    // No light is transmitted through the surface.
+    let (up, down) = (Vector3::z(), -Vector3::z());
+    assert_eq!(bsdf.eval(&up, &down), Vector3::zeros());
+    assert_eq!(bsdf.pdf(&up, &down), 0.0);
+    assert!((bsdf.eval(&up, &up) * PI - bsdf.color).norm() < 1e-6);
+}
+
+#[test]
+fn specular_reflection_test() {
+    let bsdf = SpecularReflectionBsdf {
+        color: Vector3::new(1.0, 1.0, 1.0),
+    };
+    let wo = Vector3::new(0.6, 0.0, 0.8);
+
+    let sample = bsdf.sample(&wo, 0.5, (0.5, 0.5)).unwrap();
+    assert!(sample.specular && bsdf.is_specular());
+    assert_eq!(sample.wi, Vector3::new(-0.6, 0.0, 0.8));
+    assert!((sample.f * sample.wi.z / sample.pdf - bsdf.color).norm() < 1e-6);
+    assert_eq!(bsdf.eval(&wo, &sample.wi), Vector3::zeros());
+}
+
+#[test]
+fn medium_stack_test() {
+    let air = MediumStack::air();
+    assert_eq!(air.current(), INDEX_OF_REFRACTION_AIR);
+    assert_eq!(air.outside(), INDEX_OF_REFRACTION_AIR);
+    assert_eq!(air.exit(), air);
+
+    // A glass inside water.
+    let water = air.enter(1.33);
+    let glass = water.enter(1.5);
+    assert_eq!(glass.current(), 1.5);
+    assert_eq!(glass.outside(), 1.33);
+    assert_eq!(glass.exit(), water);
+    assert_eq!(glass.exit().exit(), air);
+}
+
+#[test]
+fn refractive_material_test() {
+    let material = PerfectRefractiveMaterial {
+        index_of_refraction: 1.5,
+    };
+    let normal = Vector3::z();
+    let intersection = Intersection::new(1.0, Point3::origin(), normal, Point2::origin(), 1.0);
+    let incoming = |direction: Vector3<f32>, media: MediumStack| {
+        Ray::new(Point3::origin() - direction, direction, media)
+    };
+
+    // Head on from air, about 4% of light is reflected.
+    let ray = incoming(-Vector3::z(), MediumStack::air());
+    let bsdf = material.bsdf(&ray, &intersection);
+    let wo = -ray.direction;
+    let expected = fresnel_dielectric(1.0, 1.5 / INDEX_OF_REFRACTION_AIR);
+    let reflection = bsdf.sample(&wo, expected / 2.0, (0.5, 0.5)).unwrap();
+    assert_eq!(reflection.wi, wo);
+    assert!((reflection.pdf - expected).abs() < 1e-6);
+    assert!((expected - 0.04).abs() < 1e-3);
+
+    // At a glancing angle, much more is.
+    let grazing = Vector3::new(0.99, 0.0, -(1.0f32 - 0.99 * 0.99).sqrt());
+    let bsdf = material.bsdf(&incoming(grazing, MediumStack::air()), &intersection);
+    let reflection = bsdf.sample(&-grazing, 0.3, (0.5, 0.5)).unwrap();
+    assert!(reflection.wi.z > 0.0 && reflection.pdf > 0.4);
+
+    // Entering from water, light bends by the ratio of the glass to the
+    // water, and enters the glass.
+    let water = MediumStack::air().enter(1.33);
+    let direction = Vector3::new(0.6, 0.0, -0.8);
+    let bsdf = material.bsdf(&incoming(direction, water), &intersection);
+    let sample = bsdf.sample(&-direction, 0.99, (0.5, 0.5)).unwrap();
+    assert!(sample.wi.z < 0.0);
+    assert!((sample.wi.x.abs() * 1.5 - 0.6 * 1.33).abs() < 1e-5);
+    assert_eq!(sample.index_of_refraction, Some(1.5));
+
+    // Leaving the glass, light returns to the water.
+    let direction = Vector3::new(0.3, 0.0, (1.0f32 - 0.09).sqrt());
+    let bsdf = material.bsdf(&incoming(direction, water.enter(1.5)), &intersection);
+    let sample = bsdf.sample(&-direction, 0.99, (0.5, 0.5)).unwrap();
+    assert!(sample.wi.z > 0.0);
+    assert!((sample.wi.x.abs() * 1.33 - 0.3 * 1.5).abs() < 1e-5);
+    assert_eq!(sample.index_of_refraction, Some(1.33));
+
+    // Beyond the critical angle, light can't get out at all.
+    let direction = Vector3::new(0.9, 0.0, (1.0f32 - 0.81).sqrt());
+    let bsdf = material.bsdf(&incoming(direction, water.enter(1.5)), &intersection);
+    for &u_lobe in &[0.0, 0.5, 0.999] {
+        let sample = bsdf.sample(&-direction, u_lobe, (0.5, 0.5)).unwrap();
+        assert!((sample.wi - Vector3::new(0.9, 0.0, -direction.z)).norm() < 1e-6);
+        assert!(sample.wi.iter().all(|c| c.is_finite()));
+        assert_eq!(sample.index_of_refraction, None);
+        assert!((sample.f.x * sample.wi.z.abs() / sample.pdf - 1.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,101 @@
+extern crate nalgebra as na;
+extern crate rand;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use rand::random;
+use renderer::material::{MaterialBox, PerfectSpecularMaterial};
+use renderer::object::Object;
+use renderer::ray::Ray;
+use renderer::scene::Scene;
+use renderer::surface::{Plane, Sphere, Surface, Triangle};
+use std::sync::Arc;
+
+fn random_point(scale: f32) -> Point3<f32> {
+    Point3::new(
+        (random::<f32>() - 0.5) * scale,
+        (random::<f32>() - 0.5) * scale,
+        (random::<f32>() - 0.5) * scale,
+    )
+}
+
+fn object(surface: Box<dyn Surface>) -> Object {
+    Object {
+        surface,
+        material: MaterialBox::Reflective(Arc::new(PerfectSpecularMaterial)),
+    }
+}
+
+// Scattered spheres and triangles, above a plane which can't be bounded.
+fn random_scene() -> Scene {
+    let mut objects = Vec::new();
+    for _ in 0..200 {
+        objects.push(object(Box::new(Sphere {
+            center: random_point(10.0),
+            radius: random::<f32>() * 0.5 + 0.01,
+        })));
+
+        let a = random_point(10.0);
+        objects.push(object(Box::new(Triangle {
+            vertices: [
+                a,
+                a + random_point(1.0).coords,
+                a + random_point(1.0).coords,
+            ],
+        })));
+    }
+    objects.push(object(Box::new(Plane {
+        normal: Vector3::new(0.0, 1.0, 0.0),
+        offset: -4.0,
+    })));
+
+    Scene::new(objects)
+}
+
+#[test]
+fn bvh_matches_brute_force_test() {
+    let scene = random_scene();
+
+    for _ in 0..2000 {
+        let origin = random_point(12.0);
+        let direction = random_point(1.0).coords.normalize();
+        let ray = Ray::new_from_air(origin, direction);
+
+        let expected = scene.intersects_brute_force(&ray);
+        let actual = scene.intersects(&ray);
+
+        match (expected, actual) {
+            (None, None) => {}
+            (Some((expected, _)), Some((actual, _))) => {
+                assert_eq!(expected.distance, actual.distance);
+            }
+            _ => panic!("BVH and brute force disagree on whether the ray hits"),
+        }
+    }
+}
+
+// Shadow rays only need to know whether anything is in the way, which has to
+// agree with finding the closest hit within the same range.
+#[test]
+fn occluded_matches_intersects_test() {
+    let scene = random_scene();
+
+    for _ in 0..2000 {
+        let origin = random_point(12.0);
+        let direction = random_point(1.0).coords.normalize();
+        let ray = Ray {
+            t_max: random::<f32>() * 10.0,
+            ..Ray::new_from_air(origin, direction)
+        };
+
+        assert_eq!(scene.occluded(&ray), scene.intersects(&ray).is_some());
+    }
+}
+
+#[test]
+fn empty_scene_test() {
+    let scene = Scene::new(Vec::new());
+    let ray = Ray::new_from_air(Point3::origin(), Vector3::new(0.0, 0.0, 1.0));
+
+    assert!(scene.intersects(&ray).is_none());
+}
@@ -0,0 +1,55 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use renderer::camera::Camera;
+use std::f32::consts::FRAC_PI_2;
+
+fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+    assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn look_at_test() {
+    // Looking straight down, with +z towards the top of the image.
+    let camera = Camera::look_at(
+        Point3::new(1.0, 5.0, 2.0),
+        Point3::new(1.0, 0.0, 2.0),
+        Vector3::new(0.0, 0.0, 1.0),
+        FRAC_PI_2,
+        1.0,
+    );
+
+    let center = camera.get_ray(0.0, 0.0);
+    assert_eq!(center.origin, Point3::new(1.0, 5.0, 2.0));
+    assert_close(center.direction, Vector3::new(0.0, -1.0, 0.0));
+
+    // A 90 degree field of view puts the edges of the image at 45 degrees.
+    let top = camera.get_ray(0.0, 1.0);
+    assert_close(top.direction, Vector3::new(0.0, -1.0, 1.0).normalize());
+
+    let right = camera.get_ray(1.0, 0.0);
+    assert_close(right.direction, Vector3::new(1.0, -1.0, 0.0).normalize());
+}
+
+#[test]
+fn aspect_ratio_test() {
+    let mut camera = Camera::new(
+        Point3::origin(),
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        FRAC_PI_2,
+        2.0,
+    );
+
+    // A wide image keeps its vertical field of view and sees further to the
+    // sides.
+    let top = camera.get_ray(0.0, 1.0);
+    assert_close(top.direction, Vector3::new(0.0, 1.0, 1.0).normalize());
+    let right = camera.get_ray(1.0, 0.0);
+    assert_close(right.direction, Vector3::new(2.0, 0.0, 1.0).normalize());
+
+    camera.set_aspect_ratio(0.5);
+    let right = camera.get_ray(1.0, 0.0);
+    assert_close(right.direction, Vector3::new(0.5, 0.0, 1.0).normalize());
+}
@@ -0,0 +1,58 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point2, Point3, Vector3};
+use renderer::material::EmissiveMaterial;
+use renderer::spectrum::blackbody;
+use renderer::texture::{ConstantTexture, TexturePoint};
+use renderer::tonemap::luminance;
+
+#[test]
+fn blackbody_test() {
+    for &temperature in &[2700.0, 4000.0, 6500.0, 10_000.0] {
+        let color = blackbody(temperature);
+        assert!((luminance(&color) - 1.0).abs() < 1e-3, "{}", temperature);
+    }
+
+    // Candles and incandescent bulbs are orange, daylight is near white, and a
+    // clear sky is blue.
+    let warm = blackbody(2700.0);
+    assert!(warm.x > warm.y && warm.y > warm.z);
+
+    let daylight = blackbody(6500.0);
+    assert!(
+        daylight.iter().all(|&c| (c - 1.0).abs() < 0.1),
+        "{:?}",
+        daylight
+    );
+
+    let cool = blackbody(10_000.0);
+    assert!(cool.z > cool.y && cool.y > cool.x);
+}
+
+#[test]
+fn emissive_material_test() {
+    let color = Vector3::new(4.0, 2.0, 1.0);
+    let (up, down) = (Vector3::z(), -Vector3::z());
+    let point = TexturePoint {
+        position: Point3::origin(),
+        uv: Point2::new(0.3, 0.6),
+        color: None,
+        width: 0.0,
+    };
+
+    // Lights emit from both sides unless they are one-sided.
+    let light = EmissiveMaterial {
+        emission: Box::new(ConstantTexture { color }),
+        one_sided: false,
+    };
+    assert_eq!(light.emitted(&up, &point, &up), color);
+    assert_eq!(light.emitted(&up, &point, &down), color);
+
+    let light = EmissiveMaterial {
+        emission: Box::new(ConstantTexture { color }),
+        one_sided: true,
+    };
+    assert_eq!(light.emitted(&up, &point, &up), color);
+    assert_eq!(light.emitted(&up, &point, &down), Vector3::zeros());
+}
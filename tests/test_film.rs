@@ -0,0 +1,117 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::Vector3;
+use renderer::film::{Film, Filter, FilterKind};
+use renderer::render::CropWindow;
+
+const KINDS: [FilterKind; 5] = [
+    FilterKind::Box,
+    FilterKind::Tent,
+    FilterKind::Gaussian,
+    FilterKind::Mitchell,
+    FilterKind::Lanczos,
+];
+
+fn window(width: usize, height: usize) -> CropWindow {
+    CropWindow {
+        x0: 0,
+        y0: 0,
+        x1: width,
+        y1: height,
+    }
+}
+
+#[test]
+fn filter_shape_test() {
+    for &kind in &KINDS {
+        let filter = Filter::new(kind);
+        let r = filter.radius;
+
+        assert!(filter.evaluate(0.0, 0.0) > 0.0, "{:?}", kind);
+        assert_eq!(filter.evaluate(r, 0.0), 0.0, "{:?}", kind);
+        assert_eq!(filter.evaluate(0.0, -r), 0.0, "{:?}", kind);
+
+        // Symmetric, and no heavier anywhere than at the center.
+        for i in 0..20 {
+            let x = i as f32 / 20.0 * r;
+            assert_eq!(filter.evaluate(x, 0.3), filter.evaluate(-x, -0.3));
+            assert!(filter.evaluate(x, 0.0) <= filter.evaluate(0.0, 0.0) + 1e-6);
+        }
+    }
+
+    // Mitchell and Lanczos have negative lobes.
+    assert!(Filter::new(FilterKind::Mitchell).evaluate(1.5, 0.0) < 0.0);
+    assert!(Filter::new(FilterKind::Lanczos).evaluate(1.5, 0.0) < 0.0);
+}
+
+#[test]
+fn box_filter_stays_within_pixel_test() {
+    let mut film = Film::new(window(4, 4), Filter::default());
+
+    let mut tile = film.tile(&window(4, 4));
+    for &(u, v) in &[(0.0, 0.0), (0.99, 0.2), (0.5, 0.99)] {
+        tile.add_sample((1.0 + u, 2.0 + v), Vector3::new(1.0, 2.0, 3.0));
+    }
+    film.merge(&tile);
+
+    let image = film.image();
+    for x in 0..4 {
+        for y in 0..4 {
+            let expected = if (x, y) == (1, 2) {
+                Vector3::new(1.0, 2.0, 3.0)
+            } else {
+                Vector3::zeros()
+            };
+            assert_eq!(image[(x, y)], expected);
+        }
+    }
+}
+
+#[test]
+fn tent_filter_splats_neighbors_test() {
+    let filter = Filter {
+        kind: FilterKind::Tent,
+        radius: 1.0,
+    };
+    let mut film = Film::new(window(4, 4), filter);
+
+    // A sample on the corner between four pixels is shared equally by them.
+    let mut tile = film.tile(&CropWindow {
+        x0: 1,
+        y0: 1,
+        x1: 2,
+        y1: 2,
+    });
+    tile.add_sample((2.0, 2.0), Vector3::new(1.0, 1.0, 1.0));
+
+    // A sample at the center of a pixel only affects that pixel.
+    tile.add_sample((0.5, 0.5), Vector3::new(2.0, 2.0, 2.0));
+    film.merge(&tile);
+
+    let image = film.image();
+    for &(x, y) in &[(1, 1), (2, 1), (1, 2), (2, 2)] {
+        assert_eq!(image[(x, y)], Vector3::new(1.0, 1.0, 1.0));
+    }
+    assert_eq!(image[(0, 0)], Vector3::new(2.0, 2.0, 2.0));
+    assert_eq!(image[(3, 3)], Vector3::zeros());
+}
+
+#[test]
+fn weighted_average_test() {
+    let filter = Filter {
+        kind: FilterKind::Tent,
+        radius: 2.0,
+    };
+    let mut film = Film::new(window(3, 1), filter);
+
+    let mut tile = film.tile(&window(3, 1));
+    tile.add_sample((1.5, 0.5), Vector3::new(1.0, 0.0, 0.0));
+    tile.add_sample((2.5, 0.5), Vector3::new(0.0, 1.0, 0.0));
+    film.merge(&tile);
+
+    // Pixel 1 is at the first sample, and one pixel from the second, so the
+    // weights are 1 and 0.5.
+    let pixel = film.image()[(1, 0)];
+    assert!((pixel - Vector3::new(2.0, 1.0, 0.0) / 3.0).norm() < 1e-6);
+}
@@ -0,0 +1,273 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use renderer::bsdf::Bsdf;
+use renderer::import::{gltf, ImportError};
+use renderer::material::MaterialBox;
+use renderer::principled::{PrincipledBsdf, PrincipledParameters};
+use renderer::ray::Ray;
+use renderer::scene::Scene;
+use renderer::texture::TexturePoint;
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+// The same file as tests/data/scene.gltf in binary form, with its buffer in
+// the binary chunk rather than a separate file.
+fn binary_scene() -> Vec<u8> {
+    let json = fs::read_to_string("tests/data/scene.gltf")
+        .unwrap()
+        .replace("\"uri\": \"quad%20data.bin\", ", "");
+    let mut json = json.into_bytes();
+    json.resize(json.len().div_ceil(4) * 4, b' ');
+    let mut buffer = fs::read("tests/data/quad data.bin").unwrap();
+    buffer.resize(buffer.len().div_ceil(4) * 4, 0);
+
+    let length = 12 + 8 + json.len() + 8 + buffer.len();
+    let mut contents = b"glTF".to_vec();
+    contents.extend_from_slice(&2u32.to_le_bytes());
+    contents.extend_from_slice(&(length as u32).to_le_bytes());
+    contents.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    contents.extend_from_slice(b"JSON");
+    contents.extend_from_slice(&json);
+    contents.extend_from_slice(&(buffer.len() as u32).to_le_bytes());
+    contents.extend_from_slice(b"BIN\0");
+    contents.extend_from_slice(&buffer);
+
+    contents
+}
+
+#[test]
+fn load_test() {
+    let imported = gltf::load(Path::new("tests/data/scene.gltf")).unwrap();
+
+    // The quad, the glowing quad and the point light. The hidden node and the
+    // primitive made of lines are left out.
+    assert_eq!(imported.objects.len(), 3);
+    assert_eq!(
+        imported.warnings,
+        &["mesh 2 primitive 0 isn't made of triangles"]
+    );
+
+    // The camera looks down -z, which is +z with the z axis flipped, and the
+    // quad in front of it faces it.
+    let camera = imported.camera.unwrap();
+    assert_eq!(camera.aspect_ratio, Some(1.5));
+    assert_eq!(camera.camera.position(), Point3::origin());
+    assert!((camera.camera.direction() - Vector3::z()).norm() < 1e-6);
+    assert!(camera.camera.get_ray(1.0, 0.0).direction.x > 0.0);
+
+    let scene = Scene::new(imported.objects);
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.75, 0.0), Vector3::z());
+    let (hit, _) = scene.intersects(&ray).unwrap();
+    assert!((hit.distance - 2.0).abs() < 1e-6);
+    assert!((hit.normal + Vector3::z()).norm() < 1e-6);
+
+    // Transforms of parent nodes apply to their children.
+    let ray = Ray::new_from_air(Point3::new(2.5, 0.5, 0.0), Vector3::z());
+    let (hit, _) = scene.intersects(&ray).unwrap();
+    assert!((hit.distance - 4.0).abs() < 1e-6);
+}
+
+#[test]
+fn zero_normal_test() {
+    // Reading the normals from the positions instead makes the first one zero,
+    // so the quad is shaded flat.
+    let contents = fs::read_to_string("tests/data/scene.gltf")
+        .unwrap()
+        .replace(
+            "{\"bufferView\": 0, \"byteOffset\": 48,",
+            "{\"bufferView\": 0, \"byteOffset\": 0,",
+        );
+    let imported = gltf::parse(contents.as_bytes(), Path::new("tests/data")).unwrap();
+
+    let scene = Scene::new(imported.objects);
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.75, 0.0), Vector3::z());
+    let (hit, _) = scene.intersects(&ray).unwrap();
+    assert!((hit.normal + Vector3::z()).norm() < 1e-6);
+}
+
+#[test]
+fn spot_light_test() {
+    // Spot lights become point lights, rather than being left out.
+    let contents = fs::read_to_string("tests/data/scene.gltf")
+        .unwrap()
+        .replace(
+            "{\"type\": \"point\",",
+            "{\"type\": \"spot\", \"spot\": {\"outerConeAngle\": 0.5},",
+        );
+    let imported = gltf::parse(contents.as_bytes(), Path::new("tests/data")).unwrap();
+    assert_eq!(imported.objects.len(), 3);
+    assert!(imported
+        .warnings
+        .contains(&"spot light 0 is rendered as a point light".to_string()));
+
+    let scene = Scene::new(imported.objects);
+    let ray = Ray::new_from_air(Point3::new(0.0, 1.0, 3.0), -Vector3::z());
+    let (_, object) = scene.intersects(&ray).unwrap();
+    assert!(object.is_light());
+}
+
+#[test]
+fn load_binary_test() {
+    let text = gltf::load(Path::new("tests/data/scene.gltf")).unwrap();
+    let binary = gltf::parse(&binary_scene(), Path::new("")).unwrap();
+    assert_eq!(binary.objects.len(), text.objects.len());
+
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.75, 0.0), Vector3::z());
+    let (text_hit, _) = Scene::new(text.objects).intersects(&ray).unwrap();
+    let (binary_hit, _) = Scene::new(binary.objects).intersects(&ray).unwrap();
+    assert_eq!(binary_hit.position, text_hit.position);
+    assert_eq!(binary_hit.uv, text_hit.uv);
+}
+
+#[test]
+fn nan_vertex_test() {
+    // Make the y coordinate of the second vertex, in the buffer at the end of
+    // the binary file, NaN.
+    let mut contents = binary_scene();
+    let buffer = contents.len() - fs::read("tests/data/quad data.bin").unwrap().len();
+    contents[buffer + 16..buffer + 20].copy_from_slice(&f32::NAN.to_le_bytes());
+
+    match gltf::parse(&contents, Path::new("")) {
+        Err(ImportError::Gltf(message)) => {
+            assert_eq!(
+                message,
+                "mesh 0 primitive 0 vertex 1: position must be finite"
+            );
+        }
+        _ => panic!("expected an error for a NaN vertex"),
+    }
+}
+
+#[test]
+fn materials_test() {
+    let scene = Scene::new(
+        gltf::load(Path::new("tests/data/scene.gltf"))
+            .unwrap()
+            .objects,
+    );
+
+    // The base color is the left half of the texture, which is red, times the
+    // base color factor.
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.75, 0.0), Vector3::z());
+    let (hit, object) = scene.intersects(&ray).unwrap();
+    let expected = PrincipledBsdf::new(
+        &PrincipledParameters {
+            base_color: Vector3::new(0.5, 0.0, 0.0),
+            metallic: 0.2,
+            roughness: 0.7,
+            specular: (0.4f32 / 2.4).powi(2) / 0.08,
+            index_of_refraction: 1.4,
+            ..Default::default()
+        },
+        1.0,
+    );
+    match object.material {
+        MaterialBox::Reflective(ref material) => {
+            let bsdf = material.bsdf(&ray, &hit);
+            let wo = Vector3::new(0.3, 0.0, 1.0).normalize();
+            let wi = Vector3::new(-0.5, 0.2, 1.0).normalize();
+            assert!((bsdf.eval(&wo, &wi) - expected.eval(&wo, &wi)).norm() < 1e-5);
+        }
+        _ => panic!("expected a reflective material"),
+    }
+
+    // The emissive color is scaled by its strength, and only emitted from the
+    // front of surfaces which aren't double sided.
+    let ray = Ray::new_from_air(Point3::new(2.5, 0.5, 0.0), Vector3::z());
+    let (hit, object) = scene.intersects(&ray).unwrap();
+    match object.material {
+        MaterialBox::Emissive(ref material) => {
+            let point = TexturePoint::new(&hit);
+            let front = material.emitted(&hit.normal, &point, &-Vector3::z());
+            assert_eq!(front, Vector3::new(4.0, 2.0, 1.0));
+            assert_eq!(
+                material.emitted(&hit.normal, &point, &Vector3::z()),
+                Vector3::zeros()
+            );
+        }
+        _ => panic!("expected an emissive material"),
+    }
+
+    // The point light is a small sphere, whose radiance gives the light's
+    // intensity of 683 candela, or 1 watt per steradian.
+    let ray = Ray::new_from_air(Point3::new(0.0, 1.0, 3.0), -Vector3::z());
+    let (hit, object) = scene.intersects(&ray).unwrap();
+    assert!(hit.distance < 3.0);
+    match object.material {
+        MaterialBox::Emissive(ref material) => {
+            let radius = 3.0 - hit.distance;
+            let radiance = material.emitted(&hit.normal, &TexturePoint::new(&hit), &Vector3::z());
+            assert!((radiance.x * PI * radius * radius - 1.0).abs() < 1e-3);
+        }
+        _ => panic!("expected an emissive material"),
+    }
+}
+
+#[test]
+fn image_out_of_bounds_test() {
+    // An image in a buffer view which runs past the end of its buffer is
+    // skipped, leaving the material without a texture.
+    let scene = fs::read_to_string("tests/data/scene.gltf").unwrap();
+    let start = scene.find("{\"uri\": \"data:image/png").unwrap();
+    let end = start + scene[start..].find('}').unwrap() + 1;
+    let contents = format!(
+        "{}{{\"bufferView\": 3, \"mimeType\": \"image/png\"}}{}",
+        &scene[..start],
+        &scene[end..]
+    )
+    .replace(
+        "\"byteOffset\": 128, \"byteLength\": 12}",
+        "\"byteOffset\": 128, \"byteLength\": 12},\n    \
+         {\"buffer\": 0, \"byteOffset\": 130, \"byteLength\": 100}",
+    );
+
+    let imported = gltf::parse(contents.as_bytes(), Path::new("tests/data")).unwrap();
+    assert_eq!(imported.objects.len(), 3);
+}
+
+#[test]
+fn malformed_test() {
+    let scene = fs::read_to_string("tests/data/scene.gltf").unwrap();
+    let cases = [
+        ("not glTF".to_string(), None),
+        (
+            "{\"asset\": {\"version\": \"2.0\"}}".to_string(),
+            Some("the file has no scenes"),
+        ),
+        (
+            scene.replace(
+                "\"count\": 4, \"type\": \"VEC3\", \"min\"",
+                "\"count\": 3, \"type\": \"VEC3\", \"min\"",
+            ),
+            Some("mesh 0 primitive 0 refers to vertex 3, but there are only 3"),
+        ),
+        (
+            scene.replace(
+                "\"count\": 4, \"type\": \"VEC2\"",
+                "\"count\": 2, \"type\": \"VEC2\"",
+            ),
+            Some("mesh 0 primitive 0 has 2 texture coordinates for 4 vertices"),
+        ),
+        (
+            scene.replace(
+                "quad%20data.bin",
+                "data:application/octet-stream;base64,AAAA",
+            ),
+            Some("buffer 0 is shorter than its length of 140 bytes"),
+        ),
+    ];
+
+    for &(ref contents, expected) in &cases {
+        match gltf::parse(contents.as_bytes(), Path::new("tests/data")) {
+            Err(ImportError::Gltf(message)) => {
+                if let Some(expected) = expected {
+                    assert_eq!(message, expected);
+                }
+            }
+            _ => panic!("expected an error for {:?}", contents),
+        }
+    }
+}
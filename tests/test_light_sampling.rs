@@ -0,0 +1,121 @@
+extern crate nalgebra as na;
+extern crate rand;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use rand::random;
+use renderer::mesh::TriangleMesh;
+use renderer::surface::{Sphere, Surface, Triangle};
+use renderer::transform::{Transform, TransformedSurface};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// Check that the density reported with each sample matches `pdf_towards`, and
+// that the samples cover the expected solid angle.
+fn check_sampling(surface: &dyn Surface, reference: Point3<f32>, solid_angle: f32) {
+    let count = 20000;
+    let mut estimate = 0.0;
+
+    for _ in 0..count {
+        let sample = surface
+            .sample_towards(&reference, (random(), random()))
+            .unwrap();
+        let direction = (sample.position - reference).normalize();
+
+        let pdf = surface.pdf_towards(&reference, &direction);
+        assert!((pdf - sample.pdf).abs() <= 1e-2 * sample.pdf);
+
+        estimate += 1.0 / sample.pdf / count as f32;
+    }
+
+    assert!((estimate - solid_angle).abs() < 0.02 * solid_angle);
+}
+
+#[test]
+fn sphere_sampling_test() {
+    let sphere = Sphere {
+        center: Point3::new(0.0, 0.0, 3.0),
+        radius: 1.0,
+    };
+
+    let cos_theta_max = (1.0f32 - 1.0 / 9.0).sqrt();
+    check_sampling(&sphere, Point3::origin(), 2.0 * PI * (1.0 - cos_theta_max));
+}
+
+#[test]
+fn triangle_sampling_test() {
+    // A right triangle filling one eighth of the sphere of directions.
+    let triangle = Triangle {
+        vertices: [
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ],
+    };
+
+    check_sampling(&triangle, Point3::origin(), 4.0 * PI / 8.0);
+}
+
+#[test]
+fn mesh_sampling_test() {
+    // Two triangles covering a quarter of the sphere of directions.
+    let mesh = TriangleMesh::new(
+        vec![
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, -1.0, 0.0),
+        ],
+        Vec::new(),
+        Vec::new(),
+        vec![[0, 1, 2], [0, 2, 3]],
+    );
+
+    check_sampling(&mesh, Point3::origin(), 4.0 * PI / 4.0);
+
+    // Samples should never miss the mesh.
+    let sample = mesh
+        .sample_towards(&Point3::origin(), (0.999, 0.5))
+        .unwrap();
+    assert!(mesh.pdf_towards(&Point3::origin(), &sample.position.coords.normalize()) > 0.0);
+}
+
+#[test]
+fn transformed_sampling_test() {
+    // Stretched, turned and moved, a triangle still fills one eighth of the
+    // sphere of directions if it ends up at the same place.
+    let transform = Transform::scaling(&Vector3::new(0.5, 2.0, 1.5))
+        .then(&Transform::rotation(&Vector3::new(1.0, 2.0, 3.0), 0.7))
+        .then(&Transform::translation(&Vector3::new(-1.0, 4.0, 2.0)));
+    let reference = Point3::new(-2.0, 1.0, 3.0);
+    let inverse = transform.inverse();
+    let corner = |offset: Vector3<f32>| inverse.point(&(reference + offset));
+    let triangle = Arc::new(Triangle {
+        vertices: [
+            corner(Vector3::x()),
+            corner(Vector3::y()),
+            corner(Vector3::z()),
+        ],
+    });
+
+    check_sampling(
+        &TransformedSurface::new(triangle, transform),
+        reference,
+        4.0 * PI / 8.0,
+    );
+
+    // A sphere scaled up evenly covers more of the view.
+    let sphere = Arc::new(Sphere {
+        center: Point3::origin(),
+        radius: 0.5,
+    });
+    let transform = Transform::scaling(&Vector3::repeat(2.0))
+        .then(&Transform::translation(&Vector3::new(0.0, 0.0, 3.0)));
+
+    let cos_theta_max = (1.0f32 - 1.0 / 9.0).sqrt();
+    check_sampling(
+        &TransformedSurface::new(sphere, transform),
+        Point3::origin(),
+        2.0 * PI * (1.0 - cos_theta_max),
+    );
+}
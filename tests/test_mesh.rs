@@ -0,0 +1,166 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point2, Point3, Vector3};
+use renderer::import::{obj, ImportError};
+use renderer::material::MaterialBox;
+use renderer::mesh::TriangleMesh;
+use renderer::ray::Ray;
+use renderer::surface::Surface;
+use renderer::texture::TexturePoint;
+use std::f32::consts::PI;
+use std::path::Path;
+
+#[test]
+fn load_obj_test() {
+    let meshes = obj::load(Path::new("tests/data/cube.obj")).unwrap();
+    assert_eq!(meshes.len(), 2);
+
+    let cube = &meshes[0];
+    assert_eq!(cube.name, "cube");
+    assert_eq!(cube.mesh.indices().len(), 12);
+    assert!(cube.mesh.normals().is_empty());
+    match cube.material {
+        Some(MaterialBox::Reflective(ref material)) => {
+            let ray = Ray::new_from_air(Point3::new(0.0, 0.0, 1.0), -Vector3::z());
+            let hit = cube.mesh.intersects(&ray).unwrap();
+
+            // A diffuse BSDF scatters color / π in every direction.
+            let up = Vector3::z();
+            let f = material.bsdf(&ray, &hit).eval(&up, &up);
+            assert!((f * PI - Vector3::new(0.8, 0.1, 0.1)).norm() < 1e-6);
+        }
+        _ => panic!("expected a diffuse material"),
+    }
+
+    let light = &meshes[1];
+    assert_eq!(light.mesh.indices().len(), 2);
+    assert_eq!(light.mesh.normals().len(), light.mesh.positions().len());
+    assert_eq!(light.mesh.uvs().len(), light.mesh.positions().len());
+    match light.material {
+        Some(MaterialBox::Emissive(ref material)) => {
+            let point = TexturePoint {
+                position: Point3::origin(),
+                uv: Point2::origin(),
+                color: None,
+                width: 0.0,
+            };
+            let emitted = material.emitted(&Vector3::z(), &point, &Vector3::z());
+            assert_eq!(emitted, Vector3::new(4.0, 3.5, 3.0));
+        }
+        _ => panic!("expected an emissive material"),
+    }
+}
+
+#[test]
+fn zero_normal_test() {
+    // A normal that can't be normalized leaves the mesh with flat shading,
+    // rather than shading with NaN.
+    let meshes = obj::load(Path::new("tests/data/zero_normal.obj")).unwrap();
+    assert_eq!(
+        meshes[0].warnings,
+        &["triangle has invalid vertex normals, which are ignored"]
+    );
+    let triangle = &meshes[0].mesh;
+    assert!(triangle.normals().is_empty());
+
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.25, 1.0), -Vector3::z());
+    let hit = triangle.intersects(&ray).unwrap();
+    assert_eq!(hit.normal, Vector3::z());
+}
+
+#[test]
+fn nan_vertex_test() {
+    match obj::load(Path::new("tests/data/nan_vertex.obj")) {
+        Err(ImportError::Obj(err)) => assert_eq!(err.to_string(), "position parse error"),
+        _ => panic!("expected an error for a NaN vertex"),
+    }
+}
+
+#[test]
+fn mesh_intersection_test() {
+    let meshes = obj::load(Path::new("tests/data/cube.obj")).unwrap();
+    let cube = &meshes[0].mesh;
+
+    let bounds = cube.bounds().unwrap();
+    assert_eq!(bounds.min, Point3::new(-0.5, -0.5, -0.5));
+    assert_eq!(bounds.max, Point3::new(0.5, 0.5, 0.5));
+
+    // Hit the front face, which is wound to face away from the cube.
+    let ray = Ray::new_from_air(Point3::new(0.1, 0.2, -2.0), Vector3::new(0.0, 0.0, 1.0));
+    let intersection = cube.intersects(&ray).unwrap();
+    assert_eq!(intersection.distance, 1.5);
+    assert_eq!(intersection.normal, Vector3::new(0.0, 0.0, -1.0));
+
+    let ray = Ray::new_from_air(Point3::new(2.0, 2.0, -2.0), Vector3::new(0.0, 0.0, 1.0));
+    assert!(cube.intersects(&ray).is_none());
+
+    // The light has vertex normals, which should be used for shading.
+    let light = &meshes[1].mesh;
+    let ray = Ray::new_from_air(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+    let intersection = light.intersects(&ray).unwrap();
+    assert_eq!(intersection.normal, Vector3::new(0.0, -1.0, 0.0));
+
+    // And texture coordinates, which are interpolated across each triangle.
+    let ray = Ray::new_from_air(Point3::new(0.1, 0.0, -0.2), Vector3::new(0.0, 1.0, 0.0));
+    let intersection = light.intersects(&ray).unwrap();
+    assert!((intersection.uv - Point2::new(0.7, 0.1)).norm() < 1e-6);
+}
+
+// A single triangle wound to face down, with vertex normals which lean
+// outwards from its middle and point up.
+fn smooth_triangle() -> TriangleMesh {
+    TriangleMesh::new(
+        vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+        ],
+        vec![
+            Vector3::new(-1.0, -1.0, 1.0).normalize(),
+            Vector3::new(-1.0, 1.0, 1.0).normalize(),
+            Vector3::new(1.0, -1.0, 1.0).normalize(),
+        ],
+        Vec::new(),
+        vec![[0, 1, 2]],
+    )
+}
+
+#[test]
+fn smooth_shading_test() {
+    let mesh = smooth_triangle();
+    let ray = Ray::new_from_air(Point3::new(0.25, 0.5, 1.0), -Vector3::z());
+    let hit = mesh.intersects(&ray).unwrap();
+
+    // The vertex normals are interpolated by the barycentric coordinates of
+    // the hit, which weight the second and third vertices.
+    assert_eq!(hit.barycentric, Some(Point2::new(0.5, 0.25)));
+    let expected = Vector3::new(-1.0, -1.0, 1.0).normalize() * 0.25
+        + Vector3::new(-1.0, 1.0, 1.0).normalize() * 0.5
+        + Vector3::new(1.0, -1.0, 1.0).normalize() * 0.25;
+    assert!((hit.normal - expected.normalize()).norm() < 1e-6);
+
+    // The surface itself faces the same way as the vertex normals, even
+    // though the triangle is wound the other way.
+    assert_eq!(hit.geometric_normal, Vector3::z());
+}
+
+#[test]
+fn consistent_normals_test() {
+    let mesh = smooth_triangle();
+    let ray = Ray::new_from_air(Point3::new(0.1, 0.1, 1.0), -Vector3::z());
+    let hit = mesh.intersects(&ray).unwrap();
+    let wo = Vector3::z();
+
+    // Near the first vertex, the shading normal leans towards -x and -y. Both
+    // normals agree on light reflecting towards it, or passing straight
+    // through the surface.
+    assert!(hit.consistent(&wo, &Vector3::new(-1.0, -1.0, 1.0).normalize()));
+    assert!(hit.consistent(&wo, &-Vector3::z()));
+
+    // Grazing directions above the surface that the shading normal puts
+    // below it, and the other way around, are inconsistent.
+    let grazing = Vector3::new(1.0, 1.0, 0.1).normalize();
+    assert!(!hit.consistent(&wo, &grazing));
+    assert!(!hit.consistent(&wo, &Vector3::new(-1.0, -1.0, -0.1).normalize()));
+}
@@ -0,0 +1,266 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+#[path = "lib.rs"]
+mod common;
+
+use common::integrate_sphere;
+use na::Vector3;
+use renderer::bsdf::Bsdf;
+use renderer::microfacet::{
+    fresnel_conductor, fresnel_dielectric, fresnel_schlick, DielectricBsdf, DistributionKind,
+    Fresnel, MicrofacetDistribution, MicrofacetReflectionBsdf,
+};
+use renderer::sampler::{Sampler, UniformSampler};
+
+fn distributions() -> Vec<MicrofacetDistribution> {
+    let mut distributions = Vec::new();
+    for &kind in &[DistributionKind::Ggx, DistributionKind::Beckmann] {
+        distributions.push(MicrofacetDistribution::new(kind, 0.3, 0.3));
+        distributions.push(MicrofacetDistribution::new(kind, 0.15, 0.5));
+    }
+    distributions
+}
+
+#[test]
+fn distribution_normalization_test() {
+    let wo = Vector3::new(0.6, 0.3, 0.5).normalize();
+
+    for distribution in distributions() {
+        // The projected area of the microfacets is the area of the surface.
+        let projected = integrate_sphere(|wm| {
+            if wm.z > 0.0 {
+                distribution.d(wm) * wm.z
+            } else {
+                0.0
+            }
+        });
+        assert!((projected - 1.0).abs() < 0.01, "{:?}", distribution);
+
+        // Seen from any direction, the visible normals make up the projected
+        // area of the surface.
+        let visible = integrate_sphere(|wm| {
+            if wm.z > 0.0 {
+                distribution.visible_d(&wo, wm)
+            } else {
+                0.0
+            }
+        });
+        assert!((visible - 1.0).abs() < 0.01, "{:?}", distribution);
+    }
+}
+
+#[test]
+fn visible_normal_sampling_test() {
+    let mut sampler = UniformSampler::new(5);
+    let regions: [fn(&Vector3<f32>) -> bool; 3] = [
+        |wm| wm.z > 0.96,
+        |wm| wm.x > 0.2,
+        |wm| wm.y < -0.1 && wm.x < 0.0,
+    ];
+
+    for distribution in distributions() {
+        for &wo in &[
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.8, 0.1, 0.3).normalize(),
+            Vector3::new(-0.3, 0.9, -0.2).normalize(),
+        ] {
+            let normals: Vec<_> = (0..20_000)
+                .map(|_| distribution.sample_wm(&wo, sampler.get_2d()))
+                .collect();
+            assert!(normals.iter().all(|wm| wm.z > 0.0));
+
+            // The fraction of sampled normals in each region matches the
+            // visible density.
+            for region in &regions {
+                let fraction =
+                    normals.iter().filter(|wm| region(wm)).count() as f32 / normals.len() as f32;
+                let expected = integrate_sphere(|wm| {
+                    if wm.z > 0.0 && region(wm) {
+                        distribution.visible_d(&wo, wm)
+                    } else {
+                        0.0
+                    }
+                });
+
+                assert!(
+                    (fraction - expected).abs() < 0.015,
+                    "{:?} from {:?}: {} sampled, {} expected",
+                    distribution,
+                    wo,
+                    fraction,
+                    expected
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn fresnel_test() {
+    // At normal incidence, glass reflects 4% of light.
+    assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-6);
+    assert!((fresnel_dielectric(-1.0, 1.5) - 0.04).abs() < 1e-6);
+
+    // Everything is reflected at grazing angles, and beyond the critical
+    // angle from inside.
+    assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-6);
+    assert_eq!(fresnel_dielectric(-0.5, 1.5), 1.0);
+
+    // A conductor with no extinction at normal incidence agrees with a
+    // dielectric, and the general formula holds with it.
+    let eta = Vector3::new(1.5, 0.2, 1.0);
+    let k = Vector3::new(0.0, 3.0, 1.0);
+    let f = fresnel_conductor(1.0, &eta, &k);
+    for c in 0..3 {
+        let expected =
+            ((eta[c] - 1.0).powi(2) + k[c] * k[c]) / ((eta[c] + 1.0).powi(2) + k[c] * k[c]);
+        assert!((f[c] - expected).abs() < 1e-5);
+    }
+    assert!(
+        (fresnel_conductor(0.5, &eta, &Vector3::zeros()).x - fresnel_dielectric(0.5, 1.5)).abs()
+            < 1e-5
+    );
+
+    // Schlick's approximation is exact at normal and grazing incidence.
+    let f0 = Vector3::new(0.04, 0.5, 1.0);
+    assert_eq!(fresnel_schlick(1.0, &f0), f0);
+    assert_eq!(fresnel_schlick(0.0, &f0), Vector3::repeat(1.0));
+}
+
+#[test]
+fn conductor_test() {
+    let mut sampler = UniformSampler::new(7);
+    let gold = Fresnel::Conductor {
+        eta: Vector3::new(0.2, 0.9, 1.1),
+        k: Vector3::new(3.9, 2.4, 1.6),
+    };
+
+    for distribution in distributions() {
+        let bsdf = MicrofacetReflectionBsdf {
+            distribution,
+            fresnel: gold,
+        };
+        assert!(!bsdf.is_specular());
+
+        for &wo in &[
+            Vector3::new(0.0, 0.6, 0.8),
+            Vector3::new(0.9, 0.1, -0.3).normalize(),
+        ] {
+            // Every sample is on the viewer's side, and agrees with eval and
+            // pdf.
+            let mut estimate = Vector3::zeros();
+            for _ in 0..10_000 {
+                let u_lobe = sampler.get_1d();
+                if let Some(sample) = bsdf.sample(&wo, u_lobe, sampler.get_2d()) {
+                    assert!(sample.wi.z * wo.z > 0.0);
+                    assert!(!sample.specular);
+                    assert!((sample.pdf - bsdf.pdf(&wo, &sample.wi)).abs() < 1e-3 * sample.pdf);
+                    assert!((sample.f - bsdf.eval(&wo, &sample.wi)).norm() < 1e-5);
+
+                    estimate += sample.f * (sample.wi.z.abs() / sample.pdf) / 10_000.0;
+                }
+            }
+
+            // Some light is lost to masking and absorption, but no more than
+            // arrives is reflected.
+            assert!(
+                estimate.iter().all(|&c| c > 0.2 && c <= 1.0),
+                "{:?}",
+                estimate
+            );
+
+            let total = integrate_sphere(|wi| bsdf.pdf(&wo, wi));
+            assert!(total > 0.85 && total < 1.01, "{}", total);
+        }
+    }
+
+    // A polished conductor is a mirror tinted by the Fresnel equations.
+    let bsdf = MicrofacetReflectionBsdf {
+        distribution: MicrofacetDistribution::new(DistributionKind::Ggx, 0.0, 0.0),
+        fresnel: gold,
+    };
+    let wo = Vector3::new(0.6, 0.0, 0.8);
+    let sample = bsdf.sample(&wo, 0.5, (0.5, 0.5)).unwrap();
+    assert!(sample.specular && bsdf.is_specular());
+    assert_eq!(sample.wi, Vector3::new(-0.6, 0.0, 0.8));
+    let expected = gold.evaluate(0.8);
+    assert!((sample.f * sample.wi.z / sample.pdf - expected).norm() < 1e-6);
+}
+
+#[test]
+fn rough_dielectric_test() {
+    let mut sampler = UniformSampler::new(11);
+
+    for distribution in distributions() {
+        let bsdf = DielectricBsdf {
+            distribution,
+            outside: 1.0,
+            inside: 1.5,
+        };
+
+        for &wo in &[
+            Vector3::new(0.0, 0.6, 0.8),
+            Vector3::new(0.3, 0.1, -0.9).normalize(),
+        ] {
+            let (mut reflected, mut transmitted) = (0, 0);
+            for _ in 0..10_000 {
+                let u_lobe = sampler.get_1d();
+                if let Some(sample) = bsdf.sample(&wo, u_lobe, sampler.get_2d()) {
+                    assert!(!sample.specular);
+                    assert!((sample.pdf - bsdf.pdf(&wo, &sample.wi)).abs() < 1e-3 * sample.pdf);
+                    assert!(
+                        (sample.f - bsdf.eval(&wo, &sample.wi)).norm() < 1e-3 * sample.f.norm()
+                    );
+
+                    // Transmitted light continues in the medium on the other
+                    // side.
+                    if sample.wi.z * wo.z > 0.0 {
+                        reflected += 1;
+                        assert_eq!(sample.index_of_refraction, None);
+                    } else {
+                        transmitted += 1;
+                        let far = if wo.z > 0.0 { 1.5 } else { 1.0 };
+                        assert_eq!(sample.index_of_refraction, Some(far));
+                    }
+                }
+            }
+            assert!(reflected > 0 && transmitted > reflected);
+
+            let total = integrate_sphere(|wi| bsdf.pdf(&wo, wi));
+            assert!(total > 0.8 && total < 1.01, "{}", total);
+        }
+
+        // Light entering the glass isn't amplified.
+        let wo = Vector3::new(0.0, 0.6, 0.8);
+        let albedo = integrate_sphere(|wi| bsdf.eval(&wo, wi).x * wi.z.abs());
+        assert!(albedo > 0.3 && albedo < 1.0, "{}", albedo);
+    }
+}
+
+#[test]
+fn smooth_dielectric_test() {
+    let bsdf = DielectricBsdf {
+        distribution: MicrofacetDistribution::new(DistributionKind::Ggx, 0.0, 0.0),
+        outside: 1.0,
+        inside: 1.5,
+    };
+    assert!(bsdf.is_specular());
+
+    // Head on, 4% of light is reflected and the rest passes straight through.
+    let wo = Vector3::z();
+    let reflection = bsdf.sample(&wo, 0.01, (0.5, 0.5)).unwrap();
+    assert_eq!(reflection.wi, wo);
+    assert!((reflection.pdf - 0.04).abs() < 1e-6);
+
+    let transmission = bsdf.sample(&wo, 0.5, (0.5, 0.5)).unwrap();
+    assert!((transmission.wi + wo).norm() < 1e-6);
+    assert!((transmission.pdf - 0.96).abs() < 1e-6);
+    assert_eq!(transmission.index_of_refraction, Some(1.5));
+
+    // Leaving the glass beyond the critical angle, everything is reflected.
+    let wo = Vector3::new(0.9, 0.0, -(1.0f32 - 0.81).sqrt());
+    let sample = bsdf.sample(&wo, 0.99, (0.5, 0.5)).unwrap();
+    assert!((sample.wi - Vector3::new(-wo.x, 0.0, wo.z)).norm() < 1e-6);
+    assert_eq!(sample.index_of_refraction, None);
+}
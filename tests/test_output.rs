@@ -0,0 +1,78 @@
+extern crate exr;
+extern crate image;
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{DMatrix, Vector3};
+use renderer::output::{self, OutputFormat};
+use renderer::tonemap::ToneMapping;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("renderer-test-{}-{}", std::process::id(), name))
+}
+
+// A 3x2 screen with values well outside of [0, 1].
+fn screen() -> DMatrix<Vector3<f32>> {
+    DMatrix::from_fn(3, 2, |x, y| {
+        Vector3::new(x as f32 * 10.0 + 0.5, y as f32 * 100.0 + 0.25, 0.125)
+    })
+}
+
+#[test]
+fn format_from_path_test() {
+    let format = |name: &str| OutputFormat::from_path(&PathBuf::from(name));
+
+    assert_eq!(format("a.exr"), Some(OutputFormat::Exr));
+    assert_eq!(format("a.HDR"), Some(OutputFormat::Hdr));
+    assert_eq!(format("a.jpg"), Some(OutputFormat::Jpeg));
+    assert_eq!(format("a.txt"), None);
+    assert!(OutputFormat::Exr.is_hdr() && !OutputFormat::Png.is_hdr());
+}
+
+#[test]
+fn write_exr_test() {
+    let path = temp_path("out.exr");
+    let screen = screen();
+    output::write_image(&path, &screen, OutputFormat::Exr, &ToneMapping::default()).unwrap();
+
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        &path,
+        |size, _| DMatrix::from_element(size.0, size.1, Vector3::zeros()),
+        |pixels: &mut DMatrix<Vector3<f32>>, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+            pixels[(position.0, position.1)] = Vector3::new(r, g, b);
+        },
+    )
+    .unwrap();
+
+    // EXR stores 32-bit floats, so the screen comes back exactly.
+    assert_eq!(image.layer_data.channel_data.pixels, screen);
+}
+
+#[test]
+fn write_hdr_test() {
+    let path = temp_path("out.hdr");
+    let screen = screen();
+    output::write_image(&path, &screen, OutputFormat::Hdr, &ToneMapping::default()).unwrap();
+
+    let decoder = image::hdr::HdrDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+    let pixels = decoder.read_image_hdr().unwrap();
+    assert_eq!(pixels.len(), 6);
+
+    // RGBE shares an exponent between channels, so only the largest channel
+    // of each pixel keeps full precision.
+    for y in 0..2 {
+        for x in 0..3 {
+            let expected = screen[(x, y)];
+            let actual = pixels[y * 3 + x];
+            let max = expected.max();
+
+            for c in 0..3 {
+                assert!((actual[c] - expected[c]).abs() <= max / 128.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point2, Point3, Vector3};
+use renderer::import::{ply, ImportError};
+use renderer::material::MaterialBox;
+use renderer::ray::Ray;
+use renderer::surface::Surface;
+use renderer::texture::{Texture, TexturePoint, VertexColorTexture};
+use std::path::Path;
+
+const HEADER: &str = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+// A binary file with the same quad as tests/data/quad.ply, without normals or
+// texture coordinates, but with an element and a vertex property that aren't
+// used.
+fn binary_quad(big_endian: bool) -> Vec<u8> {
+    let format = if big_endian {
+        "binary_big_endian"
+    } else {
+        "binary_little_endian"
+    };
+    let mut contents = format!(
+        "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+         property float z\nproperty short flags\nproperty uchar red\nproperty uchar green\n\
+         property uchar blue\nelement face 1\nproperty list uchar uint vertex_indices\n\
+         element edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n",
+        format
+    )
+    .into_bytes();
+
+    let float = |value: f32| {
+        if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    };
+    let int = |value: u32| {
+        if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    };
+
+    let corners = [
+        ([0.0, 0.0], [255, 0, 0]),
+        ([1.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0], [0, 0, 255]),
+        ([0.0, 1.0], [255, 255, 255]),
+    ];
+    for &([x, y], color) in &corners {
+        contents.extend_from_slice(&float(x));
+        contents.extend_from_slice(&float(y));
+        contents.extend_from_slice(&float(0.0));
+        contents.extend_from_slice(&[0, 7]);
+        contents.extend_from_slice(&color);
+    }
+
+    contents.push(4);
+    for i in 0..4 {
+        contents.extend_from_slice(&int(i));
+    }
+    contents.extend_from_slice(&int(0));
+    contents.extend_from_slice(&int(1));
+
+    contents
+}
+
+#[test]
+fn load_ascii_test() {
+    let meshes = ply::load(Path::new("tests/data/quad.ply")).unwrap();
+    assert_eq!(meshes.len(), 1);
+
+    let quad = &meshes[0];
+    assert_eq!(quad.name, "quad");
+    assert_eq!(quad.mesh.positions().len(), 4);
+    assert_eq!(quad.mesh.indices(), &[[0, 1, 2], [0, 2, 3]]);
+    assert_eq!(quad.mesh.normals(), &[Vector3::z(); 4]);
+    assert_eq!(quad.mesh.uvs()[2], Point2::new(1.0, 1.0));
+
+    // 8-bit colors are sRGB encoded, and converted to linear.
+    assert_eq!(quad.mesh.colors()[0], Vector3::new(1.0, 0.0, 0.0));
+    assert_eq!(quad.mesh.colors()[3], Vector3::repeat(1.0));
+
+    // The colors are interpolated across each triangle.
+    let ray = Ray::new_from_air(Point3::new(0.75, 0.25, 1.0), -Vector3::z());
+    let hit = quad.mesh.intersects(&ray).unwrap();
+    assert!((hit.color.unwrap() - Vector3::new(0.25, 0.5, 0.25)).norm() < 1e-6);
+
+    // Which the default material uses as its color.
+    match quad.material {
+        Some(MaterialBox::Reflective(ref material)) => {
+            let up = Vector3::z();
+            let f = material.bsdf(&ray, &hit).eval(&up, &up);
+            assert!((f * std::f32::consts::PI - hit.color.unwrap()).norm() < 1e-6);
+        }
+        _ => panic!("expected a diffuse material"),
+    }
+}
+
+#[test]
+fn load_binary_test() {
+    let ascii = &ply::load(Path::new("tests/data/quad.ply")).unwrap()[0];
+
+    for &big_endian in &[false, true] {
+        let quad = ply::parse(&binary_quad(big_endian), "quad").unwrap();
+
+        assert_eq!(quad.mesh.positions(), ascii.mesh.positions());
+        assert_eq!(quad.mesh.colors(), ascii.mesh.colors());
+        assert_eq!(quad.mesh.indices(), ascii.mesh.indices());
+        assert!(quad.mesh.normals().is_empty() && quad.mesh.uvs().is_empty());
+    }
+}
+
+#[test]
+fn invalid_normals_test() {
+    let header = HEADER.replace(
+        "property float z\n",
+        "property float z\nproperty float nx\nproperty float ny\nproperty float nz\n",
+    );
+
+    // Normals are scaled to unit length, unless any can't be, in which case
+    // the mesh has none.
+    let contents = format!("{}0 0 0 0 0 2\n1 0 0 0 0 2\n0 1 0 0 0 2\n3 0 1 2\n", header);
+    let triangle = ply::parse(contents.as_bytes(), "triangle").unwrap();
+    assert_eq!(triangle.mesh.normals(), &[Vector3::z(); 3]);
+    assert!(triangle.warnings.is_empty());
+
+    for normal in &["0 0 0", "0 0 nan", "0 0 inf"] {
+        let contents = format!(
+            "{}0 0 0 0 0 1\n1 0 0 {}\n0 1 0 0 0 1\n3 0 1 2\n",
+            header, normal
+        );
+        let triangle = ply::parse(contents.as_bytes(), "triangle").unwrap();
+        assert!(triangle.mesh.normals().is_empty());
+        assert_eq!(
+            triangle.warnings,
+            &["triangle has invalid vertex normals, which are ignored"]
+        );
+    }
+}
+
+#[test]
+fn vertex_color_texture_test() {
+    let texture = VertexColorTexture {
+        fallback: Vector3::new(0.1, 0.2, 0.3),
+    };
+    let mut point = TexturePoint {
+        position: Point3::origin(),
+        uv: Point2::origin(),
+        color: None,
+        width: 0.0,
+    };
+    assert_eq!(texture.evaluate(&point), Vector3::new(0.1, 0.2, 0.3));
+
+    point.color = Some(Vector3::new(0.5, 0.6, 0.7));
+    assert_eq!(texture.evaluate(&point), Vector3::new(0.5, 0.6, 0.7));
+}
+
+#[test]
+fn malformed_test() {
+    let cases = [
+        ("obj\n".to_string(), "not a PLY file"),
+        (
+            HEADER.replace("end_header\n", ""),
+            "the header has no end_header line",
+        ),
+        (
+            HEADER.replace("ascii", "binary"),
+            "line 2: unsupported format",
+        ),
+        (
+            HEADER.replace("float z", "quad z"),
+            "line 6: unknown property type `quad`",
+        ),
+        (
+            HEADER.replace("element vertex 3\n", ""),
+            "line 3: property before any element",
+        ),
+        (
+            HEADER.replace("float z", "float w"),
+            "vertices have no position",
+        ),
+        (
+            HEADER.replace("vertex 3", "vertex 18446744073709551615"),
+            "vertex 0: unexpected end of file",
+        ),
+        (
+            HEADER.replace("face 1", "face 18446744073709551615") + "0 0 0\n1 0 0\n0 1 0\n",
+            "face 0: unexpected end of file",
+        ),
+        (
+            format!("{}0 0 0\n1 0 0\n0 1 0\n", HEADER),
+            "face 0: unexpected end of file",
+        ),
+        (
+            format!("{}0 0 0\n1 zero 0\n0 1 0\n3 0 1 2\n", HEADER),
+            "vertex 1: line 11: `zero` isn't a number",
+        ),
+        (
+            format!("{}0 0 0\n1 0 nan\n0 1 0\n3 0 1 2\n", HEADER),
+            "vertex 1: position must be finite",
+        ),
+        (
+            format!("{}0 0 0\n1 0 0\n0 1e300 0\n3 0 1 2\n", HEADER),
+            "vertex 2: position must be finite",
+        ),
+        (
+            format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n", HEADER),
+            "face 0 refers to vertex 3, but there are only 3",
+        ),
+        (
+            format!("{}0 0 0\n1 0 0\n0 1 0\n2 0 1\n", HEADER),
+            "face 0 has fewer than three vertices",
+        ),
+        (
+            format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 -1 2\n", HEADER),
+            "face 0: invalid vertex index -1",
+        ),
+    ];
+
+    for &(ref contents, expected) in &cases {
+        match ply::parse(contents.as_bytes(), "broken") {
+            Err(ImportError::Ply(message)) => assert_eq!(message, expected),
+            _ => panic!("expected an error for {:?}", contents),
+        }
+    }
+
+    // Binary files which end too soon.
+    let mut truncated = binary_quad(false);
+    truncated.truncate(truncated.len() - 3);
+    match ply::parse(&truncated, "broken") {
+        Err(ImportError::Ply(message)) => assert_eq!(message, "edge 0: unexpected end of file"),
+        _ => panic!("expected an error for a truncated file"),
+    }
+}
@@ -0,0 +1,280 @@
+extern crate exr;
+extern crate nalgebra as na;
+extern crate renderer;
+
+#[path = "lib.rs"]
+mod common;
+
+use common::integrate_sphere;
+use na::{DMatrix, Point3, Vector3};
+use renderer::bsdf::Bsdf;
+use renderer::integrator::MonteCarloIntegrator;
+use renderer::material::{PrincipledMaterial, ReflectiveMaterial};
+use renderer::principled::{PrincipledBsdf, PrincipledParameters};
+use renderer::ray::Ray;
+use renderer::render;
+use renderer::sampler::{Sampler, SamplerProperties, UniformSampler};
+use renderer::scene_file;
+use renderer::surface::{Sphere, Surface};
+use renderer::texture::ConstantTexture;
+use std::f32::consts::PI;
+use std::path::Path;
+
+fn materials() -> Vec<PrincipledParameters> {
+    let defaults = PrincipledParameters::default();
+
+    vec![
+        defaults,
+        PrincipledParameters {
+            base_color: Vector3::new(0.9, 0.6, 0.2),
+            metallic: 1.0,
+            roughness: 0.4,
+            anisotropic: 0.7,
+            ..defaults
+        },
+        PrincipledParameters {
+            base_color: Vector3::new(0.2, 0.3, 0.8),
+            roughness: 0.8,
+            sheen: 1.0,
+            clearcoat: 1.0,
+            clearcoat_gloss: 0.7,
+            subsurface: 0.5,
+            ..defaults
+        },
+        PrincipledParameters {
+            base_color: Vector3::new(0.9, 1.0, 0.9),
+            roughness: 0.3,
+            metallic: 0.2,
+            transmission: 0.9,
+            ..defaults
+        },
+    ]
+}
+
+#[test]
+fn principled_sampling_test() {
+    let mut sampler = UniformSampler::new(13);
+
+    for parameters in materials() {
+        let bsdf = PrincipledBsdf::new(&parameters, 1.0);
+        assert!(!bsdf.is_specular());
+
+        for &wo in &[
+            Vector3::new(0.0, 0.6, 0.8),
+            Vector3::new(0.9, 0.1, 0.3).normalize(),
+            Vector3::new(-0.2, 0.3, -0.9).normalize(),
+        ] {
+            // Samples agree with eval and pdf, which account for every lobe.
+            let mut estimate = Vector3::zeros();
+            for _ in 0..10_000 {
+                let u_lobe = sampler.get_1d();
+                if let Some(sample) = bsdf.sample(&wo, u_lobe, sampler.get_2d()) {
+                    assert!(!sample.specular);
+                    let pdf = bsdf.pdf(&wo, &sample.wi);
+                    let f = bsdf.eval(&wo, &sample.wi);
+                    assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf, "{:?}", parameters);
+                    assert!((sample.f - f).norm() <= 1e-3 * f.norm(), "{:?}", parameters);
+
+                    if parameters.transmission == 0.0 {
+                        assert!(sample.wi.z * wo.z > 0.0);
+                    }
+
+                    estimate += sample.f * (sample.wi.z.abs() / sample.pdf) / 10_000.0;
+                }
+            }
+
+            // Light arriving equally from every direction isn't amplified by
+            // much, although Burley's diffuse isn't exactly energy conserving.
+            // Radiance leaving glass is concentrated into a narrower cone,
+            // though, so is brighter.
+            let leaving_glass = parameters.transmission > 0.0 && wo.z < 0.0;
+            assert!(
+                leaving_glass || estimate.iter().all(|&c| c > 0.05 && c < 1.1),
+                "{:?} from {:?}: {:?}",
+                parameters,
+                wo,
+                estimate
+            );
+
+            let total = integrate_sphere(|wi| bsdf.pdf(&wo, wi));
+            assert!(total > 0.8 && total < 1.01, "{:?}: {}", parameters, total);
+        }
+    }
+}
+
+#[test]
+fn principled_limits_test() {
+    let defaults = PrincipledParameters::default();
+    let up = Vector3::z();
+
+    // Without specular reflection, it is diffuse, which reflects the base
+    // color head on.
+    let bsdf = PrincipledBsdf::new(
+        &PrincipledParameters {
+            base_color: Vector3::new(0.5, 0.25, 1.0),
+            specular: 0.0,
+            ..defaults
+        },
+        1.0,
+    );
+    assert!((bsdf.eval(&up, &up) * PI - Vector3::new(0.5, 0.25, 1.0)).norm() < 1e-5);
+    assert_eq!(bsdf.eval(&up, &-up), Vector3::zeros());
+
+    // A polished metal is a mirror that reflects its color.
+    let gold = Vector3::new(1.0, 0.8, 0.3);
+    let bsdf = PrincipledBsdf::new(
+        &PrincipledParameters {
+            base_color: gold,
+            metallic: 1.0,
+            roughness: 0.0,
+            ..defaults
+        },
+        1.0,
+    );
+    assert!(bsdf.is_specular());
+    let sample = bsdf.sample(&up, 0.5, (0.5, 0.5)).unwrap();
+    assert!(sample.specular);
+    assert!((sample.wi - up).norm() < 1e-6);
+    assert!((sample.f * (sample.wi.z / sample.pdf) - gold).norm() < 1e-5);
+
+    // Clear glass lets light straight through, tinted by the base color on
+    // both the way in and the way out.
+    let green = Vector3::new(0.25, 1.0, 0.25);
+    let bsdf = PrincipledBsdf::new(
+        &PrincipledParameters {
+            base_color: green,
+            roughness: 0.0,
+            transmission: 1.0,
+            ..defaults
+        },
+        1.0,
+    );
+    assert!(bsdf.is_specular());
+    let sample = bsdf.sample(&up, 0.99, (0.5, 0.5)).unwrap();
+    assert!((sample.wi + up).norm() < 1e-6);
+    assert_eq!(sample.index_of_refraction, Some(1.5));
+    let weight = sample.f * (sample.wi.z.abs() / sample.pdf) * 1.5 * 1.5;
+    assert!((weight - green.map(f32::sqrt)).norm() < 1e-5);
+}
+
+#[test]
+fn metallic_roughness_texture_test() {
+    let parameters = PrincipledParameters {
+        metallic: 0.8,
+        roughness: 0.6,
+        ..PrincipledParameters::default()
+    };
+    let material = PrincipledMaterial {
+        parameters,
+        base_color: None,
+        metallic_roughness: Some(Box::new(ConstantTexture {
+            color: Vector3::new(0.0, 0.5, 0.25),
+        })),
+    };
+
+    let sphere = Sphere {
+        center: Point3::new(0.0, 0.0, 2.0),
+        radius: 1.0,
+    };
+    let ray = Ray::new_from_air(Point3::origin(), Vector3::z());
+    let hit = sphere.intersects(&ray).unwrap();
+
+    // The green channel scales the roughness and the blue channel the
+    // metallic.
+    let expected = PrincipledBsdf::new(
+        &PrincipledParameters {
+            metallic: 0.2,
+            roughness: 0.3,
+            ..parameters
+        },
+        1.0,
+    );
+    let bsdf = material.bsdf(&ray, &hit);
+    let wo = Vector3::new(0.2, 0.1, 1.0).normalize();
+    let wi = Vector3::new(-0.4, 0.3, 1.0).normalize();
+    assert!((bsdf.eval(&wo, &wi) - expected.eval(&wo, &wi)).norm() < 1e-6);
+}
+
+// The block size over which the render and the reference are compared. Noise
+// in single pixels is too high at a few samples per pixel, but averages over
+// blocks converge well enough to catch changes in brightness or color.
+const BLOCK_SIZE: usize = 8;
+
+fn block_means(image: &DMatrix<Vector3<f32>>) -> DMatrix<Vector3<f32>> {
+    let (width, height) = image.shape();
+    DMatrix::from_fn(width / BLOCK_SIZE, height / BLOCK_SIZE, |bx, by| {
+        let block = image.slice((bx * BLOCK_SIZE, by * BLOCK_SIZE), (BLOCK_SIZE, BLOCK_SIZE));
+        block.iter().sum::<Vector3<f32>>() / (BLOCK_SIZE * BLOCK_SIZE) as f32
+    })
+}
+
+#[test]
+fn reference_render_test() {
+    // tests/data/principled_reference.exr is scenes/principled.toml rendered
+    // at 72x32 with 4096 samples per pixel:
+    //
+    //   renderer scenes/principled.toml --width 72 --height 32 --spp 4096 \
+    //       -o tests/data/principled_reference.exr
+    //
+    // It comes from this renderer, so it only catches changes in how the
+    // principled material looks, not differences from other implementations
+    // of it. It should only be regenerated after a change which is meant to
+    // alter that.
+    //
+    // A reference from another renderer, such as Cycles or Mitsuba, could
+    // replace it if it follows the same conventions. The image holds linear
+    // RGB radiance without tone mapping, seen through a pinhole camera with a
+    // 55 degree vertical field of view and a box filter. The spheres and
+    // planes are as in the scene file, with y up and the camera looking
+    // towards +z. The lights are diffuse spheres emitting white radiance of
+    // the given emissivity, there is no light from the background, and paths
+    // end after 8 bounces.
+    let mut config = scene_file::load(Path::new("scenes/principled.toml")).unwrap();
+    config.render.width = 72;
+    config.render.height = 32;
+    config.render.samples_per_pixel = 16;
+
+    let integrator = MonteCarloIntegrator {
+        camera: &config.camera,
+        scene: &config.scene,
+        width: config.render.width,
+        height: config.render.height,
+        max_bounces: config.integrator.max_bounces,
+        sampler: SamplerProperties {
+            kind: config.integrator.sampler,
+            seed: config.integrator.seed,
+            samples_per_pixel: config.render.samples_per_pixel,
+        },
+    };
+    let image = render::render(&config.render, &integrator);
+
+    let reference = exr::prelude::read_first_rgba_layer_from_file(
+        "tests/data/principled_reference.exr",
+        |size, _| DMatrix::from_element(size.0, size.1, Vector3::zeros()),
+        |pixels: &mut DMatrix<Vector3<f32>>, position, (r, g, b, _): (f32, f32, f32, f32)| {
+            pixels[(position.0, position.1)] = Vector3::new(r, g, b)
+        },
+    )
+    .unwrap()
+    .layer_data
+    .channel_data
+    .pixels;
+    assert_eq!(reference.shape(), image.shape());
+
+    // The blocks are within a few percent of each other, so this leaves room
+    // for noise while catching materials which reflect noticeably more or less
+    // light, or light of the wrong color. The absolute part of the tolerance
+    // covers blocks which are black, or nearly so, in the reference.
+    for (block, expected) in block_means(&image)
+        .iter()
+        .zip(block_means(&reference).iter())
+    {
+        let tolerance = 0.01 + 0.1 * expected.norm();
+        assert!(
+            (block - expected).norm() < tolerance,
+            "{:?} differs from {:?}",
+            block,
+            expected
+        );
+    }
+}
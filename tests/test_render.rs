@@ -0,0 +1,177 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::Vector3;
+use renderer::film::{FilmTile, Filter, FilterKind};
+use renderer::integrator::Integrator;
+use renderer::render::{self, CropWindow, RenderProperties};
+use std::ops::Range;
+use std::time::Duration;
+
+// Reports the pixel position back as the color, so that every pixel can be
+// checked against where it was rendered.
+struct PositionIntegrator;
+
+impl Integrator for PositionIntegrator {
+    fn integrate(&self, (x, y): (usize, usize), samples: Range<u32>, film: &mut FilmTile) {
+        for _ in samples {
+            let center = (x as f32 + 0.5, y as f32 + 0.5);
+            film.add_sample(center, Vector3::new(x as f32, y as f32, 1.0));
+        }
+    }
+}
+
+// Reports the index of each sample, so that the weighting of progressive
+// passes can be checked.
+struct SampleIndexIntegrator;
+
+impl Integrator for SampleIndexIntegrator {
+    fn integrate(&self, (x, y): (usize, usize), samples: Range<u32>, film: &mut FilmTile) {
+        for sample in samples {
+            let center = (x as f32 + 0.5, y as f32 + 0.5);
+            film.add_sample(center, Vector3::new(sample as f32, 0.0, 0.0));
+        }
+    }
+}
+
+fn properties(threads: usize, crop: Option<CropWindow>) -> RenderProperties {
+    RenderProperties {
+        width: 37,
+        height: 21,
+        crop,
+        threads,
+        filter: Filter::default(),
+        samples_per_pixel: 1,
+        samples_per_pass: None,
+        time_limit: None,
+    }
+}
+
+#[test]
+fn tiles_cover_window_test() {
+    let window = CropWindow {
+        x0: 3,
+        y0: 5,
+        x1: 40,
+        y1: 22,
+    };
+
+    let tiles = window.tiles(16);
+    assert_eq!(tiles.len(), 6);
+
+    let covered: usize = tiles.iter().map(|t| t.width() * t.height()).sum();
+    assert_eq!(covered, window.width() * window.height());
+    assert!(tiles
+        .iter()
+        .all(|t| t.fits(40, 22) && t.x0 >= 3 && t.y0 >= 5));
+}
+
+#[test]
+fn thread_count_does_not_change_output_test() {
+    let single = render::render(&properties(1, None), &PositionIntegrator);
+
+    for threads in 2..5 {
+        let multi = render::render(&properties(threads, None), &PositionIntegrator);
+        assert_eq!(single, multi);
+    }
+
+    // Every pixel should have been written in the right place.
+    for x in 0..37 {
+        for y in 0..21 {
+            let expected = Vector3::new(x as f32, y as f32, 1.0);
+            assert_eq!(single[(x, y)], expected);
+        }
+    }
+}
+
+#[test]
+fn crop_window_test() {
+    let crop = CropWindow {
+        x0: 10,
+        y0: 4,
+        x1: 30,
+        y1: 20,
+    };
+
+    let full = render::render(&properties(2, None), &PositionIntegrator);
+    let cropped = render::render(&properties(2, Some(crop)), &PositionIntegrator);
+
+    assert_eq!((cropped.nrows(), cropped.ncols()), (20, 16));
+    assert_eq!(cropped, full.slice((10, 4), (20, 16)).into_owned());
+}
+
+#[test]
+fn crop_window_with_wide_filter_test() {
+    let crop = CropWindow {
+        x0: 10,
+        y0: 4,
+        x1: 30,
+        y1: 20,
+    };
+
+    // Samples from pixels around the crop window are filtered into it, so it
+    // should still match the full image.
+    let gaussian = |crop| RenderProperties {
+        filter: Filter::new(FilterKind::Gaussian),
+        ..properties(2, crop)
+    };
+
+    let full = render::render(&gaussian(None), &PositionIntegrator);
+    let cropped = render::render(&gaussian(Some(crop)), &PositionIntegrator);
+
+    let expected = full.slice((10, 4), (20, 16));
+    assert!(cropped
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).norm() < 1e-4));
+}
+
+#[test]
+fn progressive_passes_test() {
+    let progressive = RenderProperties {
+        samples_per_pixel: 10,
+        samples_per_pass: Some(3),
+        ..properties(2, None)
+    };
+    assert_eq!(progressive.passes(), 4);
+
+    let mut passes = Vec::new();
+    let image = render::render_progressive(&progressive, &SampleIndexIntegrator, |pass| {
+        passes.push((pass.index, pass.samples_per_pixel, pass.last));
+    });
+
+    assert_eq!(
+        passes,
+        vec![(0, 3, false), (1, 6, false), (2, 9, false), (3, 10, true)]
+    );
+
+    // Every sample from 0 to 9 is counted once.
+    assert!(image.iter().all(|c| (c.x - 4.5).abs() < 1e-5));
+
+    // A deterministic integrator gives the same image either way.
+    let single = render::render(&properties(2, None), &PositionIntegrator);
+    let accumulated = render::render(&progressive, &PositionIntegrator);
+    assert!(single
+        .iter()
+        .zip(accumulated.iter())
+        .all(|(a, b)| (a - b).norm() < 1e-5));
+}
+
+#[test]
+fn time_limit_test() {
+    let properties = RenderProperties {
+        samples_per_pixel: 100,
+        samples_per_pass: Some(1),
+        time_limit: Some(Duration::from_nanos(1)),
+        ..properties(1, None)
+    };
+
+    // The first pass always finishes, but no more are started.
+    let mut passes = 0;
+    render::render_progressive(&properties, &PositionIntegrator, |pass| {
+        passes += 1;
+        assert!(pass.last);
+        assert_eq!(pass.samples_per_pixel, 1);
+    });
+    assert_eq!(passes, 1);
+}
@@ -0,0 +1,195 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{DMatrix, Vector3};
+use renderer::integrator::MonteCarloIntegrator;
+use renderer::render::{self, CropWindow};
+use renderer::sampler::{SamplerKind, SamplerProperties};
+use renderer::scene_file::{self, SceneConfig};
+use std::path::Path;
+
+const KINDS: [SamplerKind; 4] = [
+    SamplerKind::Uniform,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+];
+
+fn properties(kind: SamplerKind, seed: u64, samples_per_pixel: u32) -> SamplerProperties {
+    SamplerProperties {
+        kind,
+        seed,
+        samples_per_pixel,
+    }
+}
+
+// The first few dimensions of a sample.
+fn draw(properties: &SamplerProperties, pixel: (usize, usize), sample: u32) -> Vec<f32> {
+    let mut sampler = properties.sampler(pixel, sample);
+
+    let mut values = vec![sampler.get_1d()];
+    for _ in 0..4 {
+        let (u, v) = sampler.get_2d();
+        values.push(u);
+        values.push(v);
+    }
+    values
+}
+
+#[test]
+fn sampler_is_deterministic_test() {
+    for &kind in &KINDS {
+        let a = properties(kind, 7, 16);
+        let values = draw(&a, (3, 4), 5);
+
+        assert_eq!(values, draw(&a, (3, 4), 5));
+        assert!(values.iter().all(|&u| (0.0..1.0).contains(&u)));
+
+        // Changing any part of the seed gives different values.
+        assert_ne!(values, draw(&properties(kind, 8, 16), (3, 4), 5));
+        assert_ne!(values, draw(&a, (4, 3), 5));
+        assert_ne!(values, draw(&a, (3, 4), 6));
+    }
+}
+
+#[test]
+fn samplers_are_uniform_test() {
+    // Over many pixels, every dimension of every sampler should be uniformly
+    // distributed.
+    for &kind in &KINDS {
+        let properties = properties(kind, 0, 16);
+
+        let mut buckets = vec![[0; 10]; 9];
+        for pixel in 0..64 {
+            for sample in 0..16 {
+                let values = draw(&properties, (pixel, 0), sample);
+                for (dimension, &u) in values.iter().enumerate() {
+                    buckets[dimension][(u * 10.0) as usize] += 1;
+                }
+            }
+        }
+
+        for dimension in &buckets {
+            assert!(dimension.iter().all(|&n| n > 70 && n < 135), "{:?}", kind);
+        }
+    }
+}
+
+#[test]
+fn samples_are_stratified_test() {
+    // The samples of a pixel should fall into every cell of a 4x4 grid, in
+    // each pair of dimensions, and into every 16th of each single dimension.
+    for &kind in &[SamplerKind::Stratified, SamplerKind::Sobol] {
+        let properties = properties(kind, 1, 16);
+        let samples: Vec<Vec<f32>> = (0..16).map(|i| draw(&properties, (5, 9), i)).collect();
+
+        let mut strata = [false; 16];
+        for values in &samples {
+            strata[(values[0] * 16.0) as usize] = true;
+        }
+        assert!(strata.iter().all(|&hit| hit), "{:?}", kind);
+
+        for pair in 0..4 {
+            let mut cells = [false; 16];
+            for values in &samples {
+                let (u, v) = (values[1 + pair * 2], values[2 + pair * 2]);
+                cells[(u * 4.0) as usize + (v * 4.0) as usize * 4] = true;
+            }
+            assert!(cells.iter().all(|&hit| hit), "{:?}", kind);
+        }
+    }
+}
+
+fn render_box(
+    sampler: SamplerKind,
+    seed: u64,
+    samples_per_pixel: u32,
+    max_bounces: u32,
+    threads: usize,
+    samples_per_pass: Option<u32>,
+) -> DMatrix<Vector3<f32>> {
+    let SceneConfig {
+        camera,
+        scene,
+        mut render,
+        ..
+    } = scene_file::load(Path::new("scenes/box.toml")).unwrap();
+
+    render.samples_per_pixel = samples_per_pixel;
+    render.samples_per_pass = samples_per_pass;
+    render.threads = threads;
+    // A patch of the floor across the edge of the glass sphere's shadow,
+    // which exercises both pixel and light sampling.
+    render.crop = Some(CropWindow {
+        x0: 204,
+        y0: 312,
+        x1: 220,
+        y1: 328,
+    });
+
+    let integrator = MonteCarloIntegrator {
+        camera: &camera,
+        scene: &scene,
+        width: render.width,
+        height: render.height,
+        max_bounces,
+        sampler: properties(sampler, seed, samples_per_pixel),
+    };
+
+    render::render(&render, &integrator)
+}
+
+#[test]
+fn render_is_reproducible_test() {
+    let image = render_box(SamplerKind::Sobol, 1, 4, 5, 1, None);
+
+    assert_eq!(image, render_box(SamplerKind::Sobol, 1, 4, 5, 1, None));
+    assert_eq!(image, render_box(SamplerKind::Sobol, 1, 4, 5, 3, None));
+    assert_ne!(image, render_box(SamplerKind::Sobol, 2, 4, 5, 1, None));
+
+    // Progressive passes use the same samples, only summed in another order.
+    let progressive = render_box(SamplerKind::Sobol, 1, 4, 5, 2, Some(1));
+    assert!(image
+        .iter()
+        .zip(progressive.iter())
+        .all(|(a, b)| (a - b).norm() <= 1e-4 * a.norm().max(1.0)));
+}
+
+// The variance of each pixel over renders with different seeds, averaged over
+// the image. Only direct lighting is rendered, since the caustics through the
+// glass sphere cause fireflies that would swamp the comparison.
+fn mean_variance(sampler: SamplerKind) -> f32 {
+    let renders: Vec<_> = (0..8)
+        .map(|seed| render_box(sampler, seed, 16, 2, 0, None))
+        .collect();
+
+    let pixels = renders[0].len();
+    let mut total = 0.0;
+    for i in 0..pixels {
+        let mean = renders.iter().map(|r| r[i]).sum::<Vector3<f32>>() / renders.len() as f32;
+        let variance: f32 = renders.iter().map(|r| (r[i] - mean).norm_squared()).sum();
+        total += variance / (renders.len() - 1) as f32;
+    }
+
+    total / pixels as f32
+}
+
+#[test]
+fn low_discrepancy_variance_test() {
+    let uniform = mean_variance(SamplerKind::Uniform);
+
+    for &kind in &[
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ] {
+        let variance = mean_variance(kind);
+        assert!(
+            variance < uniform,
+            "{:?}: {} vs uniform {}",
+            kind,
+            variance,
+            uniform
+        );
+    }
+}
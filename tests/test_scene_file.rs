@@ -0,0 +1,476 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use renderer::integrator::IntegratorKind;
+use renderer::scene_file::{self, LoadError};
+use renderer::tonemap::{ToneMapOperator, ToneMapping};
+use std::path::Path;
+use std::time::Duration;
+
+const MINIMAL: &str = r#"
+[camera]
+position = [0.0, 0.0, -1.0]
+direction = [0.0, 0.0, 1.0]
+
+[render]
+width = 10
+height = 20
+
+[[objects]]
+surface = { type = "sphere", center = [0.0, 0.0, 1.0], radius = 0.5 }
+material = { type = "diffuse", color = [1.0, 0.5, 0.25] }
+"#;
+
+#[test]
+fn load_demo_scene_test() {
+    let config = scene_file::load(Path::new("scenes/box.toml")).unwrap();
+
+    assert_eq!(config.scene.objects().len(), 7);
+    assert_eq!(config.render.width, 500);
+    assert_eq!(config.render.height, 500);
+    assert_eq!(config.render.samples_per_pixel, 1000);
+    assert_eq!(config.integrator.max_bounces, 5);
+}
+
+#[test]
+fn integrator_defaults_test() {
+    let config = scene_file::parse(MINIMAL, Path::new("")).unwrap();
+
+    assert_eq!(config.scene.objects().len(), 1);
+    assert_eq!(config.render.width, 10);
+    assert_eq!(config.render.height, 20);
+    assert_eq!(config.render.samples_per_pixel, 100);
+    assert_eq!(config.integrator.max_bounces, 5);
+}
+
+#[test]
+fn ambient_occlusion_test() {
+    let config = scene_file::parse(MINIMAL, Path::new("")).unwrap();
+    assert_eq!(config.integrator.kind, IntegratorKind::Path);
+    assert_eq!(config.integrator.occlusion_distance, f32::INFINITY);
+
+    let contents = format!(
+        "{}\n[integrator]\ntype = \"ao\"\nocclusion_distance = 0.5\n",
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("")).unwrap();
+    assert_eq!(config.integrator.kind, IntegratorKind::AmbientOcclusion);
+    assert_eq!(config.integrator.occlusion_distance, 0.5);
+
+    for &(value, expected) in &[
+        ("type = \"whitted\"", "integrator.type"),
+        ("occlusion_distance = 0.0", "integrator.occlusion_distance"),
+    ] {
+        let contents = format!("{}\n[integrator]\n{}\n", MINIMAL, value);
+        match scene_file::parse(&contents, Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, expected),
+            _ => panic!("expected an invalid value error"),
+        }
+    }
+}
+
+#[test]
+fn parse_error_reports_location_test() {
+    let contents = MINIMAL.replace("width = 10", "width = \"ten\"");
+
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Parse(err)) => {
+            // Line and column are zero-based.
+            assert_eq!(err.line_col(), Some((6, 8)));
+            assert!(err.to_string().contains("render.width"));
+        }
+        _ => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn invalid_value_reports_field_test() {
+    let contents = MINIMAL.replace("radius = 0.5", "radius = -0.5");
+
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => {
+            assert_eq!(field, "objects[0].surface.radius");
+        }
+        _ => panic!("expected an invalid value error"),
+    }
+}
+
+#[test]
+fn time_limit_test() {
+    let contents = MINIMAL.replace("height = 20", "height = 20\ntime_limit = 2.5");
+    let config = scene_file::parse(&contents, Path::new("")).unwrap();
+    assert_eq!(config.render.time_limit, Some(Duration::from_millis(2500)));
+
+    // Too long to be a duration, as well as not being positive.
+    for limit in &["1e30", "0.0", "-1.0"] {
+        let contents = MINIMAL.replace(
+            "height = 20",
+            &format!("height = 20\ntime_limit = {}", limit),
+        );
+        match scene_file::parse(&contents, Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "render.time_limit"),
+            _ => panic!("expected an invalid value error for {}", limit),
+        }
+    }
+}
+
+#[test]
+fn missing_file_test() {
+    match scene_file::load(Path::new("scenes/does-not-exist.toml")) {
+        Err(LoadError::Io(..)) => {}
+        _ => panic!("expected an I/O error"),
+    }
+}
+
+#[test]
+fn load_mesh_test() {
+    let contents = format!("{}\n[[meshes]]\npath = \"cube.obj\"\n", MINIMAL);
+    let config = scene_file::parse(&contents, Path::new("tests/data")).unwrap();
+
+    // The sphere, plus one object per object in the OBJ file.
+    assert_eq!(config.scene.objects().len(), 3);
+
+    let contents = format!("{}\n[[meshes]]\npath = \"missing.obj\"\n", MINIMAL);
+    match scene_file::parse(&contents, Path::new("tests/data")) {
+        Err(LoadError::Import(path, _)) => assert_eq!(path, Path::new("tests/data/missing.obj")),
+        _ => panic!("expected an import error"),
+    }
+}
+
+#[test]
+fn warnings_test() {
+    let config = scene_file::parse(MINIMAL, Path::new("")).unwrap();
+    assert!(config.warnings.is_empty());
+
+    // Deprecated keys and problems in imported files are reported, but the
+    // scene still loads.
+    let contents = format!(
+        "{}\n[[meshes]]\npath = \"zero_normal.obj\"\n",
+        MINIMAL.replace(
+            "{ type = \"diffuse\", color = [1.0, 0.5, 0.25] }",
+            "{ type = \"refractive\", index_of_refraction = 1.5, reflect_prob = 0.1 }",
+        )
+    );
+    let config = scene_file::parse(&contents, Path::new("tests/data")).unwrap();
+    assert_eq!(
+        config.warnings,
+        &[
+            "objects[0].material.reflect_prob is no longer used and is ignored",
+            "tests/data/zero_normal.obj: triangle has invalid vertex normals, which are ignored",
+        ]
+    );
+}
+
+#[test]
+fn load_ply_and_stl_test() {
+    let contents = format!(
+        "{}\n[[meshes]]\npath = \"quad.ply\"\n\
+         material = {{ type = \"diffuse\", color = {{ type = \"vertex_color\" }} }}\n\
+         [[meshes]]\npath = \"tetrahedron.stl\"\n",
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("tests/data")).unwrap();
+    assert_eq!(config.scene.objects().len(), 3);
+
+    let contents = format!("{}\n[[meshes]]\npath = \"cube.3ds\"\n", MINIMAL);
+    match scene_file::parse(&contents, Path::new("tests/data")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "meshes[0].path"),
+        _ => panic!("expected an invalid value error"),
+    }
+
+    let contents = MINIMAL.replace(
+        "color = [1.0, 0.5, 0.25]",
+        "color = { type = \"vertex_color\", fallback = [-1.0, 0.0, 0.0] }",
+    );
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => {
+            assert_eq!(field, "objects[0].material.color.fallback")
+        }
+        _ => panic!("expected an invalid value error"),
+    }
+}
+
+#[test]
+fn camera_target_test() {
+    let contents = MINIMAL.replace("direction = [0.0, 0.0, 1.0]", "target = [0.0, 0.0, 5.0]");
+    let config = scene_file::parse(&contents, Path::new("")).unwrap();
+    assert_eq!(config.camera.direction(), na::Vector3::new(0.0, 0.0, 1.0));
+
+    let contents = MINIMAL.replace(
+        "direction = [0.0, 0.0, 1.0]",
+        "direction = [0.0, 0.0, 1.0]\ntarget = [0.0, 0.0, 5.0]",
+    );
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "camera"),
+        _ => panic!("expected an invalid value error"),
+    }
+
+    let contents = MINIMAL.replace("direction = [0.0, 0.0, 1.0]", "direction = [0.0, 1.0, 0.0]");
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "camera.up"),
+        _ => panic!("expected an invalid value error"),
+    }
+}
+
+#[test]
+fn tonemap_test() {
+    let config = scene_file::parse(MINIMAL, Path::new("")).unwrap();
+    assert_eq!(config.tonemap, ToneMapping::default());
+
+    let contents = format!(
+        "{}\n[tonemap]\nexposure = -1.5\noperator = \"aces\"\ndither = true\n",
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("")).unwrap();
+    assert_eq!(config.tonemap.exposure, -1.5);
+    assert_eq!(config.tonemap.operator, ToneMapOperator::Aces);
+    assert!(config.tonemap.dither);
+
+    let contents = contents.replace("aces", "sepia");
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "tonemap.operator"),
+        _ => panic!("expected an invalid value error"),
+    }
+}
+
+#[test]
+fn microfacet_material_test() {
+    let diffuse = r#"{ type = "diffuse", color = [1.0, 0.5, 0.25] }"#;
+
+    let conductor = r#"{ type = "conductor", eta = [0.2, 0.9, 1.1], k = [3.9, 2.4, 2.1], roughness = [0.05, 0.4] }"#;
+    assert!(scene_file::parse(&MINIMAL.replace(diffuse, conductor), Path::new("")).is_ok());
+
+    // Roughness is optional for conductors, which are then polished.
+    let polished = r#"{ type = "conductor", eta = [0.2, 0.9, 1.1], k = [3.9, 2.4, 2.1] }"#;
+    assert!(scene_file::parse(&MINIMAL.replace(diffuse, polished), Path::new("")).is_ok());
+
+    let glass = r#"{ type = "rough_dielectric", index_of_refraction = 1.5, roughness = 0.2, distribution = "beckmann" }"#;
+    assert!(scene_file::parse(&MINIMAL.replace(diffuse, glass), Path::new("")).is_ok());
+
+    for &(material, expected) in &[
+        (
+            r#"{ type = "rough_dielectric", index_of_refraction = 1.5, roughness = 0.2, distribution = "phong" }"#,
+            "objects[0].material.distribution",
+        ),
+        (
+            r#"{ type = "conductor", eta = [0.2, 0.9, 1.1], k = [3.9, 2.4, 2.1], roughness = -0.1 }"#,
+            "objects[0].material.roughness",
+        ),
+        (
+            r#"{ type = "conductor", eta = [0.2, 0.9, 1.1], k = [3.9, -2.4, 2.1] }"#,
+            "objects[0].material.k",
+        ),
+    ] {
+        match scene_file::parse(&MINIMAL.replace(diffuse, material), Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, expected),
+            _ => panic!("expected {} to be invalid", expected),
+        }
+    }
+}
+
+#[test]
+fn principled_material_test() {
+    let diffuse = r#"{ type = "diffuse", color = [1.0, 0.5, 0.25] }"#;
+
+    // Parameters that are left out take their defaults.
+    let principled =
+        r#"{ type = "principled", base_color = [0.9, 0.6, 0.2], metallic = 1.0, roughness = 0.3 }"#;
+    assert!(scene_file::parse(&MINIMAL.replace(diffuse, principled), Path::new("")).is_ok());
+    let plain = r#"{ type = "principled" }"#;
+    assert!(scene_file::parse(&MINIMAL.replace(diffuse, plain), Path::new("")).is_ok());
+
+    let out_of_range = r#"{ type = "principled", clearcoat = 1.5 }"#;
+    match scene_file::parse(&MINIMAL.replace(diffuse, out_of_range), Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "objects[0].material.clearcoat"),
+        _ => panic!("expected an invalid clearcoat"),
+    }
+
+    let misspelled = r#"{ type = "principled", metalic = 1.0 }"#;
+    match scene_file::parse(&MINIMAL.replace(diffuse, misspelled), Path::new("")) {
+        Err(LoadError::Parse(err)) => assert!(err.to_string().contains("metalic")),
+        _ => panic!("expected unknown parameters to be rejected"),
+    }
+}
+
+#[test]
+fn emissive_material_test() {
+    let diffuse = r#"{ type = "diffuse", color = [1.0, 0.5, 0.25] }"#;
+
+    for &light in &[
+        r#"{ type = "emissive", emissivity = 10.0 }"#,
+        r#"{ type = "emissive", color = [1.0, 0.8, 0.6], one_sided = true }"#,
+        r#"{ type = "emissive", temperature = 2700.0, emissivity = 5.0 }"#,
+    ] {
+        assert!(scene_file::parse(&MINIMAL.replace(diffuse, light), Path::new("")).is_ok());
+    }
+
+    for &(light, expected) in &[
+        (
+            r#"{ type = "emissive", color = [1.0, 0.8, 0.6], temperature = 2700.0 }"#,
+            "objects[0].material.temperature",
+        ),
+        (
+            r#"{ type = "emissive", temperature = -5.0 }"#,
+            "objects[0].material.temperature",
+        ),
+        (
+            r#"{ type = "emissive", color = [1.0, -0.8, 0.6] }"#,
+            "objects[0].material.color",
+        ),
+    ] {
+        match scene_file::parse(&MINIMAL.replace(diffuse, light), Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, expected),
+            _ => panic!("expected {} to be invalid", expected),
+        }
+    }
+
+    // Textures are found relative to the scene file.
+    let screen = r#"{ type = "emissive", texture = "missing.png" }"#;
+    match scene_file::parse(&MINIMAL.replace(diffuse, screen), Path::new("scenes")) {
+        Err(LoadError::Texture(path, _)) => assert_eq!(path, Path::new("scenes/missing.png")),
+        _ => panic!("expected the texture to be missing"),
+    }
+}
+
+#[test]
+fn textured_material_test() {
+    let diffuse = r#"{ type = "diffuse", color = [1.0, 0.5, 0.25] }"#;
+
+    for &material in &[
+        r#"{ type = "diffuse", color = { type = "checkerboard", even = [0.1, 0.1, 0.1], odd = [0.9, 0.9, 0.9], scale = 4.0 } }"#,
+        r#"{ type = "diffuse", color = { type = "gradient", from = [1.0, 1.0, 1.0], to = [0.2, 0.4, 0.9] } }"#,
+        r#"{ type = "principled", base_color = { type = "noise", low = [0.9, 0.9, 0.85], high = [0.3, 0.3, 0.3], scale = 3.0, octaves = 6 } }"#,
+        r#"{ type = "emissive", emissivity = 2.0, color = { type = "checkerboard", even = [1.0, 0.0, 0.0], odd = [0.0, 0.0, 1.0], scale = [4.0, 2.0] } }"#,
+    ] {
+        assert!(scene_file::parse(&MINIMAL.replace(diffuse, material), Path::new("")).is_ok());
+    }
+
+    for &(material, expected) in &[
+        (
+            r#"{ type = "diffuse", color = { type = "image", path = "wood.png", filter = "cubic" } }"#,
+            "objects[0].material.color.filter",
+        ),
+        (
+            r#"{ type = "diffuse", color = { type = "image", path = "wood.png", wrap = "tile" } }"#,
+            "objects[0].material.color.wrap",
+        ),
+        (
+            r#"{ type = "diffuse", color = { type = "checkerboard", even = [0.1, 0.1, 0.1], odd = [0.9, -0.9, 0.9] } }"#,
+            "objects[0].material.color.odd",
+        ),
+        (
+            r#"{ type = "principled", base_color = { type = "noise", low = [0.0, 0.0, 0.0], high = [1.0, 1.0, 1.0], octaves = 0 } }"#,
+            "objects[0].material.base_color.octaves",
+        ),
+    ] {
+        match scene_file::parse(&MINIMAL.replace(diffuse, material), Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, expected),
+            _ => panic!("expected {} to be invalid", expected),
+        }
+    }
+
+    let missing = r#"{ type = "diffuse", color = { type = "image", path = "wood.png" } }"#;
+    match scene_file::parse(&MINIMAL.replace(diffuse, missing), Path::new("textures")) {
+        Err(LoadError::Texture(path, _)) => assert_eq!(path, Path::new("textures/wood.png")),
+        _ => panic!("expected the texture to be missing"),
+    }
+}
+
+#[test]
+fn instancing_test() {
+    let contents = format!(
+        r#"{}
+[[meshes]]
+path = "cube.obj"
+transform = {{ scale = [1.0, 2.0, 1.0], rotate = {{ axis = [0.0, 1.0, 0.0], angle = 45.0 }}, translate = [0.0, 0.0, 4.0] }}
+
+[prototypes.pair]
+objects = [
+    {{ surface = {{ type = "sphere", center = [0.0, 0.0, 0.0], radius = 1.0 }}, material = {{ type = "specular" }} }},
+    {{ surface = {{ type = "sphere", center = [2.0, 0.0, 0.0], radius = 1.0 }}, material = {{ type = "emissive" }}, transform = {{ scale = 0.5 }} }},
+]
+
+[[instances]]
+prototype = "pair"
+transform = {{ translate = [-3.0, 0.0, 5.0] }}
+
+[[instances]]
+prototype = "pair"
+transform = {{ matrix = [[0.0, -1.0, 0.0, 3.0], [1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 5.0], [0.0, 0.0, 0.0, 1.0]] }}
+"#,
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("tests/data")).unwrap();
+
+    // The sphere, the two objects from the OBJ file, and two from each
+    // instance. The prototype itself isn't part of the scene.
+    assert_eq!(config.scene.objects().len(), 7);
+
+    for &(transform, expected) in &[
+        ("{ scale = [1.0, 0.0, 1.0] }", "objects[0].transform.scale"),
+        (
+            "{ rotate = { axis = [0.0, 0.0, 0.0], angle = 10.0 } }",
+            "objects[0].transform.rotate.axis",
+        ),
+        (
+            "{ matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]], scale = 2.0 }",
+            "objects[0].transform",
+        ),
+        (
+            "{ matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]] }",
+            "objects[0].transform.matrix",
+        ),
+        (
+            "{ matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 1.0, 1.0]] }",
+            "objects[0].transform.matrix",
+        ),
+    ] {
+        let contents = format!("{}transform = {}\n", MINIMAL, transform);
+        match scene_file::parse(&contents, Path::new("")) {
+            Err(LoadError::Invalid { field, .. }) => assert_eq!(field, expected),
+            _ => panic!("expected {} to be invalid", expected),
+        }
+    }
+
+    let contents = format!("{}\n[[instances]]\nprototype = \"chair\"\n", MINIMAL);
+    match scene_file::parse(&contents, Path::new("")) {
+        Err(LoadError::Invalid { field, .. }) => assert_eq!(field, "instances[0].prototype"),
+        _ => panic!("expected the prototype to be missing"),
+    }
+}
+
+#[test]
+fn load_gltf_test() {
+    // A glTF file renders from its own camera, with the camera's aspect ratio.
+    let config = scene_file::load(Path::new("tests/data/scene.gltf")).unwrap();
+    assert_eq!((config.render.width, config.render.height), (720, 480));
+    assert_eq!(config.scene.objects().len(), 3);
+
+    // Or it can be placed in a scene file like any other mesh.
+    let contents = format!(
+        "{}\n[[meshes]]\npath = \"scene.gltf\"\ntransform = {{ translate = [0.0, 0.0, 10.0] }}\n",
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("tests/data")).unwrap();
+    assert_eq!(config.scene.objects().len(), 4);
+    let ray = renderer::ray::Ray::new_from_air(na::Point3::new(0.25, 0.75, 0.0), na::Vector3::z());
+    let (hit, _) = config.scene.intersects(&ray).unwrap();
+    assert!((hit.distance - 12.0).abs() < 1e-5);
+}
+
+#[test]
+fn plane_normal_test() {
+    let contents = format!(
+        "{}\n[[objects]]\n\
+         surface = {{ type = \"plane\", normal = [0.0, 2.0, 0.0], offset = 1.0 }}\n\
+         material = {{ type = \"diffuse\", color = [0.5, 0.5, 0.5] }}\n",
+        MINIMAL
+    );
+    let config = scene_file::parse(&contents, Path::new("")).unwrap();
+
+    // The normal is normalized, and the plane stays where it was.
+    let ray = renderer::ray::Ray::new_from_air(na::Point3::new(5.0, 3.0, 5.0), -na::Vector3::y());
+    let (hit, _) = config.scene.intersects(&ray).unwrap();
+    assert!((hit.distance - 2.0).abs() < 1e-6);
+    assert_eq!(hit.normal, na::Vector3::y());
+}
@@ -0,0 +1,226 @@
+extern crate nalgebra as na;
+extern crate rand;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use renderer::intersection::Intersection;
+use renderer::material::{EmissiveMaterial, MaterialBox, PerfectDiffuseMaterial};
+use renderer::mesh::TriangleMesh;
+use renderer::object::Object;
+use renderer::ray::{MediumStack, Ray};
+use renderer::sampling::uniform_cone;
+use renderer::scene::Scene;
+use renderer::scene_file;
+use renderer::surface::Sphere;
+use renderer::texture::ConstantTexture;
+use renderer::transform::{Transform, TransformedSurface};
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+
+// How often rays leaving surfaces find something they shouldn't.
+#[derive(Debug, Default)]
+struct Acne {
+    // Rays which hit the surface they left straight away.
+    self_hits: usize,
+
+    // Shadow rays which were blocked by the surface they left, or by the light
+    // they were cast towards.
+    shadowed: usize,
+}
+
+// Cast rays from `eye` onto the surfaces of a scene, then cast more rays from
+// each hit point: bounces in random directions, and shadow rays towards the
+// lights. `curved` says which objects are curved, and so can be hit again by
+// rays passing into them.
+fn acne<S, T>(scene: &Scene, eye: Point3<f32>, curved: &[usize], spawn: S, spawn_to: T) -> Acne
+where
+    S: Fn(&Intersection, Vector3<f32>) -> Ray,
+    T: Fn(&Intersection, &Point3<f32>, &Vector3<f32>, &Vector3<f32>) -> Ray,
+{
+    let mut rng = StdRng::seed_from_u64(0);
+    let random_direction = |rng: &mut StdRng| uniform_cone((rng.gen(), rng.gen()), -1.0);
+    let index = |object: &Object| {
+        scene
+            .objects()
+            .iter()
+            .position(|o| ptr::eq(o, object))
+            .unwrap()
+    };
+
+    let mut acne = Acne::default();
+    for _ in 0..2000 {
+        let direction = random_direction(&mut rng);
+        let (hit, object) = match scene.intersects(&Ray::new_from_air(eye, direction)) {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        for _ in 0..4 {
+            // Rays passing into a curved object may hit the far side of it.
+            let bounce = random_direction(&mut rng);
+            let passes_in = bounce.dot(&direction) > 0.0;
+            if passes_in && curved.contains(&index(object)) {
+                continue;
+            }
+
+            if let Some((again, other)) = scene.intersects(&spawn(&hit, bounce)) {
+                if ptr::eq(object, other) && again.distance < 1e-2 {
+                    acne.self_hits += 1;
+                }
+            }
+        }
+
+        if let Some((sample, light)) =
+            scene.sample_light(&hit.position, rng.gen(), (rng.gen(), rng.gen()))
+        {
+            // Curved objects may shadow themselves.
+            if ptr::eq(object, light) || curved.contains(&index(object)) {
+                continue;
+            }
+
+            let shadow_ray = spawn_to(&hit, &sample.position, &sample.normal, &sample.error);
+            if let Some((_, other)) = scene.intersects(&shadow_ray) {
+                if ptr::eq(object, other) || ptr::eq(light, other) {
+                    acne.shadowed += 1;
+                }
+            }
+        }
+    }
+
+    acne
+}
+
+// Rays which start exactly on the surface, as bounces used to.
+fn naive(scene: &Scene, eye: Point3<f32>, curved: &[usize]) -> Acne {
+    acne(
+        scene,
+        eye,
+        curved,
+        |hit, direction| Ray::new_from_air(hit.position, direction),
+        |hit, target, _, _| {
+            let offset = target - hit.position;
+            Ray {
+                t_max: offset.norm(),
+                ..Ray::new_from_air(hit.position, offset.normalize())
+            }
+        },
+    )
+}
+
+fn robust(scene: &Scene, eye: Point3<f32>, curved: &[usize]) -> Acne {
+    acne(
+        scene,
+        eye,
+        curved,
+        |hit, direction| hit.spawn_ray(direction, MediumStack::air()),
+        |hit, target, normal, error| hit.spawn_ray_to(target, normal, error),
+    )
+}
+
+#[test]
+fn cornell_box_acne_test() {
+    let config = scene_file::load(Path::new("scenes/box.toml")).unwrap();
+    let eye = Point3::new(0.0, 0.3, -1.0);
+
+    // The glass ball and the light are the only curved objects.
+    let curved = [0, 6];
+    let before = naive(&config.scene, eye, &curved);
+    assert!(before.self_hits > 0 && before.shadowed > 0, "{:?}", before);
+
+    let after = robust(&config.scene, eye, &curved);
+    assert_eq!((after.self_hits, after.shadowed), (0, 0));
+}
+
+// A box made of triangles, far from the origin and turned so that its walls
+// aren't aligned with the axes, where floating point error is much larger.
+fn distant_box() -> Scene {
+    let diffuse = || {
+        MaterialBox::Reflective(Arc::new(PerfectDiffuseMaterial {
+            color: Box::new(ConstantTexture {
+                color: Vector3::repeat(0.8),
+            }),
+        }))
+    };
+    let corner = |i: usize| {
+        Point3::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { -1.0 } else { 1.0 },
+        )
+    };
+    let walls = [
+        [0, 1, 3, 2],
+        [4, 6, 7, 5],
+        [0, 4, 5, 1],
+        [2, 3, 7, 6],
+        [0, 2, 6, 4],
+        [1, 5, 7, 3],
+    ];
+
+    let placement = Transform::scaling(&Vector3::new(3.0, 2.0, 2.5))
+        .then(&Transform::rotation(&Vector3::new(1.0, 2.0, 0.5), 0.6))
+        .then(&Transform::translation(&Vector3::new(
+            1500.0, -700.0, 2300.0,
+        )));
+
+    let mut objects: Vec<Object> = walls
+        .iter()
+        .map(|wall| {
+            let mesh = TriangleMesh::new(
+                wall.iter().map(|&i| placement.point(&corner(i))).collect(),
+                Vec::new(),
+                Vec::new(),
+                vec![[0, 1, 2], [0, 2, 3]],
+            );
+            Object {
+                surface: Box::new(mesh),
+                material: diffuse(),
+            }
+        })
+        .collect();
+
+    let ball = Arc::new(Sphere {
+        center: Point3::origin(),
+        radius: 1.0,
+    });
+    objects.push(Object {
+        surface: Box::new(TransformedSurface::new(
+            ball.clone(),
+            Transform::scaling(&Vector3::new(0.4, 0.3, 0.3)).then(&placement),
+        )),
+        material: diffuse(),
+    });
+    objects.push(Object {
+        surface: Box::new(TransformedSurface::new(
+            ball,
+            Transform::scaling(&Vector3::repeat(0.1))
+                .then(&Transform::translation(&Vector3::new(0.3, 0.8, 0.2)))
+                .then(&placement),
+        )),
+        material: MaterialBox::Emissive(Arc::new(EmissiveMaterial {
+            emission: Box::new(ConstantTexture {
+                color: Vector3::repeat(10.0),
+            }),
+            one_sided: false,
+        })),
+    });
+
+    Scene::new(objects)
+}
+
+#[test]
+fn distant_box_acne_test() {
+    let scene = distant_box();
+    let eye = Point3::new(1500.0, -700.0, 2300.0) + Vector3::new(0.1, -0.2, 0.3);
+
+    // Far from the origin, bounces off the walls hit them again most of all.
+    let curved = [6, 7];
+    let before = naive(&scene, eye, &curved);
+    assert!(before.self_hits > 0, "{:?}", before);
+
+    let after = robust(&scene, eye, &curved);
+    assert_eq!((after.self_hits, after.shadowed), (0, 0));
+}
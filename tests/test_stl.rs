@@ -0,0 +1,161 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use renderer::import::{stl, ImportError};
+use renderer::ray::Ray;
+use renderer::surface::Surface;
+use std::path::Path;
+
+const FACET: &str = "
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+";
+
+// A binary file with the same triangles, in the same order.
+fn binary(triangles: &[[Point3<f32>; 3]]) -> Vec<u8> {
+    // Exporters often start the header like an ASCII file.
+    let mut contents = b"solid exported as binary".to_vec();
+    contents.resize(80, 0);
+    contents.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+
+    for triangle in triangles {
+        // The normal isn't used, so it doesn't need to be correct.
+        contents.extend_from_slice(&[0; 12]);
+        for vertex in triangle {
+            for &c in vertex.iter() {
+                contents.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        contents.extend_from_slice(&[0, 0]);
+    }
+
+    contents
+}
+
+#[test]
+fn load_ascii_test() {
+    let meshes = stl::load(Path::new("tests/data/tetrahedron.stl")).unwrap();
+    assert_eq!(meshes.len(), 1);
+
+    let tetrahedron = &meshes[0];
+    assert_eq!(tetrahedron.name, "tetrahedron");
+    assert!(tetrahedron.material.is_none());
+    assert_eq!(tetrahedron.mesh.indices().len(), 4);
+    assert_eq!(tetrahedron.mesh.positions().len(), 12);
+
+    // Triangles are wound to face out of the solid.
+    let ray = Ray::new_from_air(Point3::new(0.2, 0.2, -1.0), Vector3::z());
+    let hit = tetrahedron.mesh.intersects(&ray).unwrap();
+    assert_eq!(hit.distance, 1.0);
+    assert_eq!(hit.normal, -Vector3::z());
+}
+
+#[test]
+fn load_binary_test() {
+    let ascii = &stl::load(Path::new("tests/data/tetrahedron.stl")).unwrap()[0];
+    let triangles: Vec<[Point3<f32>; 3]> = ascii
+        .mesh
+        .positions()
+        .chunks(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .collect();
+
+    let meshes = stl::parse(&binary(&triangles), "tetrahedron").unwrap();
+    assert_eq!(meshes.len(), 1);
+    assert_eq!(meshes[0].name, "tetrahedron");
+    assert_eq!(meshes[0].mesh.positions(), ascii.mesh.positions());
+    assert_eq!(meshes[0].mesh.indices(), ascii.mesh.indices());
+}
+
+#[test]
+fn multiple_solids_test() {
+    let contents = format!(
+        "solid first part\n{}{}endsolid\nsolid\n{}endsolid\nsolid empty\nendsolid empty\n",
+        FACET, FACET, FACET
+    );
+    let meshes = stl::parse(contents.as_bytes(), "parts").unwrap();
+
+    // Solids without names are named after the file, and empty ones are left
+    // out.
+    let names: Vec<&str> = meshes.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["first part", "parts"]);
+    assert_eq!(meshes[0].mesh.indices().len(), 2);
+    assert_eq!(meshes[1].mesh.indices().len(), 1);
+}
+
+#[test]
+fn malformed_test() {
+    let solid = |facet: &str| format!("solid broken\n{}endsolid\n", facet);
+    let cases = [
+        (
+            "not an STL file".to_string(),
+            "the size of the file doesn't match its triangle count",
+        ),
+        ("solid broken\n".to_string(), "unexpected end of file"),
+        (
+            solid(&FACET.replace("normal 0 0 1", "normal 0 1")),
+            "line 3: expected `facet normal` and three numbers",
+        ),
+        (
+            solid(&FACET.replace("vertex 1 0 0", "vertex 1 0 zero")),
+            "line 6: expected `vertex` and three numbers",
+        ),
+        (
+            solid(&FACET.replace("      vertex 0 1 0\n", "")),
+            "line 7: a facet must have exactly three vertices",
+        ),
+        (
+            solid(&FACET.replace(
+                "      vertex 0 1 0\n",
+                "      vertex 0 1 0\n      vertex 1 1 0\n",
+            )),
+            "line 8: a facet must have exactly three vertices",
+        ),
+        (
+            solid(&FACET.replace("outer loop", "inner loop")),
+            "line 4: unexpected `inner`",
+        ),
+    ];
+
+    for &(ref contents, expected) in &cases {
+        match stl::parse(contents.as_bytes(), "broken") {
+            Err(ImportError::Stl(message)) => assert_eq!(message, expected),
+            _ => panic!("expected an error for {:?}", contents),
+        }
+    }
+
+    let triangle = [
+        Point3::origin(),
+        Point3::new(1.0, 0.0, 0.0),
+        Point3::new(0.0, 1.0, 0.0),
+    ];
+
+    // A binary file with a vertex which isn't a number.
+    let mut triangles = [triangle, triangle];
+    triangles[1][2].y = f32::NAN;
+    match stl::parse(&binary(&triangles), "broken") {
+        Err(ImportError::Stl(message)) => {
+            assert_eq!(message, "triangle 1: vertices must be finite numbers");
+        }
+        _ => panic!("expected an error for a NaN vertex"),
+    }
+
+    // A binary file with a triangle missing.
+    let mut truncated = binary(&[triangle, triangle]);
+    truncated.truncate(truncated.len() - 50);
+    match stl::parse(&truncated, "broken") {
+        Err(ImportError::Stl(message)) => {
+            assert_eq!(
+                message,
+                "the size of the file doesn't match its triangle count"
+            );
+        }
+        _ => panic!("expected an error for a truncated file"),
+    }
+}
@@ -3,11 +3,11 @@ extern crate nalgebra as na;
 extern crate rand;
 extern crate renderer;
 
-use na::Point3;
+use na::{Point2, Point3, Vector3};
 use rand::random;
 use renderer::ray::Ray;
-use renderer::surface::{Sphere, Surface};
-use std::f32::consts::{PI, FRAC_PI_2};
+use renderer::surface::{Plane, Sphere, Surface, Triangle};
+use std::f32::consts::{FRAC_PI_2, PI};
 
 #[test]
 fn sphere_test() {
@@ -63,3 +63,92 @@ fn sphere_test() {
     //     assert!(!sphere1.intersects(&ray));
     // }
 }
+
+#[test]
+fn sphere_from_inside_test() {
+    let sphere = Sphere {
+        center: Point3::new(1.0, 2.0, 3.0),
+        radius: 2.0,
+    };
+
+    // A ray starting inside the sphere hits its far side.
+    let ray = Ray::new_from_air(Point3::new(1.5, 2.0, 3.0), Vector3::new(1.0, 0.0, 0.0));
+    let intersection = sphere.intersects(&ray).unwrap();
+    assert!((intersection.distance - 1.5).abs() < 1e-5);
+    assert!((intersection.normal - Vector3::x()).norm() < 1e-5);
+
+    // A sphere entirely behind the ray isn't hit.
+    let ray = Ray::new_from_air(Point3::new(4.0, 2.0, 3.0), Vector3::new(1.0, 0.0, 0.0));
+    assert!(sphere.intersects(&ray).is_none());
+}
+
+#[test]
+fn sphere_uv_test() {
+    let sphere = Sphere {
+        center: Point3::new(1.0, 2.0, 3.0),
+        radius: 2.0,
+    };
+
+    // Texture coordinates run around the equator and up from the bottom pole.
+    let ray = Ray::new_from_air(Point3::new(1.0, 2.0, -3.0), Vector3::new(0.0, 0.0, 1.0));
+    let intersection = sphere.intersects(&ray).unwrap();
+    assert!((intersection.uv - Point2::new(0.75, 0.5)).norm() < 1e-6);
+
+    let ray = Ray::new_from_air(Point3::new(1.0, 10.0, 3.0), Vector3::new(0.0, -1.0, 0.0));
+    let intersection = sphere.intersects(&ray).unwrap();
+    assert!((intersection.uv.y - 1.0).abs() < 1e-6);
+
+    // Points sampled on the sphere have the same texture coordinates as rays
+    // hitting them.
+    let reference = Point3::new(4.0, 5.0, 6.0);
+    for _ in 0..100 {
+        let sample = sphere
+            .sample_towards(&reference, (random(), random()))
+            .unwrap();
+        let ray = Ray::new_from_air(reference, sample.position - reference);
+        let intersection = sphere.intersects(&ray).unwrap();
+        assert!((intersection.uv - sample.uv).norm() < 1e-3);
+    }
+}
+
+// A triangle's normal follows its winding order wherever it is, and is the
+// same from both sides.
+#[test]
+fn triangle_normal_test() {
+    for &offset in &[-10.0, 0.0, 10.0] {
+        let triangle = Triangle {
+            vertices: [
+                Point3::new(offset, 0.0, 0.0),
+                Point3::new(offset + 1.0, 0.0, 0.0),
+                Point3::new(offset, 1.0, 0.0),
+            ],
+        };
+        assert_eq!(triangle.normal(), Vector3::z());
+
+        for &side in &[-1.0, 1.0] {
+            let origin = Point3::new(offset + 0.25, 0.5, side);
+            let ray = Ray::new_from_air(origin, Vector3::new(0.0, 0.0, -side));
+            let hit = triangle.intersects(&ray).unwrap();
+
+            assert_eq!(hit.normal, Vector3::z());
+            assert_eq!(hit.barycentric, Some(Point2::new(0.25, 0.5)));
+        }
+    }
+}
+
+#[test]
+fn plane_normal_test() {
+    // Normals which aren't of unit length still give unit normals at
+    // intersections, with the plane in the same place.
+    let plane = Plane {
+        normal: Vector3::new(0.0, 2.0, 0.0),
+        offset: 1.0,
+    };
+    let ray = Ray::new_from_air(Point3::new(0.5, 3.0, -0.5), -Vector3::y());
+    let hit = plane.intersects(&ray).unwrap();
+
+    assert!((hit.distance - 2.0).abs() < 1e-6);
+    assert_eq!(hit.normal, Vector3::y());
+    assert_eq!(hit.geometric_normal, Vector3::y());
+    assert_eq!(plane.normal_towards(hit.position), Vector3::y());
+}
@@ -0,0 +1,155 @@
+extern crate image;
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{DMatrix, Point2, Point3, Vector2, Vector3};
+use renderer::texture::{
+    perlin, CheckerboardTexture, ConstantTexture, GradientTexture, ImageTexture, Texture,
+    TextureFilter, TexturePoint, WrapMode,
+};
+use renderer::tonemap::srgb_to_linear;
+use std::env;
+use std::fs;
+
+fn at(u: f32, v: f32, width: f32) -> TexturePoint {
+    TexturePoint {
+        position: Point3::origin(),
+        uv: Point2::new(u, v),
+        color: None,
+        width,
+    }
+}
+
+fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+    assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+}
+
+// A 2 x 2 image, with red at the top left, blue at the top right, green at the
+// bottom left and white at the bottom right.
+fn image(filter: TextureFilter, wrap: WrapMode) -> ImageTexture {
+    let pixels = DMatrix::from_row_slice(
+        2,
+        2,
+        &[
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+        ],
+    );
+    ImageTexture::new(pixels, filter, wrap)
+}
+
+#[test]
+fn image_texture_test() {
+    let red = Vector3::new(1.0, 0.0, 0.0);
+    let blue = Vector3::new(0.0, 0.0, 1.0);
+    let texture = image(TextureFilter::Bilinear, WrapMode::Repeat);
+    assert_eq!(texture.size(), (2, 2));
+    assert_eq!(texture.levels(), 1);
+
+    // Pixel centers are looked up exactly, with v increasing up the image.
+    assert_close(texture.evaluate(&at(0.25, 0.75, 0.0)), red);
+    assert_close(texture.evaluate(&at(0.75, 0.75, 0.0)), blue);
+
+    // Between them, pixels are blended.
+    let middle = texture.evaluate(&at(0.5, 0.5, 0.0));
+    assert_close(middle, Vector3::new(0.5, 0.5, 0.5));
+
+    // The nearest pixel isn't blended.
+    let nearest = image(TextureFilter::Nearest, WrapMode::Repeat);
+    assert_close(nearest.evaluate(&at(0.45, 0.55, 0.0)), red);
+
+    // Outside of the image, it repeats, is mirrored or clamped.
+    assert_close(texture.evaluate(&at(1.25, -0.25, 0.0)), red);
+    let mirror = image(TextureFilter::Bilinear, WrapMode::Mirror);
+    assert_close(mirror.evaluate(&at(1.25, 0.75, 0.0)), blue);
+    assert_close(mirror.evaluate(&at(-0.25, 0.75, 0.0)), red);
+    let clamp = image(TextureFilter::Bilinear, WrapMode::Clamp);
+    assert_close(clamp.evaluate(&at(5.0, 0.75, 0.0)), blue);
+    assert_close(clamp.evaluate(&at(-0.1, 0.75, 0.0)), red);
+}
+
+#[test]
+fn trilinear_filtering_test() {
+    // Alternating black and white columns.
+    let pixels = DMatrix::from_fn(64, 64, |x, _| Vector3::repeat((x % 2) as f32));
+    let texture = ImageTexture::new(pixels, TextureFilter::Trilinear, WrapMode::Repeat);
+    assert_eq!(texture.levels(), 7);
+
+    // Close up, the columns are sharp.
+    let white = texture.evaluate(&at(1.5 / 64.0, 0.5, 0.0));
+    assert_close(white, Vector3::repeat(1.0));
+
+    // From far away, when a pixel covers several columns, they are averaged to
+    // grey rather than picking one at random.
+    for &u in &[0.1, 0.37, 0.5] {
+        let grey = texture.evaluate(&at(u, 0.5, 0.1));
+        assert_close(grey, Vector3::repeat(0.5));
+    }
+
+    // Beyond the width of the whole texture, it is the average.
+    assert_close(texture.evaluate(&at(0.3, 0.2, 10.0)), Vector3::repeat(0.5));
+}
+
+#[test]
+fn image_texture_file_test() {
+    let path = env::temp_dir().join(format!("renderer-test-{}-texture.png", std::process::id()));
+    let mut image = image::RgbImage::new(2, 1);
+    image.put_pixel(0, 0, image::Rgb([255, 128, 0]));
+    image.put_pixel(1, 0, image::Rgb([0, 0, 0]));
+    image.save(&path).unwrap();
+
+    // Colors are decoded from sRGB to linear.
+    let texture = ImageTexture::open(&path, TextureFilter::Bilinear, WrapMode::Clamp).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(texture.size(), (2, 1));
+    let expected = Vector3::new(1.0, srgb_to_linear(128.0 / 255.0), 0.0);
+    assert_close(texture.evaluate(&at(0.25, 0.5, 0.0)), expected);
+}
+
+#[test]
+fn procedural_texture_test() {
+    let (black, white) = (Vector3::zeros(), Vector3::repeat(1.0));
+    let checkerboard = CheckerboardTexture {
+        even: Box::new(ConstantTexture { color: black }),
+        odd: Box::new(ConstantTexture { color: white }),
+    };
+    assert_close(checkerboard.evaluate(&at(0.5, 0.5, 0.0)), black);
+    assert_close(checkerboard.evaluate(&at(1.5, 0.5, 0.0)), white);
+    assert_close(checkerboard.evaluate(&at(-0.5, 0.5, 0.0)), white);
+
+    // Within a square, filtering doesn't blend in the other color, but across
+    // the edge of one it does.
+    assert_close(checkerboard.evaluate(&at(0.5, 0.5, 0.5)), black);
+    let edge = checkerboard.evaluate(&at(1.0, 0.5, 0.5));
+    assert_close(edge, Vector3::repeat(0.5));
+    assert_close(
+        checkerboard.evaluate(&at(0.3, 0.8, 4.0)),
+        Vector3::repeat(0.5),
+    );
+
+    let gradient = GradientTexture {
+        from: black,
+        to: white,
+        direction: Vector2::new(0.0, 0.5),
+    };
+    assert_close(gradient.evaluate(&at(0.7, 1.0, 0.0)), Vector3::repeat(0.5));
+    assert_close(gradient.evaluate(&at(0.7, 3.0, 0.0)), white);
+    assert_close(gradient.evaluate(&at(0.7, -1.0, 0.0)), black);
+
+    // Noise is zero on the lattice, and varies smoothly and within bounds
+    // between.
+    assert_eq!(perlin(&Point3::new(3.0, -2.0, 7.0)), 0.0);
+    let mut previous = perlin(&Point3::new(0.0, 0.3, 0.6));
+    let mut varies = false;
+    for i in 1..1000 {
+        let value = perlin(&Point3::new(i as f32 * 0.01, 0.3, 0.6));
+        assert!(value.abs() <= 1.1);
+        assert!((value - previous).abs() < 0.05);
+        varies |= (value - previous).abs() > 1e-3;
+        previous = value;
+    }
+    assert!(varies);
+}
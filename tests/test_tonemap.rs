@@ -0,0 +1,97 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::Vector3;
+use renderer::tonemap::{self, ToneMapOperator, ToneMapping};
+
+fn grey(v: f32) -> Vector3<f32> {
+    Vector3::new(v, v, v)
+}
+
+#[test]
+fn srgb_round_trip_test() {
+    assert_eq!(tonemap::linear_to_srgb(0.0), 0.0);
+    assert!((tonemap::linear_to_srgb(1.0) - 1.0).abs() < 1e-6);
+
+    // Middle grey is encoded well above half of the 8-bit range.
+    assert!((tonemap::linear_to_srgb(0.18) * 255.0 - 117.9).abs() < 0.5);
+
+    for i in 0..=100 {
+        let c = i as f32 / 100.0;
+        let back = tonemap::srgb_to_linear(tonemap::linear_to_srgb(c));
+        assert!((back - c).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn operators_compress_highlights_test() {
+    for &operator in &[
+        ToneMapOperator::Clamp,
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::Aces,
+    ] {
+        let tonemap = ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        };
+
+        // Monotonic and within the displayable range.
+        let mut previous = 0.0;
+        for i in 0..200 {
+            let mapped = tonemap.map(grey(i as f32 * 0.25)).x;
+            assert!(mapped >= previous && mapped <= 1.0);
+            previous = mapped;
+        }
+
+        assert_eq!(tonemap.map(grey(0.0)), grey(0.0));
+        assert_eq!(tonemap.map(grey(-1.0)), grey(0.0));
+    }
+
+    // Unlike clamping, the curves keep detail in highlights.
+    for &operator in &[ToneMapOperator::Reinhard, ToneMapOperator::Aces] {
+        let tonemap = ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        };
+        assert!(tonemap.map(grey(2.0)).x < tonemap.map(grey(4.0)).x);
+    }
+}
+
+#[test]
+fn exposure_test() {
+    let tonemap = ToneMapping {
+        exposure: 1.0,
+        ..ToneMapping::default()
+    };
+
+    assert_eq!(tonemap.map(grey(0.25)), grey(0.5));
+    assert_eq!(tonemap.to_srgb8(grey(0.5), 0, 0), [255, 255, 255]);
+}
+
+#[test]
+fn dither_preserves_average_test() {
+    let tonemap = ToneMapping {
+        dither: true,
+        ..ToneMapping::default()
+    };
+
+    // A value halfway between two 8-bit levels should be dithered between
+    // them, averaging out to the original value.
+    let value = tonemap::srgb_to_linear(100.5 / 255.0);
+
+    let mut sum = 0.0;
+    for y in 0..64 {
+        for x in 0..64 {
+            let pixel = tonemap.to_srgb8(grey(value), x, y);
+            assert!(pixel[0] >= 99 && pixel[0] <= 102);
+            sum += pixel[0] as f32;
+        }
+    }
+    assert!((sum / 4096.0 - 100.5).abs() < 0.1);
+
+    // The noise only depends on the pixel position.
+    assert_eq!(
+        tonemap.to_srgb8(grey(value), 3, 7),
+        tonemap.to_srgb8(grey(value), 3, 7)
+    );
+}
@@ -0,0 +1,98 @@
+extern crate nalgebra as na;
+extern crate renderer;
+
+use na::{Point3, Vector3};
+use renderer::aabb::Aabb;
+use renderer::ray::Ray;
+use renderer::surface::{Sphere, Surface};
+use renderer::transform::{Transform, TransformedSurface};
+use std::f32::consts::FRAC_PI_2;
+use std::sync::Arc;
+
+fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+    assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn transform_test() {
+    // Scale, then a quarter turn about y, then move up.
+    let transform = Transform::scaling(&Vector3::new(2.0, 1.0, 1.0))
+        .then(&Transform::rotation(&Vector3::y(), FRAC_PI_2))
+        .then(&Transform::translation(&Vector3::new(0.0, 3.0, 0.0)));
+
+    let point = transform.point(&Point3::new(1.0, 0.0, 0.0));
+    assert_close(point.coords, Vector3::new(0.0, 3.0, -2.0));
+
+    // Directions aren't moved, and the inverse undoes the whole thing.
+    assert_close(
+        transform.vector(&Vector3::x()),
+        Vector3::new(0.0, 0.0, -2.0),
+    );
+    let back = transform.inverse().point(&point);
+    assert_close(back.coords, Vector3::x());
+
+    // Normals stay perpendicular to a stretched surface, here the plane
+    // x + y = 0.
+    let scale = Transform::scaling(&Vector3::new(2.0, 1.0, 1.0));
+    let along = scale.vector(&Vector3::new(1.0, -1.0, 0.0));
+    let normal = scale.normal(&Vector3::new(1.0, 1.0, 0.0));
+    assert!(along.dot(&normal).abs() < 1e-6);
+
+    let bounds = transform.bounds(&Aabb::from_points(&[
+        Point3::origin(),
+        Point3::new(1.0, 1.0, 1.0),
+    ]));
+    assert_close(bounds.min.coords, Vector3::new(0.0, 3.0, -2.0));
+    assert_close(bounds.max.coords, Vector3::new(1.0, 4.0, 0.0));
+
+    assert!(Transform::new(na::Matrix4::zeros()).is_none());
+}
+
+#[test]
+fn transformed_surface_test() {
+    // A unit sphere scaled up and moved away is the same as a bigger sphere
+    // there to begin with.
+    let unit = Arc::new(Sphere {
+        center: Point3::origin(),
+        radius: 1.0,
+    });
+    let transform = Transform::scaling(&Vector3::repeat(2.0))
+        .then(&Transform::translation(&Vector3::new(0.0, 0.0, 5.0)));
+    let transformed = TransformedSurface::new(unit, transform);
+    let sphere = Sphere {
+        center: Point3::new(0.0, 0.0, 5.0),
+        radius: 2.0,
+    };
+
+    let ray = Ray::new_from_air(Point3::new(0.5, 0.3, 0.0), Vector3::z());
+    let expected = sphere.intersects(&ray).unwrap();
+    let hit = transformed.intersects(&ray).unwrap();
+    assert!((hit.distance - expected.distance).abs() < 1e-4);
+    assert_close(hit.position.coords, expected.position.coords);
+    assert_close(hit.normal, expected.normal);
+    assert!((hit.uv - expected.uv).norm() < 1e-5);
+    assert!((hit.uv_density - expected.uv_density).abs() < 1e-4);
+
+    let bounds = transformed.bounds().unwrap();
+    assert_close(bounds.min.coords, Vector3::new(-2.0, -2.0, 3.0));
+    assert_close(bounds.max.coords, Vector3::new(2.0, 2.0, 7.0));
+
+    let miss = Ray::new_from_air(Point3::new(2.5, 0.0, 0.0), Vector3::z());
+    assert!(transformed.intersects(&miss).is_none());
+
+    // Stretched along z, the sphere becomes an ellipsoid whose normals lean
+    // away from the long axis.
+    let ellipsoid = TransformedSurface::new(
+        Arc::new(Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+        }),
+        Transform::scaling(&Vector3::new(1.0, 1.0, 3.0)),
+    );
+    let diagonal = Vector3::new(1.0, 0.0, 1.0).normalize();
+    let ray = Ray::new_from_air(Point3::from(diagonal * -10.0), diagonal);
+    let hit = ellipsoid.intersects(&ray).unwrap();
+    let p = hit.position.coords;
+    assert!((p.x * p.x + p.z * p.z / 9.0 - 1.0).abs() < 1e-4);
+    assert_close(hit.normal, Vector3::new(p.x, 0.0, p.z / 9.0).normalize());
+}